@@ -0,0 +1,55 @@
+import { spawn } from 'node:child_process'
+import { readFileSync } from 'node:fs'
+import { createInterface } from 'node:readline'
+
+import test from 'ava'
+
+import { LicenseClient } from '../index.js'
+
+// Runs conformance/scenarios.json against the napi build, like tests/conformance.rs does
+// against the Rust client. Needs CHIPA_MOCK_SERVER pointing at a built chipa-mock-server.
+const MOCK_SERVER = process.env.CHIPA_MOCK_SERVER
+const cases = JSON.parse(readFileSync(new URL('../conformance/scenarios.json', import.meta.url)))
+
+function startMockServer() {
+  const server = spawn(MOCK_SERVER, ['--port', '0'], { stdio: ['ignore', 'pipe', 'inherit'] })
+  const lines = createInterface({ input: server.stdout })
+  return new Promise((resolve, reject) => {
+    let url
+    const licenses = {}
+    server.once('error', reject)
+    server.once('exit', (code) => reject(new Error(`chipa-mock-server exited with ${code}`)))
+    lines.on('line', (line) => {
+      const listening = line.match(/^Listening on (\S+)/)
+      if (listening) {
+        url = listening[1]
+        return
+      }
+      const [name, license] = line.trim().split(/\s+/)
+      licenses[name] = license
+      if (url && cases.every((c) => !c.scenario || licenses[c.scenario])) {
+        resolve({ server, url, licenses })
+      }
+    })
+  })
+}
+
+async function run(client, licenses, c) {
+  const license = c.scenario ? licenses[c.scenario] : c.license
+  try {
+    return { token: await client.validateLicense(license, c.application) }
+  } catch (e) {
+    return { error: e.kind, remediation: e.remediation?.action }
+  }
+}
+
+const conformance = MOCK_SERVER ? test.serial : test.serial.skip
+
+conformance('conformance scenarios', async (t) => {
+  const { server, url, licenses } = await startMockServer()
+  t.teardown(() => server.kill())
+  const client = new LicenseClient(url)
+  for (const c of cases) {
+    t.deepEqual(await run(client, licenses, c), c.expect, c.name)
+  }
+})
@@ -0,0 +1,49 @@
+import { createServer } from 'node:net'
+
+import test from 'ava'
+
+import { LicenseClient } from '../index.js'
+
+// A URL nothing listens on, so every attempt fails with a network error.
+function unreachableUrl() {
+  const server = createServer()
+  return new Promise((resolve) => {
+    server.listen(0, '127.0.0.1', () => {
+      const { port } = server.address()
+      server.close(() => resolve(`http://127.0.0.1:${port}`))
+    })
+  })
+}
+
+test('retries report each attempt and the total', async (t) => {
+  const client = new LicenseClient(await unreachableUrl(), {
+    retries: { maxAttempts: 3, baseDelayMs: 10, maxDelayMs: 15, retryOn: ['network'] },
+  })
+  const events = []
+  client.onRetry((event) => events.push(event))
+
+  const error = await t.throwsAsync(
+    client.validateLicense('550e8400-e29b-41d4-a716-446655440000', 'my-app'),
+  )
+  t.is(error.code, 'Network')
+  t.is(error.attempts, 3)
+
+  // The callback is queued without blocking the request, let it run.
+  await new Promise((resolve) => setImmediate(resolve))
+  t.deepEqual(events, [
+    { attempt: 1, delayMs: 10, errorCode: 'network' },
+    { attempt: 2, delayMs: 15, errorCode: 'network' },
+  ])
+})
+
+test('an unknown retryOn is rejected', (t) => {
+  const error = t.throws(
+    () => new LicenseClient('http://127.0.0.1', { retries: { retryOn: ['4xx'] } }),
+  )
+  t.regex(error.message, /Invalid retryOn '4xx'/)
+})
+
+test('options must be an object', (t) => {
+  t.throws(() => new LicenseClient('http://127.0.0.1', 4))
+  t.notThrows(() => new LicenseClient('http://127.0.0.1', { maxConcurrency: 4 }))
+})
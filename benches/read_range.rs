@@ -0,0 +1,47 @@
+//! Times `ChipaReader::read_range` fetching 1 KB from a 1 GB streamed `.chipa` file,
+//! against decrypting the whole file. The ranges straddle a chunk boundary, the worst
+//! case, which opens two chunks.
+//!
+//! Usage: `cargo bench --bench read_range`
+
+use std::{
+    io::{self, Read},
+    time::{Duration, Instant},
+};
+
+use chipa_license_validator::{ChipaFile, ChipaReader, CHUNK_SIZE};
+
+const BODY: u64 = 1 << 30;
+const RANGE: usize = 1024;
+const ROUNDS: u64 = 20;
+const KEY: &str = "bench key";
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("dataset.chipa").to_string_lossy().into_owned();
+    ChipaFile::save_stream(&path, KEY, io::repeat(7).take(BODY))?;
+
+    let start = Instant::now();
+    let mut reader = ChipaReader::open(&path, KEY)?;
+    println!("open, {} chunks indexed: {:?}", BODY / CHUNK_SIZE as u64, start.elapsed());
+
+    let mut slowest = Duration::ZERO;
+    let start = Instant::now();
+    for round in 1..=ROUNDS {
+        let boundary = round * (BODY / (ROUNDS + 1)) / CHUNK_SIZE as u64 * CHUNK_SIZE as u64;
+        let started = Instant::now();
+        let range = reader.read_range(boundary - RANGE as u64 / 2, RANGE)?;
+        slowest = slowest.max(started.elapsed());
+        assert_eq!(range.len(), RANGE);
+    }
+    println!(
+        "read_range, 1 KB: {:?} on average, {:?} at most",
+        start.elapsed() / ROUNDS as u32,
+        slowest
+    );
+
+    let start = Instant::now();
+    ChipaFile::load_stream(&path, KEY, io::sink())?;
+    println!("load_stream, 1 GB: {:?}", start.elapsed());
+    Ok(())
+}
@@ -1,5 +1,34 @@
 extern crate napi_build;
 
+use std::{
+    collections::hash_map::RandomState,
+    env, fs,
+    hash::{BuildHasher, Hasher},
+    path::PathBuf,
+};
+
 fn main() {
     napi_build::setup();
+    generate_obfuscation_key();
+}
+
+fn generate_obfuscation_key() {
+    let state = RandomState::new();
+    let key: Vec<String> = (0..4)
+        .flat_map(|i| {
+            let mut hasher = state.build_hasher();
+            hasher.write_usize(i);
+            hasher.finish().to_le_bytes()
+        })
+        .map(|b| b.to_string())
+        .collect();
+    let out = PathBuf::from(env::var("OUT_DIR").unwrap()).join("obfuscation_key.rs");
+    fs::write(
+        out,
+        format!(
+            "pub const OBFUSCATION_KEY: [u8; 32] = [{}];\n",
+            key.join(", ")
+        ),
+    )
+    .unwrap();
 }
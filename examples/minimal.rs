@@ -0,0 +1,19 @@
+//! Opens a `.chipa` file with nothing but the crypto core. CI builds this with
+//! `--no-default-features` to keep an eye on what the minimal profile links.
+//!
+//! Usage: `minimal <file.chipa> <key>`
+
+use chipa_license_validator::ChipaFile;
+use serde_json::Value;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = std::env::args().skip(1);
+    let (Some(path), Some(key)) = (args.next(), args.next()) else {
+        eprintln!("usage: minimal <file.chipa> <key>");
+        std::process::exit(2);
+    };
+    let data = std::fs::read(path)?;
+    let file = ChipaFile::from_bytes(&data, &key)?;
+    println!("{}", file.read::<Value>()?);
+    Ok(())
+}
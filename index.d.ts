@@ -3,6 +3,123 @@
 
 /* auto-generated by NAPI-RS */
 
+/** The undecoded response of a license validation. */
+export interface RawValidation {
+  /** The HTTP status code returned by the license server. */
+  status: number
+  /**
+   * The decrypted response body, or `null` if the server sent none. It contains
+   * the license token in clear, so keep it out of logs and error reports.
+   */
+  body: any
+}
+/** Options accepted by the `LicenseClient` constructor. Every field is optional. */
+export interface ClientOptions {
+  /**
+   * Maximum number of requests the client has in flight at once. Further requests
+   * wait for a free slot instead of failing. Defaults to no limit.
+   */
+  maxConcurrency?: number
+  /**
+   * Milliseconds each attempt may take, from sending the request to reading the
+   * whole response. Defaults to no timeout.
+   */
+  timeoutMs?: number
+  /**
+   * Milliseconds to wait for a connection to the license server. Defaults to no
+   * timeout.
+   */
+  connectTimeoutMs?: number
+  /** How validations and seat queries are retried. Defaults to no retries. */
+  retries?: RetryOptions
+  /**
+   * Milliseconds a request may take with all of its retries and backoff. Past it
+   * the request fails with the `Network` code and kind `deadline_exceeded`.
+   * Defaults to no deadline.
+   */
+  operationTimeoutMs?: number
+  /**
+   * Extra headers sent with every request. `Authorization`, `Content-Type` and the
+   * protocol version header cannot be overridden.
+   */
+  headers?: Record<string, string>
+  /**
+   * A name for this machine, e.g. the device name the user chose, sent with
+   * activations. Other machines see it in `SeatInfo.machineLabel`.
+   */
+  machineLabel?: string
+}
+/**
+ * How a `LicenseClient` retries validations and seat queries. Every field is
+ * optional.
+ */
+export interface RetryOptions {
+  /** Attempts per request, the first one included. Defaults to 1, no retries. */
+  maxAttempts?: number
+  /**
+   * Milliseconds to wait before the first retry, doubled for each one after.
+   * Defaults to 200.
+   */
+  baseDelayMs?: number
+  /** Milliseconds the wait before a retry never exceeds. Defaults to no cap. */
+  maxDelayMs?: number
+  /**
+   * Which failures are retried: "network" for an unreachable server or a timeout,
+   * "5xx" and "429", which waits at least its `Retry-After`. Defaults to
+   * `["network", "5xx"]`.
+   */
+  retryOn?: Array<'network' | '5xx' | '429'>
+}
+/** A retry about to be made, passed to the `onRetry` callback. */
+export interface RetryEvent {
+  /** The attempt that failed, counting from 1. */
+  attempt: number
+  /** Milliseconds until the retry. */
+  delayMs: number
+  /** What failed: "network", "5xx" or "429". */
+  errorCode: string
+}
+/** A single seat occupancy sample. */
+export interface SeatSample {
+  /** Unix timestamp, in seconds, at which the sample was taken. */
+  timestamp: number
+  /** Number of seats in use at that time. */
+  used: number
+}
+/** Seat occupancy of a license. */
+export interface SeatUsage {
+  /** Number of seats currently in use, or `null` if unknown. */
+  used?: number
+  /** Number of seats the license allows, or `null` if unknown. */
+  total?: number
+  /** Recent samples, oldest first, if the server provides them. */
+  samples: Array<SeatSample>
+}
+/** A seat of a license held by this machine. */
+export interface Activation {
+  /** The token to pass to `heartbeat`. */
+  token: string
+  /** Milliseconds between heartbeats, or `null` if the server does not expire seats. */
+  heartbeatIntervalMs?: number
+  /**
+   * The id other machines see in `SeatInfo.id`, or `null` if the server does not
+   * list seats.
+   */
+  activationId?: string
+}
+/**
+ * A seat held by another machine, listed in the `seats` of a "SeatLimitReached"
+ * error.
+ */
+export interface SeatInfo {
+  /** The activation holding the seat, to pass to `deactivateActivation`. */
+  id: string
+  /** The `machineLabel` the machine was activated with, if any. */
+  machineLabel?: string
+  /** Unix timestamp, in seconds, of the machine's last activation or heartbeat. */
+  lastSeen: number
+}
+
 /**
  * A client for validating licenses against the Chipa License Server.
  *
@@ -31,11 +148,46 @@ export declare class LicenseClient {
    *
    * # Arguments
    * * `baseUrl` - The base URL of the license server (e.g., "https://license.example.com")
+   * * `options` - Optional `ClientOptions` with timeouts, retries, extra headers and
+   *   `maxConcurrency`.
    *
    * # Returns
    * A new `LicenseClient` instance configured with the specified base URL.
+   *
+   * # Throws
+   * Throws an error if `maxConcurrency` is 0 or a header name or value is invalid.
+   *
+   * # Example
+   * ```typescript
+   * const client = new LicenseClient("https://license.example.com", {
+   *     timeoutMs: 5000,
+   *     retries: { maxAttempts: 4, retryOn: ["network", "5xx", "429"] },
+   *     headers: { "X-Tenant": "acme" },
+   * });
+   * ```
+   */
+  constructor(baseUrl: string, options?: ClientOptions | undefined | null)
+  /**
+   * Calls `callback` before every retry with a `RetryEvent`. It is queued on the
+   * event loop, so it never holds up the request, and it does not keep the
+   * process alive. Pass `null` to remove it.
+   *
+   * # Example
+   * ```typescript
+   * const client = new LicenseClient(url, { retries: { maxAttempts: 3 } });
+   * client.onRetry(({ attempt, delayMs, errorCode }) => {
+   *     console.warn(`attempt ${attempt} failed (${errorCode}), retrying in ${delayMs}ms`);
+   * });
+   * ```
+   */
+  onRetry(callback: ((event: RetryEvent) => void) | null): void
+  /**
+   * The number of requests this client currently has in flight.
+   *
+   * Requests waiting for a slot under `maxConcurrency` are not counted, so this
+   * never exceeds the limit.
    */
-  constructor(baseUrl: string)
+  get inFlight(): number
   /**
    * Updates the base URL of the license server.
    *
@@ -46,22 +198,271 @@ export declare class LicenseClient {
    * A new `LicenseClient` instance with the updated URL configuration.
    */
   static setUrl(url: string): LicenseClient
+  /**
+   * Releases this client's hold on its shared connection pool.
+   *
+   * Clients with the same base URL share one connection pool, which is freed once
+   * no client uses it. Calling `close` releases this client's share immediately
+   * instead of waiting for garbage collection. A closed client can still be used;
+   * it reacquires a pool for the duration of each request.
+   */
+  close(): void
   /**
    * Validates a license key for a specific application.
    *
    * # Arguments
-   * * `license` - The UUID of the license to validate
+   * * `license` - The license to validate, either a UUID or a `CHIPA-XXXX-XXXX-XXXX-XXXX` key
    * * `application` - The identifier of the application requesting validation
+   * * `cachePath` - Optional path of an encrypted file keeping the last successful
+   *   validation. It is refreshed on every successful validation and used only when
+   *   the server cannot be reached. Requires `graceSeconds`.
+   * * `graceSeconds` - How old, in seconds, a cached validation may be to stand in
+   *   for the server. Requires `cachePath`.
    *
    * # Returns
    * A Promise that resolves to a validation token string if successful.
    *
    * # Throws
    * Throws an error if:
-   * - The license UUID is invalid
-   * - The server cannot be reached
-   * - The license is invalid or expired
+   * - The license is neither a valid UUID nor a valid key
+   * - The server cannot be reached and there is no cached validation within the grace period
+   * - The license is invalid or expired, even if a cached validation exists
    * - The application is not authorized
+   * - The license server no longer supports this client's protocol version. The
+   *   message starts with "Client too old" and ends with the download URL when the
+   *   server provides one
+   * - Only one of `cachePath` and `graceSeconds` is given, or `graceSeconds` is negative
+   *
+   * Errors from the license server have a `code` to switch on: "LicenseExpired",
+   * "LicenseNotFound", "ApplicationUnauthorized", "Network", "ClientTooOld",
+   * "UnsupportedByServer" or "GenericFailure", and a numeric `status` when the
+   * server answered. Seat activations add "SeatLimitReached" and "UnknownMachine",
+   * and transfers "TransferCooldown".
+   * Every error, a malformed license included, also has the finer `kind` used by
+   * the Rust and Python libraries, e.g. "invalid_license", and a `remediation`
+   * whose `action` is e.g. "renew", with `portalUrl`, or "retry_after", with
+   * `retryAfter` in seconds. `attempts` counts the requests made, retries included.
+   *
+   * # Example
+   * ```typescript
+   * // Keep working for up to a day without network.
+   * const token = await client.validateLicense(license, "my-app", "./license.chipa", 24 * 3600);
+   *
+   * try {
+   *     await client.validateLicense(license, "my-app");
+   * } catch (error) {
+   *     switch (error.code) {
+   *         case "LicenseExpired": showRenewDialog(); break;
+   *         case "Network": showOfflineBanner(); break;
+   *         default: throw error;
+   *     }
+   * }
+   * ```
+   */
+  validateLicense(license: string, application: string, cachePath?: string | undefined | null, graceSeconds?: number | undefined | null): Promise<string>
+  /**
+   * Validates a license key and returns the server's response without interpreting it.
+   *
+   * Unlike `validateLicense`, this does not fail on an error status or on fields
+   * the client does not know about, which makes it possible to read experimental
+   * response fields before the typed API supports them.
+   *
+   * # Arguments
+   * * `license` - The license to validate, either a UUID or a `CHIPA-XXXX-XXXX-XXXX-XXXX` key
+   * * `application` - The identifier of the application requesting validation
+   *
+   * # Returns
+   * A Promise that resolves to `{ status, body }`, where `body` is the decrypted
+   * JSON response or `null` if the server sent an empty body.
+   *
+   * # Throws
+   * Throws an error if the license is malformed, the server cannot be reached
+   * (`code` "Network"), or the response body cannot be decrypted or is not JSON.
+   */
+  validateLicenseRaw(license: string, application: string): Promise<RawValidation>
+  /**
+   * Fetches the seat occupancy of a license.
+   *
+   * # Arguments
+   * * `license` - The license to query, either a UUID or a `CHIPA-XXXX-XXXX-XXXX-XXXX` key
+   * * `lenient` - When `true`, servers that do not support seat reporting yield an
+   *   unknown usage (`used` and `total` are `null`) instead of an error
+   *
+   * # Returns
+   * A Promise that resolves to the current `SeatUsage`.
+   *
+   * # Throws
+   * Throws an error if the license is malformed, the server cannot be reached,
+   * or the server rejects the request. The error has the same `code` and
+   * `status` as those of `validateLicense`.
+   */
+  seatUsage(license: string, lenient?: boolean | undefined | null): Promise<SeatUsage>
+  /**
+   * Takes a seat of a seat-limited license for this machine.
+   *
+   * Activating a machine that already holds a seat refreshes its token.
+   *
+   * # Arguments
+   * * `license` - The license to activate, either a UUID or a `CHIPA-XXXX-XXXX-XXXX-XXXX` key
+   * * `application` - The identifier of the application requesting the seat
+   * * `machineId` - Optional identifier of the machine. Defaults to a hash of the
+   *   machine id or volume serial, which never leaves the machine in clear
+   *
+   * # Returns
+   * A Promise that resolves to the `Activation`, whose `token` is sent to
+   * `heartbeat` every `heartbeatIntervalMs`.
+   *
+   * # Throws
+   * Throws an error with `code` "SeatLimitReached" if every seat is held by other
+   * machines, "UnsupportedByServer" if the server has no seat-limited licenses,
+   * or one of the codes of `validateLicense`. A "SeatLimitReached" error lists the
+   * seats in `seats`, an array of `SeatInfo` that is empty if the server does not
+   * send them.
+   *
+   * # Example
+   * ```typescript
+   * const seat = await client.activateLicense(license, "my-app");
+   * let token = seat.token;
+   * setInterval(async () => {
+   *     token = (await client.heartbeat(license, "my-app", token)).token;
+   * }, seat.heartbeatIntervalMs ?? 300_000);
+   * ```
+   */
+  activateLicense(license: string, application: string, machineId?: string | undefined | null): Promise<Activation>
+  /**
+   * Frees the seat held by this machine, e.g. when the user signs out.
+   *
+   * # Arguments
+   * * `license` - The activated license, either a UUID or a `CHIPA-XXXX-XXXX-XXXX-XXXX` key
+   * * `application` - The identifier of the application holding the seat
+   * * `machineId` - Optional identifier of the machine, the same as given to
+   *   `activateLicense`
+   *
+   * # Throws
+   * Throws an error with `code` "UnknownMachine" if the machine holds no seat, or
+   * one of the codes of `activateLicense`.
+   */
+  deactivateLicense(license: string, application: string, machineId?: string | undefined | null): Promise<void>
+  /**
+   * Frees the seat of another machine, e.g. a stale one listed in the `seats` of a
+   * "SeatLimitReached" error.
+   *
+   * # Arguments
+   * * `license` - The activated license, either a UUID or a `CHIPA-XXXX-XXXX-XXXX-XXXX` key
+   * * `application` - The identifier of the application holding the seat
+   * * `activationId` - The `id` of the seat's `SeatInfo`
+   *
+   * # Throws
+   * Throws an error with `code` "UnknownMachine" if the seat is already free, or
+   * one of the codes of `activateLicense`.
+   *
+   * # Example
+   * ```typescript
+   * try {
+   *     await client.activateLicense(license, "my-app");
+   * } catch (error) {
+   *     if (error.code !== "SeatLimitReached") throw error;
+   *     const stale = await pickSeatToFree(error.seats);
+   *     await client.deactivateActivation(license, "my-app", stale.id);
+   *     await client.activateLicense(license, "my-app");
+   * }
+   * ```
+   */
+  deactivateActivation(license: string, application: string, activationId: string): Promise<void>
+  /**
+   * Moves the seat of another machine to this one in a single server call, so there
+   * is no moment when neither machine holds it, e.g. when the user replaces a
+   * laptop. The old machine's token stops working.
+   *
+   * # Arguments
+   * * `license` - The activated license, either a UUID or a `CHIPA-XXXX-XXXX-XXXX-XXXX` key
+   * * `application` - The identifier of the application holding the seat
+   * * `fromActivation` - The `activationId` of the seat to move
+   * * `machineId` - Optional identifier of this machine, as for `activateLicense`
+   * * `force` - When `true`, asks the server to move the seat even though the old
+   *   machine has not released it, e.g. because it is broken. Servers allow that
+   *   only so often
+   *
+   * # Returns
+   * A Promise that resolves to this machine's `Activation`.
+   *
+   * # Throws
+   * Throws an error with `code` "TransferCooldown" if a forced transfer was made
+   * too recently, with `remediation.retryAfter` in seconds when the server says,
+   * "UnknownMachine" if the seat to move is already free, or one of the codes of
+   * `activateLicense`.
+   */
+  transferLicense(license: string, application: string, fromActivation: string, machineId?: string | undefined | null, force?: boolean | undefined | null): Promise<Activation>
+  /**
+   * Confirms that this machine still holds its seat and refreshes the token.
+   *
+   * # Arguments
+   * * `license` - The activated license, either a UUID or a `CHIPA-XXXX-XXXX-XXXX-XXXX` key
+   * * `application` - The identifier of the application holding the seat
+   * * `token` - The token of the last activation or heartbeat
+   * * `lenient` - When `true`, servers without seat-limited licenses hand back
+   *   `token` instead of an error
+   *
+   * # Returns
+   * A Promise that resolves to the refreshed `Activation`.
+   *
+   * # Throws
+   * Throws an error with `code` "UnknownMachine" once the server has freed the
+   * seat, or one of the codes of `activateLicense`.
+   */
+  heartbeat(license: string, application: string, token: string, lenient?: boolean | undefined | null): Promise<Activation>
+}
+/**
+ * An encrypted `.chipa` file holding any JSON-compatible value.
+ *
+ * The same files are read and written by the Rust and Python libraries, so
+ * configuration encrypted by one can be shipped to the others.
+ *
+ * `load` and `save` block the event loop while the file is read or written and
+ * decrypted or encrypted. Use `loadAsync` and `saveAsync` for large files or on a
+ * server, they do the work on the libuv thread pool.
+ *
+ * # Example
+ * ```typescript
+ * const file = new ChipaFile({ strategy: "momentum", risk: 0.02 });
+ * file.save("strategy.chipa", key);
+ *
+ * const strategy = ChipaFile.load("strategy.chipa", key).read();
+ * console.log(strategy.risk);
+ * ```
+ */
+export declare class ChipaFile {
+  /**
+   * Creates a file holding `data`, which defaults to `null`.
+   *
+   * # Throws
+   * Throws an error if `data` is not JSON-compatible, e.g. a function or a symbol.
+   */
+  constructor(data?: unknown | undefined | null)
+  /**
+   * Reads and decrypts the file at `path` with `key`.
+   *
+   * # Throws
+   * Throws an error if the file cannot be read, or with a message starting with
+   * "Decryption error" if `key` is not the key it was saved with.
+   */
+  static load(path: string, key: string): ChipaFile
+  /** Like `load`, without blocking the event loop. */
+  static loadAsync(path: string, key: string): Promise<ChipaFile>
+  /** Encrypts the file with `key` and writes it to `path`, replacing any existing file. */
+  save(path: string, key: string): void
+  /**
+   * Like `save`, without blocking the event loop. Later changes to the file are
+   * not part of the write.
+   */
+  saveAsync(path: string, key: string): Promise<void>
+  /** Returns the value held by the file as plain JavaScript objects. */
+  read(): any
+  /**
+   * Replaces the value held by the file. Call `save` to persist it.
+   *
+   * # Throws
+   * Throws an error if `data` is not JSON-compatible, e.g. a function or a symbol.
    */
-  validateLicense(license: string, application: string): Promise<string>
+  write(data: unknown): void
 }
@@ -310,6 +310,7 @@ if (!nativeBinding) {
   throw new Error(`Failed to load native binding`)
 }
 
-const { LicenseClient } = nativeBinding
+const { LicenseClient, ChipaFile } = nativeBinding
 
 module.exports.LicenseClient = LicenseClient
+module.exports.ChipaFile = ChipaFile
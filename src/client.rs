@@ -1,17 +1,63 @@
 use core::fmt;
+use std::{collections::BTreeSet, time::Duration};
+#[cfg(all(feature = "client", feature = "fs"))]
+use std::{
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+#[cfg(feature = "client")]
+use std::{
+    collections::HashMap,
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, OnceLock, Weak,
+    },
+};
 
+use http::StatusCode;
+#[cfg(feature = "client")]
 use reqwest_wasm::{
-    header::{HeaderValue, AUTHORIZATION, CONTENT_TYPE},
-    Client, Method, StatusCode,
+    header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, CONTENT_TYPE, RETRY_AFTER},
+    Client, Method, Url,
 };
-use serde::{
-    de::{DeserializeOwned, Error},
-    Deserialize, Serialize,
-};
-use tenacity_utils::security::{headers::VERSION as VERSION_STR, TenacityMiddleware, Version};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "client")]
+use serde::de::{DeserializeOwned, Error};
+#[cfg(feature = "client")]
+use tenacity_utils::security::headers::VERSION as VERSION_STR;
+use serde_json::Value;
+#[cfg(feature = "client")]
+use tokio::sync::Semaphore;
 use uuid::Uuid;
 
+#[cfg(all(feature = "client", feature = "fs"))]
+use crate::{
+    encryption::{ChipaError, ChipaFile},
+    fs::{ChipaFs, RealFs},
+};
+#[cfg(feature = "client")]
+use crate::{
+    license::LicenseId,
+    perf::{Operation, Timer},
+    version::Version,
+};
+
+#[cfg(feature = "client")]
 const VERSION: Version = Version::V1;
+#[cfg(feature = "client")]
+const MAX_CONTEXT_SIZE: usize = 16 * 1024;
+#[cfg(feature = "client")]
+const UNSUPPORTED_VERSION: &str = "unsupported_version";
+const LICENSE_EXPIRED: &str = "license_expired";
+const LICENSE_NOT_FOUND: &str = "license_not_found";
+const APPLICATION_UNAUTHORIZED: &str = "application_unauthorized";
+const SEAT_LIMIT_REACHED: &str = "seat_limit_reached";
+const MACHINE_NOT_ACTIVATED: &str = "machine_not_activated";
+#[cfg(feature = "client")]
+const TRANSFER_COOLDOWN: &str = "transfer_cooldown";
+#[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+const DEFAULT_RETRY_BACKOFF: Duration = Duration::from_millis(200);
 
 #[derive(thiserror::Error, Debug)]
 pub enum TError {
@@ -19,19 +65,366 @@ pub enum TError {
     Anyhow(#[from] anyhow::Error),
     #[error("Parsing error: {0}")]
     Parsing(#[from] serde_json::Error),
+    #[cfg(feature = "client")]
     #[error("Request error: {0}")]
     Request(#[from] reqwest_wasm::Error),
     #[error("Response error: {0}")]
     Response(#[from] ApiError),
     #[error("UUID Parsing error: {0}")]
     UuidParsing(#[from] uuid::Error),
+    #[error("Invalid license, {0}")]
+    InvalidLicense(String),
     #[error("Chipa File error, {0}")]
     ChipaFile(#[from] crate::encryption::ChipaError),
+    #[error("License not validated, {0}")]
+    NotValidated(Box<TError>),
+    #[error("Empty response from '{endpoint}' with status {status}, the license server is likely misconfigured")]
+    EmptyResponse { endpoint: String, status: StatusCode },
+    #[error("Invalid base URL, {0}")]
+    InvalidUrl(String),
+    #[error("Validation context too large, {size} bytes exceeds the {max} bytes limit")]
+    ContextTooLarge { size: usize, max: usize },
+    #[error(
+        "Client too old, the license server requires protocol version {server_min_version} or newer but this client uses version {client_version}{}",
+        upgrade_hint(.download_url)
+    )]
+    ClientTooOld {
+        server_min_version: u16,
+        client_version: u16,
+        download_url: Option<String>,
+    },
+    #[error(
+        "Unsupported by server, '{capability}' needs license server {min_server_version} or newer"
+    )]
+    UnsupportedByServer {
+        capability: Capability,
+        min_server_version: &'static str,
+    },
+    #[error(
+        "Deadline exceeded, gave up after {attempts} attempts on {endpoints_tried} endpoints"
+    )]
+    DeadlineExceeded { attempts: u32, endpoints_tried: u32 },
+    #[error("{error}{}", seat_holders(.seats))]
+    NoSeatsAvailable {
+        seats: Vec<SeatInfo>,
+        error: Box<ApiError>,
+    },
+    #[error(
+        "Transfer cooldown, the license was force-transferred recently{}",
+        retry_hint(.retry_after)
+    )]
+    TransferCooldown { retry_after: Option<Duration> },
+}
+
+fn retry_hint(retry_after: &Option<Duration>) -> String {
+    match retry_after {
+        Some(after) => format!(", retry in {} seconds", after.as_secs()),
+        None => String::new(),
+    }
+}
+
+fn seat_holders(seats: &[SeatInfo]) -> String {
+    let labels: Vec<&str> = seats
+        .iter()
+        .map(|seat| seat.machine_label.as_deref().unwrap_or("unlabeled machine"))
+        .collect();
+    match labels.is_empty() {
+        true => String::new(),
+        false => format!(", held by {}", labels.join(", ")),
+    }
+}
+
+/// A seat held by another machine, as listed by a server refusing an activation for
+/// lack of seats. Free it with `TClient::deactivate_activation` if the machine is gone.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SeatInfo {
+    /// The activation holding the seat.
+    pub id: Uuid,
+    /// The label the machine was activated with, if any.
+    #[serde(default)]
+    pub machine_label: Option<String>,
+    /// Unix timestamp, in seconds, of the machine's last activation or heartbeat.
+    pub last_seen: u64,
+}
+
+fn upgrade_hint(download_url: &Option<String>) -> String {
+    match download_url {
+        Some(url) => format!(", download the latest version from {}", url),
+        None => String::new(),
+    }
+}
+
+impl TError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            TError::Anyhow(_) => "anyhow",
+            TError::Parsing(_) => "parsing",
+            #[cfg(feature = "client")]
+            TError::Request(_) => "request",
+            TError::Response(_) => "response",
+            TError::UuidParsing(_) => "uuid_parsing",
+            TError::InvalidLicense(_) => "invalid_license",
+            TError::ChipaFile(_) => "chipa_file",
+            TError::NotValidated(_) => "not_validated",
+            TError::EmptyResponse { .. } => "empty_response",
+            TError::InvalidUrl(_) => "invalid_url",
+            TError::ContextTooLarge { .. } => "context_too_large",
+            TError::ClientTooOld { .. } => "client_too_old",
+            TError::UnsupportedByServer { .. } => "unsupported_by_server",
+            TError::DeadlineExceeded { .. } => "deadline_exceeded",
+            TError::NoSeatsAvailable { .. } => "no_seats_available",
+            TError::TransferCooldown { .. } => "transfer_cooldown",
+        }
+    }
+
+    #[cfg(feature = "client")]
+    pub fn address_family(&self) -> Option<&'static str> {
+        let TError::Request(e) = self else {
+            return None;
+        };
+        let host = e.url()?.host_str()?;
+        if host.starts_with('[') {
+            Some("ipv6")
+        } else if host.parse::<std::net::Ipv4Addr>().is_ok() {
+            Some("ipv4")
+        } else {
+            Some("dns")
+        }
+    }
+
+    /// The HTTP status of the server's answer, if the error comes from one.
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            TError::Response(e) => e.status,
+            TError::NoSeatsAvailable { error, .. } => error.status,
+            TError::EmptyResponse { status, .. } => Some(*status),
+            TError::NotValidated(e) => e.status(),
+            _ => None,
+        }
+    }
+
+    /// The license expired or is unpaid: a 410 or 402, or the server's
+    /// `license_expired` code.
+    pub fn is_expired(&self) -> bool {
+        self.api_error().is_some_and(ApiError::is_expired)
+    }
+
+    /// The server does not know the license: a 404, or the `license_not_found` code.
+    /// A wrong base URL also answers 404, check it if every license is reported
+    /// missing.
+    pub fn is_license_not_found(&self) -> bool {
+        self.api_error().is_some_and(ApiError::is_license_not_found)
+    }
+
+    /// The license does not cover this application: a 401 or 403, or the
+    /// `application_unauthorized` code.
+    pub fn is_unauthorized_app(&self) -> bool {
+        self.api_error().is_some_and(ApiError::is_unauthorized_app)
+    }
+
+    /// Every seat of the license is held by other machines: a 409, or the
+    /// `seat_limit_reached` code. Activations fail with `TError::NoSeatsAvailable`,
+    /// which lists the seats; deactivate one of them to free it.
+    pub fn is_seat_limit_reached(&self) -> bool {
+        self.api_error().is_some_and(ApiError::is_seat_limit_reached)
+    }
+
+    /// The machine holds no seat of the license, only the `machine_not_activated` code
+    /// says so. Activate it again to get a new token.
+    pub fn is_unknown_machine(&self) -> bool {
+        self.api_error().is_some_and(ApiError::is_unknown_machine)
+    }
+
+    /// The license server could not be reached or did not answer in time.
+    pub fn is_network(&self) -> bool {
+        match self {
+            #[cfg(feature = "client")]
+            TError::Request(_) => true,
+            TError::DeadlineExceeded { .. } => true,
+            TError::NotValidated(e) => e.is_network(),
+            _ => false,
+        }
+    }
+
+    // A 404 without a code, what servers without the endpoint answer.
+    #[cfg(feature = "client")]
+    fn is_bare_404(&self) -> bool {
+        self.api_error()
+            .is_some_and(|e| e.status == Some(StatusCode::NOT_FOUND) && e.code.is_none())
+    }
+
+    #[cfg(feature = "client")]
+    fn unsupported(capability: Capability) -> Self {
+        TError::UnsupportedByServer {
+            capability,
+            min_server_version: capability.min_server_version(),
+        }
+    }
+
+    fn api_error(&self) -> Option<&ApiError> {
+        match self {
+            TError::Response(e) => Some(e),
+            TError::NoSeatsAvailable { error, .. } => Some(error),
+            TError::NotValidated(e) => e.api_error(),
+            _ => None,
+        }
+    }
+
+    pub fn remediation(&self) -> Remediation {
+        match self {
+            #[cfg(feature = "client")]
+            TError::Request(_) => Remediation::CheckInternet,
+            TError::DeadlineExceeded { .. } => Remediation::CheckInternet,
+            TError::Response(e) => e.remediation(),
+            TError::NoSeatsAvailable { error, .. } => error.remediation(),
+            TError::NotValidated(e) => e.remediation(),
+            TError::ClientTooOld { .. } => Remediation::UpdateApp,
+            TError::TransferCooldown {
+                retry_after: Some(after),
+            } => Remediation::RetryAfter(*after),
+            TError::TransferCooldown { retry_after: None } => Remediation::RetryLater,
+            TError::Anyhow(_)
+            | TError::Parsing(_)
+            | TError::UuidParsing(_)
+            | TError::InvalidLicense(_)
+            | TError::ChipaFile(_)
+            | TError::EmptyResponse { .. }
+            | TError::InvalidUrl(_)
+            | TError::ContextTooLarge { .. }
+            | TError::UnsupportedByServer { .. } => Remediation::ContactSupport,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Remediation {
+    RetryAfter(Duration),
+    RetryLater,
+    CheckInternet,
+    Renew { portal_url: Option<String> },
+    ContactSupport,
+    UpdateApp,
+}
+
+impl Remediation {
+    pub fn action(&self) -> &'static str {
+        match self {
+            Remediation::RetryAfter(_) => "retry_after",
+            Remediation::RetryLater => "retry_later",
+            Remediation::CheckInternet => "check_internet",
+            Remediation::Renew { .. } => "renew",
+            Remediation::ContactSupport => "contact_support",
+            Remediation::UpdateApp => "update_app",
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "action", rename_all = "snake_case")]
+enum RemediationHint {
+    RetryAfter { seconds: u64 },
+    RetryLater,
+    CheckInternet,
+    Renew { portal_url: Option<String> },
+    ContactSupport,
+    UpdateApp,
+}
+
+impl From<RemediationHint> for Remediation {
+    fn from(hint: RemediationHint) -> Self {
+        match hint {
+            RemediationHint::RetryAfter { seconds } => {
+                Remediation::RetryAfter(Duration::from_secs(seconds))
+            }
+            RemediationHint::RetryLater => Remediation::RetryLater,
+            RemediationHint::CheckInternet => Remediation::CheckInternet,
+            RemediationHint::Renew { portal_url } => Remediation::Renew { portal_url },
+            RemediationHint::ContactSupport => Remediation::ContactSupport,
+            RemediationHint::UpdateApp => Remediation::UpdateApp,
+        }
+    }
 }
 
+/// An error answer from the license server. `code` is the server's machine-readable
+/// reason, when it sends one; `status` is always set for responses.
 #[derive(Deserialize, Debug)]
 pub struct ApiError {
     pub error: String,
+    #[serde(default)]
+    pub code: Option<String>,
+    #[serde(default)]
+    pub portal_url: Option<String>,
+    #[serde(default)]
+    remediation: Option<RemediationHint>,
+    #[serde(skip)]
+    pub status: Option<StatusCode>,
+    #[serde(skip)]
+    pub retry_after: Option<Duration>,
+}
+
+impl ApiError {
+    // The server's code wins over the status, which only says what kind of failure it was.
+    fn is(&self, code: &str, statuses: &[StatusCode]) -> bool {
+        match &self.code {
+            Some(c) => c == code,
+            None => self.status.is_some_and(|status| statuses.contains(&status)),
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.is(
+            LICENSE_EXPIRED,
+            &[StatusCode::GONE, StatusCode::PAYMENT_REQUIRED],
+        )
+    }
+
+    pub fn is_license_not_found(&self) -> bool {
+        self.is(LICENSE_NOT_FOUND, &[StatusCode::NOT_FOUND])
+    }
+
+    pub fn is_unauthorized_app(&self) -> bool {
+        self.is(
+            APPLICATION_UNAUTHORIZED,
+            &[StatusCode::UNAUTHORIZED, StatusCode::FORBIDDEN],
+        )
+    }
+
+    pub fn is_seat_limit_reached(&self) -> bool {
+        self.is(SEAT_LIMIT_REACHED, &[StatusCode::CONFLICT])
+    }
+
+    pub fn is_unknown_machine(&self) -> bool {
+        self.is(MACHINE_NOT_ACTIVATED, &[])
+    }
+
+    /// What the user can do about the error: the server's own hint if it sent one,
+    /// otherwise what the classifiers above make of it, so that the server's code wins
+    /// over the status here too, and last what the status alone suggests.
+    pub fn remediation(&self) -> Remediation {
+        if let Some(hint) = &self.remediation {
+            return hint.clone().into();
+        }
+        if self.is_expired() {
+            return Remediation::Renew {
+                portal_url: self.portal_url.clone(),
+            };
+        }
+        if self.is_unauthorized_app() || self.is_license_not_found() {
+            return Remediation::ContactSupport;
+        }
+        match self.status {
+            Some(StatusCode::TOO_MANY_REQUESTS) => match self.retry_after {
+                Some(after) => Remediation::RetryAfter(after),
+                None => Remediation::RetryLater,
+            },
+            Some(StatusCode::GONE | StatusCode::PAYMENT_REQUIRED) => Remediation::Renew {
+                portal_url: self.portal_url.clone(),
+            },
+            Some(StatusCode::UPGRADE_REQUIRED) => Remediation::UpdateApp,
+            Some(status) if status.is_server_error() => Remediation::RetryLater,
+            _ => Remediation::ContactSupport,
+        }
+    }
 }
 
 impl fmt::Display for ApiError {
@@ -42,13 +435,27 @@ impl fmt::Display for ApiError {
 
 impl std::error::Error for ApiError {}
 
+#[cfg(feature = "client")]
 pub type SecureResult<T> = Result<T, TError>;
+#[cfg(feature = "client")]
 #[derive(Clone)]
 pub struct SecureResponse {
     pub status: StatusCode,
+    retry_after: Option<Duration>,
     body: Option<String>,
 }
 
+#[cfg(feature = "client")]
+#[derive(Deserialize)]
+struct UnsupportedVersion {
+    code: String,
+    #[serde(default)]
+    min_version: Option<u16>,
+    #[serde(default)]
+    download_url: Option<String>,
+}
+
+#[cfg(feature = "client")]
 #[derive(Clone, Deserialize)]
 pub(crate) struct ValidateResponse {
     #[serde(rename = "success")]
@@ -56,89 +463,1446 @@ pub(crate) struct ValidateResponse {
     token: String,
 }
 
+/// The undecoded answer to a validation. `body` holds the token and whatever else the
+/// server sent, so `Debug` redacts every field whose name looks like a secret. An error
+/// answer that is not JSON, like a proxy's error page, is kept as a `Value::String`.
+#[derive(Clone)]
+pub struct RawValidation {
+    pub status: StatusCode,
+    pub body: Value,
+}
+
+impl fmt::Debug for RawValidation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RawValidation")
+            .field("status", &self.status)
+            .field("body", &redacted(&self.body))
+            .finish()
+    }
+}
+
+const SECRET_FIELDS: [&str; 4] = ["token", "secret", "password", "key"];
+
+fn redacted(value: &Value) -> Value {
+    match value {
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(name, value)| {
+                    let name_lower = name.to_lowercase();
+                    let value = match SECRET_FIELDS.iter().any(|s| name_lower.contains(s)) {
+                        true => Value::String("<redacted>".to_string()),
+                        false => redacted(value),
+                    };
+                    (name.clone(), value)
+                })
+                .collect(),
+        ),
+        Value::Array(values) => Value::Array(values.iter().map(redacted).collect()),
+        value => value.clone(),
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SeatUsage {
+    pub used: Option<u32>,
+    pub total: Option<u32>,
+    #[serde(default)]
+    pub samples: Vec<SeatSample>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SeatSample {
+    pub timestamp: u64,
+    pub used: u32,
+}
+
+impl SeatUsage {
+    pub fn unknown() -> Self {
+        Self::default()
+    }
+
+    pub fn is_known(&self) -> bool {
+        self.used.is_some() && self.total.is_some()
+    }
+}
+
+/// A license server feature that older servers lack. Validation itself works
+/// against every server and is not listed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[non_exhaustive]
+pub enum Capability {
+    ValidateContext,
+    Seats,
+    Activation,
+    Transfer,
+}
+
+/// Every capability this client can use, with the first server release that has it.
+pub const SUPPORT_MATRIX: [(Capability, &str); 4] = [
+    (Capability::ValidateContext, "1.2.0"),
+    (Capability::Seats, "1.3.0"),
+    (Capability::Activation, "1.4.0"),
+    (Capability::Transfer, "1.5.0"),
+];
+
+impl Capability {
+    /// The name the server lists in its `/capabilities` answer.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Capability::ValidateContext => "validate_context",
+            Capability::Seats => "seats",
+            Capability::Activation => "activation",
+            Capability::Transfer => "transfer",
+        }
+    }
+
+    pub fn min_server_version(&self) -> &'static str {
+        SUPPORT_MATRIX
+            .iter()
+            .find(|(capability, _)| capability == self)
+            .map_or("unknown", |(_, version)| version)
+    }
+
+    /// The capability a `/capabilities` name stands for, `None` for names from newer
+    /// servers that this client cannot use.
+    pub fn from_name(name: &str) -> Option<Self> {
+        SUPPORT_MATRIX
+            .iter()
+            .map(|(capability, _)| *capability)
+            .find(|capability| capability.name() == name)
+    }
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// What a license server is known to support, from `TClient::server_capabilities`
+/// and from the 405s older servers answer for methods their routes lack.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ServerCapabilities {
+    /// The release the server reported, `None` if it was not asked or predates
+    /// `/capabilities`.
+    pub server_version: Option<String>,
+    asked: bool,
+    listed: Option<BTreeSet<Capability>>,
+    missing: BTreeSet<Capability>,
+}
+
+impl ServerCapabilities {
+    /// `Some(false)` when the server is known to lack `capability`, `None` when
+    /// nothing is known about it yet.
+    pub fn supports(&self, capability: Capability) -> Option<bool> {
+        if self.missing.contains(&capability) {
+            return Some(false);
+        }
+        self.listed
+            .as_ref()
+            .map(|listed| listed.contains(&capability))
+    }
+
+    /// The server was asked and has no `/capabilities` endpoint, so its capabilities
+    /// are only learned as requests fail.
+    pub fn predates_discovery(&self) -> bool {
+        self.asked && self.listed.is_none()
+    }
+}
+
+/// A seat of a license held by one machine. Send `token` to `TClient::heartbeat`
+/// every `heartbeat_interval` to keep the seat; the server frees seats that stop
+/// sending them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ActivationToken {
+    pub token: String,
+    /// `None` when the server does not expire seats.
+    pub heartbeat_interval: Option<Duration>,
+    /// The id other machines see in `SeatInfo::id`, `None` from servers that do not
+    /// list seats.
+    pub activation_id: Option<Uuid>,
+}
+
+#[cfg(feature = "client")]
+#[derive(Deserialize)]
+struct ActivationResponse {
+    token: String,
+    #[serde(default)]
+    heartbeat_interval: Option<u64>,
+    #[serde(default)]
+    activation_id: Option<Uuid>,
+}
+
+#[cfg(feature = "client")]
+impl From<ActivationResponse> for ActivationToken {
+    fn from(response: ActivationResponse) -> Self {
+        Self {
+            token: response.token,
+            heartbeat_interval: response.heartbeat_interval.map(Duration::from_secs),
+            activation_id: response.activation_id,
+        }
+    }
+}
+
+#[cfg(feature = "client")]
+#[derive(Deserialize)]
+struct SeatsHeld {
+    #[serde(default)]
+    seats: Vec<SeatInfo>,
+}
+
+#[cfg(feature = "client")]
+#[derive(Deserialize)]
+struct CapabilitiesResponse {
+    #[serde(default)]
+    server_version: Option<String>,
+    capabilities: Vec<String>,
+}
+
+/// Client for the license server, exported as `LicenseClient`.
+///
+/// Clients are cheap to clone, and clients with the same base URL share one
+/// connection pool. Every request is encrypted with the identity of the license it
+/// is about.
+///
+/// ```
+/// # #[cfg(all(feature = "test-util", feature = "mock-server"))]
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use chipa_license_validator::{
+///     test_util::{MockConfig, MockServer},
+///     LicenseClient, LicenseId,
+/// };
+///
+/// let server = MockServer::start(MockConfig::default()).await?;
+/// let client = LicenseClient::new(server.url());
+///
+/// let license: LicenseId = "CHIPA-AB12-CD34-EF56-GH78".parse()?;
+/// let token = client.validate_license(license, "my-app".to_string()).await?;
+/// assert_eq!(token, "mock-token");
+/// # server.stop().await;
+/// # Ok(())
+/// # }
+/// # #[cfg(not(all(feature = "test-util", feature = "mock-server")))]
+/// # fn main() {}
+/// ```
+#[cfg(feature = "client")]
 #[derive(Clone)]
 pub struct TClient {
-    inner: Client,
+    inner: Option<Arc<Client>>,
     base_url: String,
+    default_context: Value,
+    lenient: bool,
+    limit: Option<(NonZeroUsize, Arc<Semaphore>)>,
+    in_flight: Arc<AtomicUsize>,
+    headers: HeaderMap,
+    connect_timeout: Option<Duration>,
+    machine_label: Option<String>,
+    #[cfg(not(target_arch = "wasm32"))]
+    timeout: Option<Duration>,
+    #[cfg(not(target_arch = "wasm32"))]
+    retries: u32,
+    #[cfg(not(target_arch = "wasm32"))]
+    retry_backoff: Duration,
+    #[cfg(not(target_arch = "wasm32"))]
+    operation_timeout: Option<Duration>,
+    #[cfg(not(target_arch = "wasm32"))]
+    retry_on: BTreeSet<RetryOn>,
+    #[cfg(not(target_arch = "wasm32"))]
+    max_retry_backoff: Option<Duration>,
+    #[cfg(not(target_arch = "wasm32"))]
+    on_retry: Option<RetryHook>,
+    #[cfg(feature = "fs")]
+    quarantined: Arc<Mutex<Vec<Quarantined>>>,
+    #[cfg(feature = "fs")]
+    quarantine_observer: Option<QuarantineObserver>,
+}
+
+/// Builds a `TClient`, see `TClient::builder`. Each method does what the `set_*` method
+/// of the same name does.
+#[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+#[must_use]
+pub struct TClientBuilder {
+    client: TClient,
+}
+
+#[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+impl TClientBuilder {
+    pub fn timeout(self, timeout: Duration) -> Self {
+        self.map(|client| client.set_timeout(Some(timeout)))
+    }
+
+    pub fn connect_timeout(self, timeout: Duration) -> Self {
+        self.map(|client| client.set_connect_timeout(Some(timeout)))
+    }
+
+    pub fn retries(self, retries: u32) -> Self {
+        self.map(|client| client.set_retries(retries))
+    }
+
+    pub fn retry_backoff(self, backoff: Duration) -> Self {
+        self.map(|client| client.set_retry_backoff(backoff))
+    }
+
+    pub fn header(self, name: HeaderName, value: HeaderValue) -> Self {
+        self.map(|client| client.set_header(name, value))
+    }
+
+    pub fn build(self) -> TClient {
+        self.client
+    }
+
+    fn map(self, set: impl FnOnce(TClient) -> TClient) -> Self {
+        Self {
+            client: set(self.client),
+        }
+    }
+}
+
+/// A kind of failure `TClient` may retry, see `TClient::set_retry_on`.
+#[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum RetryOn {
+    /// The server could not be reached or did not answer in time.
+    Network,
+    /// A 5xx answer.
+    ServerError,
+    /// A `429 Too Many Requests` answer. The retry waits at least its `Retry-After`.
+    RateLimited,
+}
+
+#[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+impl RetryOn {
+    /// "network", "5xx" or "429", the names the bindings use.
+    pub fn name(&self) -> &'static str {
+        match self {
+            RetryOn::Network => "network",
+            RetryOn::ServerError => "5xx",
+            RetryOn::RateLimited => "429",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [RetryOn::Network, RetryOn::ServerError, RetryOn::RateLimited]
+            .into_iter()
+            .find(|retry_on| retry_on.name() == name)
+    }
+}
+
+/// A retry `TClient` is about to make, after waiting `delay`. `attempt` is the attempt
+/// that failed, counting from 1.
+#[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryEvent {
+    pub attempt: u32,
+    pub delay: Duration,
+    pub reason: RetryOn,
+}
+
+/// Called before every retry, on the task making the request. It must not block.
+#[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+pub type RetryHook = Arc<dyn Fn(&RetryEvent) + Send + Sync>;
+
+/// A validation cache that could not be decoded, moved aside by
+/// `validate_license_cached` so the next validation starts a fresh one.
+#[cfg(all(feature = "client", feature = "fs"))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Quarantined {
+    pub path: PathBuf,
+    /// `path` with `.corrupt-<unix millis>` appended.
+    pub moved_to: PathBuf,
+    pub reason: String,
+}
+
+/// Called with every cache a client moves aside, on the thread that found it.
+#[cfg(all(feature = "client", feature = "fs"))]
+pub type QuarantineObserver = Arc<dyn Fn(&Quarantined) + Send + Sync>;
+
+#[cfg(feature = "client")]
+struct InFlight<'a>(&'a AtomicUsize);
+
+#[cfg(feature = "client")]
+impl<'a> InFlight<'a> {
+    fn enter(counter: &'a AtomicUsize) -> Self {
+        counter.fetch_add(1, Ordering::SeqCst);
+        Self(counter)
+    }
+}
+
+#[cfg(feature = "client")]
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+// Clients are pooled per base URL and connect timeout, the only setting that lives on
+// the connection pool itself.
+#[cfg(feature = "client")]
+type PoolKey = (String, Option<Duration>);
+
+#[cfg(feature = "client")]
+static POOLS: OnceLock<Mutex<HashMap<PoolKey, Weak<Client>>>> = OnceLock::new();
+
+#[cfg(feature = "client")]
+fn pools() -> std::sync::MutexGuard<'static, HashMap<PoolKey, Weak<Client>>> {
+    POOLS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+}
+
+// Capabilities are a property of the server, so clones and clients with other
+// settings share what was learned about a base URL.
+#[cfg(feature = "client")]
+static CAPABILITIES: OnceLock<Mutex<HashMap<String, ServerCapabilities>>> = OnceLock::new();
+
+#[cfg(feature = "client")]
+fn capabilities() -> std::sync::MutexGuard<'static, HashMap<String, ServerCapabilities>> {
+    CAPABILITIES
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+}
+
+// A client reqwest fails to build is not pooled: the error surfaces on the next request
+// instead of a default client that would quietly ignore `connect_timeout`.
+#[cfg(feature = "client")]
+fn pooled_client(base_url: &str, connect_timeout: Option<Duration>) -> SecureResult<Arc<Client>> {
+    let key = (base_url.to_string(), connect_timeout);
+    let mut pools = pools();
+    if let Some(client) = pools.get(&key).and_then(Weak::upgrade) {
+        return Ok(client);
+    }
+    pools.retain(|_, pool| pool.strong_count() > 0);
+    let builder = Client::builder();
+    #[cfg(not(target_arch = "wasm32"))]
+    let builder = match connect_timeout {
+        Some(timeout) => builder.connect_timeout(timeout),
+        None => builder,
+    };
+    let client = Arc::new(builder.build()?);
+    pools.insert(key, Arc::downgrade(&client));
+    Ok(client)
+}
+
+#[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+fn backoff(base: Duration, attempt: u32) -> Duration {
+    base.saturating_mul(1u32.checked_shl(attempt).unwrap_or(u32::MAX))
+}
+
+#[cfg(all(feature = "client", feature = "fs"))]
+#[derive(Serialize, Deserialize)]
+struct CachedValidation {
+    license: Uuid,
+    application: String,
+    token: String,
+    validated_at: u64,
+}
+
+#[cfg(all(feature = "client", feature = "fs"))]
+impl CachedValidation {
+    fn key(license: Uuid) -> String {
+        format!("chipa-validation-cache:{}", license)
+    }
+
+    fn save(&self, path: &str, fs: &dyn ChipaFs) -> SecureResult<()> {
+        ChipaFile::new(VERSION, self)?.save_with(path, &Self::key(self.license), fs)?;
+        Ok(())
+    }
+
+    // A missing cache or one for another application is `None`, one that cannot be
+    // decoded is an error.
+    fn load(
+        path: &str,
+        license: Uuid,
+        application: &str,
+        fs: &dyn ChipaFs,
+    ) -> Result<Option<Self>, ChipaError> {
+        if !fs.exists(Path::new(path)) {
+            return Ok(None);
+        }
+        let cached: Self = ChipaFile::load_with(path, &Self::key(license), fs)?.read()?;
+        Ok((cached.license == license && cached.application == application).then_some(cached))
+    }
+
+    fn age(&self) -> Option<Duration> {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH + Duration::from_secs(self.validated_at))
+            .ok()
+    }
+}
+
+// Failed, timed out and retried validations are timed too, without a payload size.
+#[cfg(feature = "client")]
+fn validated_bytes(req: &SecureResult<SecureResponse>) -> Option<usize> {
+    req.as_ref().ok()?.body.as_ref().map(String::len)
+}
+
+#[cfg(feature = "client")]
+fn merge_context(default: &Value, context: Value) -> Value {
+    match (default, context) {
+        (Value::Object(default), Value::Object(context)) => {
+            let mut merged = default.clone();
+            merged.extend(context);
+            Value::Object(merged)
+        }
+        (default, Value::Null) => default.clone(),
+        (_, context) => context,
+    }
 }
 
+#[cfg(feature = "client")]
 impl TClient {
     pub fn new(base: String) -> Self {
         Self {
-            inner: Client::new(),
+            inner: pooled_client(&base, None).ok(),
             base_url: base,
+            default_context: Value::Null,
+            lenient: false,
+            limit: None,
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            headers: HeaderMap::new(),
+            connect_timeout: None,
+            machine_label: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            timeout: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            retries: 0,
+            #[cfg(not(target_arch = "wasm32"))]
+            retry_backoff: DEFAULT_RETRY_BACKOFF,
+            #[cfg(not(target_arch = "wasm32"))]
+            operation_timeout: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            retry_on: BTreeSet::from([RetryOn::Network, RetryOn::ServerError]),
+            #[cfg(not(target_arch = "wasm32"))]
+            max_retry_backoff: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            on_retry: None,
+            #[cfg(feature = "fs")]
+            quarantined: Arc::default(),
+            #[cfg(feature = "fs")]
+            quarantine_observer: None,
+        }
+    }
+
+    /// Starts a client for `base` with the most used settings named as in the bindings'
+    /// options. Everything else is set on the built client with the `set_*` methods.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// use chipa_license_validator::LicenseClient;
+    /// use http::header::{HeaderName, HeaderValue};
+    ///
+    /// let client = LicenseClient::builder("https://license.example.com".to_string())
+    ///     .timeout(Duration::from_secs(5))
+    ///     .connect_timeout(Duration::from_secs(2))
+    ///     .retries(3)
+    ///     .retry_backoff(Duration::from_millis(100))
+    ///     .header(HeaderName::from_static("x-tenant"), HeaderValue::from_static("acme"))
+    ///     .build();
+    /// # let _ = client;
+    /// ```
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn builder(base: String) -> TClientBuilder {
+        TClientBuilder {
+            client: Self::new(base),
         }
     }
 
     pub fn set_url(mut self, url: String) -> Self {
+        self.inner = pooled_client(&url, self.connect_timeout).ok();
         self.base_url = url;
         self
     }
 
-    async fn _send_secure<T: Serialize>(
-        &self,
-        url: String,
-        body: Option<T>,
-        method: Method,
-        id: Uuid,
-    ) -> SecureResult<SecureResponse> {
-        let encryptor = VERSION.encryptor();
-        let id_header = encryptor.encrypt_header(id).await?;
-        let request = self
-            .inner
-            .request(method, url.to_string())
-            .header(AUTHORIZATION, id_header)
-            .header(VERSION_STR, "v1");
-        // .header("Agents", json!(agents).to_string());
+    /// Sends `name: value` with every request. Headers the client sets itself
+    /// (`Authorization`, `Content-Type` and the protocol version) are not overridden.
+    pub fn set_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        let reserved = name == AUTHORIZATION
+            || name == CONTENT_TYPE
+            || name.as_str().eq_ignore_ascii_case(VERSION_STR);
+        if !reserved {
+            self.headers.insert(name, value);
+        }
+        self
+    }
 
-        let req = match body {
-            Some(body) => request
-                .header(CONTENT_TYPE, HeaderValue::from_static("application/json"))
-                .body(
-                    encryptor
-                        .encrypt(id, &serde_json::to_string(&body)?)
-                        .await?,
-                ),
-            None => request,
-        };
+    /// Sends `label`, e.g. the device name the user chose, with every activation. Other
+    /// machines see it in `SeatInfo::machine_label` when the license runs out of seats.
+    pub fn set_machine_label(mut self, label: Option<String>) -> Self {
+        self.machine_label = label;
+        self
+    }
 
-        let response = self.inner.execute(req.build()?).await?;
-        let status = response.status();
-        let body = response.text().await?;
-        match body.is_empty() {
-            true => Ok(SecureResponse { status, body: None }),
-            false => {
-                let decrypted_body = encryptor.decrypt(id, &body).await?.clone();
-                Ok(SecureResponse {
-                    status,
-                    body: Some(decrypted_body),
-                })
-            }
-        }
+    /// Fails each attempt that takes longer than `timeout` from sending the request to
+    /// reading the whole response, with a `TError::Request` that retries may recover.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
     }
 
-    pub async fn validate_license(
+    /// Gives up connecting to the license server after `timeout`. Clients with the same
+    /// base URL and connect timeout share a connection pool.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_connect_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.connect_timeout = timeout;
+        self.inner = pooled_client(&self.base_url, timeout).ok();
+        self
+    }
+
+    /// Retries GET requests up to `retries` times when the server cannot be reached or
+    /// answers with a 5xx status, or on the failures chosen with `set_retry_on`. Other
+    /// statuses and requests with a body are never retried. Defaults to 0.
+    ///
+    /// ```
+    /// # #[cfg(all(feature = "test-util", feature = "mock-server"))]
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use std::time::Duration;
+    ///
+    /// use chipa_license_validator::{
+    ///     test_util::{MockConfig, MockServer},
+    ///     LicenseClient,
+    /// };
+    /// use uuid::Uuid;
+    ///
+    /// // The first two requests get a 503.
+    /// let server = MockServer::start(MockConfig { fail_first: 2, ..Default::default() }).await?;
+    /// let client = LicenseClient::new(server.url())
+    ///     .set_timeout(Some(Duration::from_secs(5)))
+    ///     .set_retries(3)
+    ///     .set_retry_backoff(Duration::from_millis(10));
+    /// let token = client.validate_license(Uuid::new_v4(), "my-app".to_string()).await?;
+    /// assert_eq!(token, "mock-token");
+    /// assert_eq!(server.request_count(), 3);
+    /// # server.stop().await;
+    /// # Ok(())
+    /// # }
+    /// # #[cfg(not(all(feature = "test-util", feature = "mock-server")))]
+    /// # fn main() {}
+    /// ```
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Waits `backoff` before the first retry and doubles the wait for each one after.
+    /// Defaults to 200ms.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_retry_backoff(mut self, backoff: Duration) -> Self {
+        self.retry_backoff = backoff;
+        self
+    }
+
+    /// Caps the wait before a retry, which otherwise doubles without limit. Defaults to
+    /// no cap.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_max_retry_backoff(mut self, max: Option<Duration>) -> Self {
+        self.max_retry_backoff = max;
+        self
+    }
+
+    /// Chooses which failures `set_retries` retries. Defaults to `RetryOn::Network` and
+    /// `RetryOn::ServerError`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_retry_on(mut self, retry_on: impl IntoIterator<Item = RetryOn>) -> Self {
+        self.retry_on = retry_on.into_iter().collect();
+        self
+    }
+
+    /// Calls `hook` before every retry, e.g. to log it. `None` removes the hook.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_on_retry(mut self, hook: Option<RetryHook>) -> Self {
+        self.on_retry = hook;
+        self
+    }
+
+    /// Bounds a whole request, every retry and backoff included, by `timeout`. The
+    /// attempt running when it elapses is aborted, and a retry that could not start
+    /// before it is not made; both fail with `TError::DeadlineExceeded`. Defaults to
+    /// no deadline.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_operation_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.operation_timeout = timeout;
+        self
+    }
+
+    /// Hands every validation cache this client moves aside to `observer`, e.g. to log
+    /// it with the application's logger. `None` removes the observer.
+    #[cfg(feature = "fs")]
+    pub fn set_quarantine_observer(mut self, observer: Option<QuarantineObserver>) -> Self {
+        self.quarantine_observer = observer;
+        self
+    }
+
+    /// The validation caches this client and its clones moved aside, oldest first.
+    #[cfg(feature = "fs")]
+    pub fn quarantined_files(&self) -> Vec<Quarantined> {
+        self.quarantined
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    pub fn set_default_context(mut self, context: Value) -> Self {
+        self.default_context = context;
+        self
+    }
+
+    /// Lets optional features degrade on servers that lack them instead of failing
+    /// with `TError::UnsupportedByServer`: `seat_usage` then returns
+    /// `SeatUsage::unknown()` and `heartbeat` hands back the token it was given.
+    pub fn set_lenient(mut self, lenient: bool) -> Self {
+        self.lenient = lenient;
+        self
+    }
+
+    /// Caps how many requests this client and its clones have in flight at once.
+    /// Requests over the limit wait for a free slot instead of failing.
+    ///
+    /// ```
+    /// # #[cfg(all(feature = "test-util", feature = "mock-server"))]
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use std::num::NonZeroUsize;
+    ///
+    /// use chipa_license_validator::{
+    ///     test_util::{MockConfig, MockServer},
+    ///     LicenseClient,
+    /// };
+    /// use uuid::Uuid;
+    ///
+    /// let server = MockServer::start(MockConfig::default()).await?;
+    /// let client = LicenseClient::new(server.url()).set_max_concurrency(NonZeroUsize::new(4));
+    /// let validations = (0..16).map(|_| {
+    ///     let client = client.clone();
+    ///     tokio::spawn(async move {
+    ///         client.validate_license(Uuid::new_v4(), "my-app".to_string()).await
+    ///     })
+    /// });
+    /// for validation in validations.collect::<Vec<_>>() {
+    ///     validation.await??;
+    /// }
+    /// assert!(server.peak_concurrency() <= 4);
+    /// assert_eq!(client.in_flight(), 0);
+    /// # server.stop().await;
+    /// # Ok(())
+    /// # }
+    /// # #[cfg(not(all(feature = "test-util", feature = "mock-server")))]
+    /// # fn main() {}
+    /// ```
+    pub fn set_max_concurrency(mut self, max: Option<NonZeroUsize>) -> Self {
+        self.limit = max.map(|max| (max, Arc::new(Semaphore::new(max.get()))));
+        self
+    }
+
+    pub fn max_concurrency(&self) -> Option<NonZeroUsize> {
+        self.limit.as_ref().map(|(max, _)| *max)
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+
+    pub fn close(&mut self) {
+        self.inner = None;
+    }
+
+    fn http(&self) -> SecureResult<Arc<Client>> {
+        match &self.inner {
+            Some(inner) => Ok(inner.clone()),
+            None => pooled_client(&self.base_url, self.connect_timeout),
+        }
+    }
+
+    fn endpoint(&self, segments: &[&str]) -> SecureResult<Url> {
+        let invalid = |reason: String| TError::InvalidUrl(format!("'{}' {}", self.base_url, reason));
+        let mut url = Url::parse(&self.base_url).map_err(|e| invalid(e.to_string()))?;
+        if url.host().is_none() {
+            return Err(invalid("has no host".to_string()));
+        }
+        url.path_segments_mut()
+            .map_err(|_| invalid("cannot be a base".to_string()))?
+            .pop_if_empty()
+            .extend(segments);
+        Ok(url)
+    }
+
+    fn validate_url(&self, license: &LicenseId, application: &str) -> SecureResult<Url> {
+        self.endpoint(&[
+            "subscriptions",
+            "validateapp",
+            &license.to_string(),
+            application,
+        ])
+    }
+
+    async fn _send_secure<T: Serialize>(
+        &self,
+        url: Url,
+        body: Option<T>,
+        method: Method,
+        id: Uuid,
+    ) -> SecureResult<SecureResponse> {
+        let body = body.map(|body| serde_json::to_string(&body)).transpose()?;
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let retries = if method == Method::GET { self.retries } else { 0 };
+            let started = std::time::Instant::now();
+            // There is a single base URL, failing over to another endpoint is up to DNS.
+            let exceeded = |attempts| TError::DeadlineExceeded {
+                attempts,
+                endpoints_tried: 1,
+            };
+            let mut attempt = 0;
+            loop {
+                let send = self.send_attempt(url.clone(), body.as_deref(), method.clone(), id);
+                let result = match self.operation_timeout {
+                    Some(deadline) => {
+                        let remaining = deadline.saturating_sub(started.elapsed());
+                        tokio::time::timeout(remaining, send)
+                            .await
+                            .map_err(|_| exceeded(attempt + 1))?
+                    }
+                    None => send.await,
+                };
+                let reason = match &result {
+                    Ok(response) if response.status == StatusCode::TOO_MANY_REQUESTS => {
+                        Some(RetryOn::RateLimited)
+                    }
+                    Ok(response) if response.status.is_server_error() => Some(RetryOn::ServerError),
+                    Err(TError::Request(e)) if !e.is_builder() => Some(RetryOn::Network),
+                    _ => None,
+                };
+                let reason = match reason.filter(|reason| self.retry_on.contains(reason)) {
+                    Some(reason) if attempt < retries => reason,
+                    _ => return result,
+                };
+                let mut delay = backoff(self.retry_backoff, attempt);
+                if let Ok(SecureResponse {
+                    retry_after: Some(retry_after),
+                    ..
+                }) = &result
+                {
+                    delay = delay.max(*retry_after);
+                }
+                if let Some(max) = self.max_retry_backoff {
+                    delay = delay.min(max);
+                }
+                if self
+                    .operation_timeout
+                    .is_some_and(|deadline| started.elapsed() + delay >= deadline)
+                {
+                    return Err(exceeded(attempt + 1));
+                }
+                if let Some(hook) = &self.on_retry {
+                    hook(&RetryEvent {
+                        attempt: attempt + 1,
+                        delay,
+                        reason,
+                    });
+                }
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+        }
+        #[cfg(target_arch = "wasm32")]
+        self.send_attempt(url, body.as_deref(), method, id).await
+    }
+
+    // Encrypts the headers and body again on every attempt, the encryptor may use a
+    // fresh nonce each time.
+    async fn send_attempt(
+        &self,
+        url: Url,
+        body: Option<&str>,
+        method: Method,
+        id: Uuid,
+    ) -> SecureResult<SecureResponse> {
+        let _permit = match &self.limit {
+            Some((_, semaphore)) => semaphore.acquire().await.ok(),
+            None => None,
+        };
+        let _in_flight = InFlight::enter(&self.in_flight);
+        let encryptor = VERSION.encryptor();
+        let id_header = encryptor.encrypt_header(id).await?;
+        let http = self.http()?;
+        let request = http
+            .request(method, url)
+            .headers(self.headers.clone())
+            .header(AUTHORIZATION, id_header)
+            .header(VERSION_STR, "v1");
+        // .header("Agents", json!(agents).to_string());
+        #[cfg(not(target_arch = "wasm32"))]
+        let request = match self.timeout {
+            Some(timeout) => request.timeout(timeout),
+            None => request,
+        };
+
+        let req = match body {
+            Some(body) => request
+                .header(CONTENT_TYPE, HeaderValue::from_static("application/json"))
+                .body(encryptor.encrypt(id, body).await?),
+            None => request,
+        };
+
+        let response = http.execute(req.build()?).await?;
+        let status = response.status();
+        let retry_after = response
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse().ok())
+            .map(Duration::from_secs);
+        let body = response.text().await?;
+        match body.is_empty() {
+            true => Ok(SecureResponse {
+                status,
+                retry_after,
+                body: None,
+            }),
+            false => {
+                // Routing errors and proxy pages are sent before the API is reached,
+                // so a failed response may legitimately be plain text.
+                let decrypted_body = match encryptor.decrypt(id, &body).await {
+                    Ok(decrypted) => decrypted,
+                    Err(_) if !status.is_success() => body,
+                    Err(e) => return Err(e.into()),
+                };
+                Ok(SecureResponse {
+                    status,
+                    retry_after,
+                    body: Some(decrypted_body),
+                })
+            }
+        }
+    }
+
+    /// Validates `license` for `application` and returns the server's token.
+    ///
+    /// Every error carries a `kind()` for logging and a `remediation()` that tells the
+    /// application what to do next.
+    ///
+    /// ```
+    /// # #[cfg(all(feature = "test-util", feature = "mock-server"))]
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use chipa_license_validator::{
+    ///     test_util::{MockConfig, MockServer, Scenario},
+    ///     Error, LicenseClient, LicenseId, Remediation,
+    /// };
+    ///
+    /// fn explain(e: &Error) -> String {
+    ///     match e.remediation() {
+    ///         Remediation::RetryAfter(delay) => format!("busy, retry in {:?}", delay),
+    ///         Remediation::RetryLater => "server unavailable, retry later".to_string(),
+    ///         Remediation::CheckInternet => "cannot reach the license server".to_string(),
+    ///         Remediation::Renew { portal_url } => match portal_url {
+    ///             Some(url) => format!("license expired, renew at {}", url),
+    ///             None => "license expired".to_string(),
+    ///         },
+    ///         Remediation::UpdateApp => "please update the application".to_string(),
+    ///         Remediation::ContactSupport => format!("{} error, contact support", e.kind()),
+    ///     }
+    /// }
+    ///
+    /// let server = MockServer::start(MockConfig::default()).await?;
+    /// let client = LicenseClient::new(server.url());
+    ///
+    /// // The license server rejected the license.
+    /// let e = client
+    ///     .validate_license(Scenario::Expired.license(), "my-app".to_string())
+    ///     .await
+    ///     .unwrap_err();
+    /// assert_eq!(e.kind(), "response");
+    /// assert!(explain(&e).starts_with("license expired"));
+    ///
+    /// // The server asked us to slow down.
+    /// let e = client
+    ///     .validate_license(Scenario::RateLimited.license(), "my-app".to_string())
+    ///     .await
+    ///     .unwrap_err();
+    /// assert!(matches!(e.remediation(), Remediation::RetryAfter(_)));
+    ///
+    /// // The license never left the machine.
+    /// let e = "not-a-license".parse::<LicenseId>().unwrap_err();
+    /// assert!(matches!(e, Error::InvalidLicense(_)));
+    ///
+    /// // The server is gone.
+    /// let url = server.url();
+    /// server.stop().await;
+    /// let e = LicenseClient::new(url)
+    ///     .validate_license(Scenario::Valid.license(), "my-app".to_string())
+    ///     .await
+    ///     .unwrap_err();
+    /// assert_eq!(explain(&e), "cannot reach the license server");
+    /// # Ok(())
+    /// # }
+    /// # #[cfg(not(all(feature = "test-util", feature = "mock-server")))]
+    /// # fn main() {}
+    /// ```
+    pub async fn validate_license(
+        &self,
+        license: impl Into<LicenseId>,
+        application: String,
+    ) -> SecureResult<String> {
+        let license = license.into();
+        let url = self.validate_url(&license, &application)?;
+        let timer = Timer::start();
+        let req = self
+            ._send_secure::<()>(url, None, Method::GET, license.identity())
+            .await;
+        timer.finish(Operation::Validate, validated_bytes(&req));
+        let req = req?;
+        if req.status.is_success() {
+            let body = req
+                .success_json::<ValidateResponse>("/subscriptions/validateapp")?
+                .token;
+            Ok(body)
+        } else {
+            Err(req.error())
+        }
+    }
+
+    /// Validates like `validate_license` and keeps the last successful validation in
+    /// an encrypted `.chipa` file at `cache_path`, with a key derived from the license.
+    /// `cache_path` gets the `.chipa` extension if it lacks it, like `ChipaFile::save`.
+    ///
+    /// When the license server cannot be reached (`TError::Request`), a validation of
+    /// the same license and application cached at most `grace` ago is returned instead.
+    /// Any answer from the server is final: a rejection is returned as is and also
+    /// removes the cache, so a revoked license cannot fall back to it. A missing, stale
+    /// or unreadable cache returns the network error. Writing the cache is best effort,
+    /// a license the server accepted is never turned into an error by a full disk or a
+    /// read-only directory.
+    ///
+    /// A cache that cannot be decoded is renamed to `<cache_path>.corrupt-<unix millis>`,
+    /// reported to the `set_quarantine_observer` observer and listed by
+    /// `quarantined_files`, and a fresh one is written on the next success.
+    #[cfg(feature = "fs")]
+    pub async fn validate_license_cached(
+        &self,
+        license: impl Into<LicenseId>,
+        application: String,
+        grace: Duration,
+        cache_path: &str,
+    ) -> SecureResult<String> {
+        self.validate_license_cached_with(license, application, grace, cache_path, &RealFs)
+            .await
+    }
+
+    /// `validate_license_cached` with the cache kept in `fs` instead of on disk.
+    #[cfg(feature = "fs")]
+    pub async fn validate_license_cached_with(
         &self,
+        license: impl Into<LicenseId>,
+        application: String,
+        grace: Duration,
+        cache_path: &str,
+        fs: &dyn ChipaFs,
+    ) -> SecureResult<String> {
+        let license = license.into();
+        let identity = license.identity();
+        let cache_file = ChipaFile::chipa_path(cache_path);
+        let cache_path = cache_file.to_string_lossy();
+        match self.validate_license(license, application.clone()).await {
+            Ok(token) => {
+                // Moves a corrupt cache aside before it is overwritten.
+                self.read_cache(&cache_path, identity, &application, fs);
+                let validated_at = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs();
+                let cached = CachedValidation {
+                    license: identity,
+                    application,
+                    token: token.clone(),
+                    validated_at,
+                };
+                // Without the cache the next offline start fails, but this one succeeded.
+                let _ = cached.save(&cache_path, fs);
+                Ok(token)
+            }
+            Err(e @ TError::Request(_)) => {
+                match self.read_cache(&cache_path, identity, &application, fs) {
+                    Some(cached) if cached.age().is_some_and(|age| age <= grace) => {
+                        Ok(cached.token)
+                    }
+                    _ => Err(e),
+                }
+            }
+            Err(TError::Response(e)) => {
+                let rejected = e.status.is_some_and(|status| {
+                    status.is_client_error() && status != StatusCode::TOO_MANY_REQUESTS
+                });
+                if rejected {
+                    let _ = fs.remove(&cache_file);
+                }
+                Err(TError::Response(e))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    // Unreadable caches are moved aside, but an I/O error says nothing about the file.
+    #[cfg(feature = "fs")]
+    fn read_cache(
+        &self,
+        path: &str,
         license: Uuid,
+        application: &str,
+        fs: &dyn ChipaFs,
+    ) -> Option<CachedValidation> {
+        match CachedValidation::load(path, license, application, fs) {
+            Ok(cached) => cached,
+            Err(ChipaError::FileCreation(_)) => None,
+            Err(e) => {
+                self.quarantine(Path::new(path), e.to_string(), fs);
+                None
+            }
+        }
+    }
+
+    #[cfg(feature = "fs")]
+    fn quarantine(&self, path: &Path, reason: String, fs: &dyn ChipaFs) {
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let mut moved_to = path.as_os_str().to_owned();
+        moved_to.push(format!(".corrupt-{}", millis));
+        let moved_to = PathBuf::from(moved_to);
+        // A cache that cannot be moved aside is still replaced by the next save.
+        if fs.rename(path, &moved_to).is_err() {
+            return;
+        }
+        let quarantined = Quarantined {
+            path: path.to_path_buf(),
+            moved_to,
+            reason,
+        };
+        if let Some(observer) = &self.quarantine_observer {
+            observer(&quarantined);
+        }
+        self.quarantined
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(quarantined);
+    }
+
+    pub async fn validate_license_raw(
+        &self,
+        license: impl Into<LicenseId>,
+        application: String,
+    ) -> SecureResult<RawValidation> {
+        let license = license.into();
+        let url = self.validate_url(&license, &application)?;
+        let timer = Timer::start();
+        let req = self
+            ._send_secure::<()>(url, None, Method::GET, license.identity())
+            .await;
+        timer.finish(Operation::Validate, validated_bytes(&req));
+        let req = req?;
+        let body = match req.body {
+            Some(body) => match serde_json::from_str(&body) {
+                Ok(body) => body,
+                Err(_) if !req.status.is_success() => Value::String(body),
+                Err(e) => return Err(e.into()),
+            },
+            None => Value::Null,
+        };
+        Ok(RawValidation {
+            status: req.status,
+            body,
+        })
+    }
+
+    pub async fn validate_license_with_context(
+        &self,
+        license: impl Into<LicenseId>,
         application: String,
+        context: Value,
     ) -> SecureResult<String> {
-        let url = format!(
-            "{}/subscriptions/validateapp/{}/{}",
-            self.base_url, license, application
-        );
+        let license = license.into();
+        let context = merge_context(&self.default_context, context);
+        let size = serde_json::to_vec(&context)?.len();
+        if size > MAX_CONTEXT_SIZE {
+            return Err(TError::ContextTooLarge {
+                size,
+                max: MAX_CONTEXT_SIZE,
+            });
+        }
+        let url = self.validate_url(&license, &application)?;
+        self.require(Capability::ValidateContext)?;
+        let timer = Timer::start();
         let req = self
-            ._send_secure::<()>(url, None, Method::GET, license)
-            .await?;
+            ._send_secure(url, Some(context), Method::POST, license.identity())
+            .await;
+        timer.finish(Operation::Validate, validated_bytes(&req));
+        let req = req?;
         if req.status.is_success() {
-            let body = req.json::<ValidateResponse>()?.token;
+            let body = req
+                .success_json::<ValidateResponse>("/subscriptions/validateapp")?
+                .token;
             Ok(body)
+        } else if self.record_missing(Capability::ValidateContext, &req) {
+            Err(TError::unsupported(Capability::ValidateContext))
+        } else {
+            Err(req.error())
+        }
+    }
+
+    pub async fn seat_usage(&self, license: impl Into<LicenseId>) -> SecureResult<SeatUsage> {
+        let license = license.into();
+        let url = self.endpoint(&["subscriptions", "seats", &license.to_string()])?;
+        if let Err(e) = self.require(Capability::Seats) {
+            return match self.lenient {
+                true => Ok(SeatUsage::unknown()),
+                false => Err(e),
+            };
+        }
+        let req = self
+            ._send_secure::<()>(url, None, Method::GET, license.identity())
+            .await?;
+        if req.status.is_success() {
+            req.success_json::<SeatUsage>("/subscriptions/seats")
+        } else if req.status == StatusCode::NOT_FOUND && self.lenient {
+            Ok(SeatUsage::unknown())
+        } else if self.record_missing(Capability::Seats, &req) {
+            Err(TError::unsupported(Capability::Seats))
+        } else {
+            Err(req.error())
+        }
+    }
+
+    /// Takes a seat of a seat-limited license for the machine `machine_id`, usually
+    /// `DeviceFingerprint::collect().activation_id()`. Activating a machine that
+    /// already holds a seat refreshes its token. A full license fails with
+    /// `TError::NoSeatsAvailable`, listing the seats when the server sends them.
+    pub async fn activate_license(
+        &self,
+        license: impl Into<LicenseId>,
+        application: String,
+        machine_id: String,
+    ) -> SecureResult<ActivationToken> {
+        let body = serde_json::json!({
+            "machine_id": machine_id,
+            "machine_label": self.machine_label,
+        });
+        self.activation("activate", license.into(), &application, Method::POST, body)
+            .await?
+            .success_json::<ActivationResponse>("/subscriptions/activate")
+            .map(ActivationToken::from)
+    }
+
+    /// Frees the seat held by `machine_id`. A machine without a seat fails with an
+    /// error for which `is_unknown_machine` is true.
+    pub async fn deactivate_license(
+        &self,
+        license: impl Into<LicenseId>,
+        application: String,
+        machine_id: String,
+    ) -> SecureResult<()> {
+        let body = serde_json::json!({ "machine_id": machine_id });
+        self.activation("activate", license.into(), &application, Method::DELETE, body)
+            .await
+            .map(|_| ())
+    }
+
+    /// Frees the seat of another machine by its `SeatInfo::id`, e.g. a stale one listed
+    /// by `TError::NoSeatsAvailable`. An activation the server does not know fails with
+    /// an error for which `is_unknown_machine` is true.
+    pub async fn deactivate_activation(
+        &self,
+        license: impl Into<LicenseId>,
+        application: String,
+        activation_id: Uuid,
+    ) -> SecureResult<()> {
+        let body = serde_json::json!({ "activation_id": activation_id });
+        self.activation("activate", license.into(), &application, Method::DELETE, body)
+            .await
+            .map(|_| ())
+    }
+
+    /// Moves the seat of the activation `from_activation` to the machine `machine_id` in
+    /// one server call, so there is no moment when neither machine holds it, e.g. when
+    /// the user replaces a laptop. The old machine's token stops working.
+    ///
+    /// Servers may refuse to move the seat of a machine that is still in use. If the old
+    /// machine is gone and cannot deactivate itself, `force` asks the server to move the
+    /// seat anyway; servers allow that only so often and fail with
+    /// `TError::TransferCooldown` until the next forced transfer may be made.
+    pub async fn transfer_license(
+        &self,
+        license: impl Into<LicenseId>,
+        application: String,
+        from_activation: Uuid,
+        machine_id: String,
+        force: bool,
+    ) -> SecureResult<ActivationToken> {
+        let body = serde_json::json!({
+            "from_activation": from_activation,
+            "machine_id": machine_id,
+            "machine_label": self.machine_label,
+            "force": force,
+        });
+        self.activation("transfer", license.into(), &application, Method::POST, body)
+            .await?
+            .success_json::<ActivationResponse>("/subscriptions/transfer")
+            .map(ActivationToken::from)
+    }
+
+    /// Confirms that the machine still holds the seat `token` was issued for and
+    /// returns a refreshed token. Call it every `heartbeat_interval`; once the server
+    /// has freed the seat this fails with an error for which `is_unknown_machine` is
+    /// true.
+    pub async fn heartbeat(
+        &self,
+        license: impl Into<LicenseId>,
+        application: String,
+        token: String,
+    ) -> SecureResult<ActivationToken> {
+        let body = serde_json::json!({ "token": token });
+        match self
+            .activation("heartbeat", license.into(), &application, Method::POST, body)
+            .await
+        {
+            Ok(req) => req
+                .success_json::<ActivationResponse>("/subscriptions/heartbeat")
+                .map(ActivationToken::from),
+            Err(e)
+                if self.lenient
+                    && (matches!(e, TError::UnsupportedByServer { .. }) || e.is_bare_404()) =>
+            {
+                Ok(ActivationToken {
+                    token,
+                    heartbeat_interval: None,
+                    activation_id: None,
+                })
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    // Sends an activation request and returns the response if it succeeded.
+    async fn activation(
+        &self,
+        action: &str,
+        license: LicenseId,
+        application: &str,
+        method: Method,
+        body: Value,
+    ) -> SecureResult<SecureResponse> {
+        let capability = match action {
+            "transfer" => Capability::Transfer,
+            _ => Capability::Activation,
+        };
+        let url = self.endpoint(&["subscriptions", action, &license.to_string(), application])?;
+        self.require(capability)?;
+        let req = self
+            ._send_secure(url, Some(body), method, license.identity())
+            .await?;
+        if req.status.is_success() {
+            Ok(req)
+        } else if self.record_missing(capability, &req) {
+            Err(TError::unsupported(capability))
+        } else {
+            Err(req.activation_error())
+        }
+    }
+
+    /// What the license server at this client's base URL supports, for diagnostics.
+    /// The first call asks `/capabilities`; a server that predates it is reported by
+    /// `predates_discovery` and only tells what it lacks by answering 405.
+    /// Answers are cached per base URL for the life of the process, and calls that
+    /// need a capability the server is known to lack fail fast with
+    /// `TError::UnsupportedByServer`.
+    pub async fn server_capabilities(&self) -> SecureResult<ServerCapabilities> {
+        if let Some(known) = capabilities().get(&self.base_url).filter(|known| known.asked) {
+            return Ok(known.clone());
+        }
+        let url = self.endpoint(&["capabilities"])?;
+        let req = self
+            ._send_secure::<()>(url, None, Method::GET, Uuid::nil())
+            .await?;
+        let listed = if req.status.is_success() {
+            let response = req.success_json::<CapabilitiesResponse>("/capabilities")?;
+            let listed = response
+                .capabilities
+                .iter()
+                .filter_map(|name| Capability::from_name(name))
+                .collect();
+            Some((response.server_version, listed))
+        } else if req.status == StatusCode::NOT_FOUND {
+            None
         } else {
-            let body = req.json::<ApiError>()?;
-            Err(TError::from(body))
+            return Err(req.error());
+        };
+        let mut cache = capabilities();
+        let known = cache.entry(self.base_url.clone()).or_default();
+        known.asked = true;
+        if let Some((server_version, listed)) = listed {
+            known.server_version = server_version;
+            known.listed = Some(listed);
         }
+        Ok(known.clone())
+    }
+
+    // Only consults what is already known, so supported calls cost no extra round trip.
+    fn require(&self, capability: Capability) -> SecureResult<()> {
+        let known = capabilities()
+            .get(&self.base_url)
+            .and_then(|known| known.supports(capability));
+        match known {
+            Some(false) => Err(TError::unsupported(capability)),
+            _ => Ok(()),
+        }
+    }
+
+    // A 405 without a code is a server whose route lacks the method, unless it listed
+    // the capability. A bare 404 proves nothing: old servers answer it for unknown
+    // licenses too, and one mistyped license must not disable a feature for all.
+    fn record_missing(&self, capability: Capability, req: &SecureResponse) -> bool {
+        if req.status != StatusCode::METHOD_NOT_ALLOWED
+            || req.api_error().is_ok_and(|e| e.code.is_some())
+        {
+            return false;
+        }
+        let mut cache = capabilities();
+        let known = cache.entry(self.base_url.clone()).or_default();
+        if known.supports(capability) == Some(true) {
+            return false;
+        }
+        known.missing.insert(capability);
+        true
+    }
+
+    /// Validates `license` and opens the sealed file at `path` with the token the
+    /// server returns, see `ChipaFile::open_sealed`. Files are bound to the token they
+    /// were sealed with, so after the server rotates the license's token they fail with
+    /// `ChipaError::Tampered` until the vendor seals them again.
+    #[cfg(feature = "fs")]
+    pub async fn open_sealed<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        license: impl Into<LicenseId>,
+        application: String,
+    ) -> SecureResult<T> {
+        let license = license.into();
+        let token = self
+            .validate_license(license.clone(), application)
+            .await
+            .map_err(|e| TError::NotValidated(Box::new(e)))?;
+        Ok(ChipaFile::open_sealed(path, license.identity(), &token)?)
     }
 }
 
+#[cfg(feature = "client")]
 impl SecureResponse {
     pub fn json<T>(&self) -> SecureResult<T>
     where
@@ -151,4 +1915,1523 @@ impl SecureResponse {
             ))),
         }
     }
+
+    pub fn api_error(&self) -> SecureResult<ApiError> {
+        let mut error = self.json::<ApiError>()?;
+        error.status = Some(self.status);
+        error.retry_after = self.retry_after;
+        Ok(error)
+    }
+
+    /// The error a failed response stands for. A server that dropped this client's
+    /// protocol version answers `426 Upgrade Required` with the `unsupported_version`
+    /// code, in plain text since it cannot encrypt for us; that becomes
+    /// `TError::ClientTooOld`.
+    pub fn error(&self) -> TError {
+        if self.status == StatusCode::UPGRADE_REQUIRED {
+            if let Ok(unsupported) = self.json::<UnsupportedVersion>() {
+                if unsupported.code == UNSUPPORTED_VERSION {
+                    let client_version = u16::from(VERSION);
+                    return TError::ClientTooOld {
+                        // A server that omits its minimum still needs something newer.
+                        server_min_version: unsupported
+                            .min_version
+                            .unwrap_or(client_version + 1),
+                        client_version,
+                        download_url: unsupported.download_url,
+                    };
+                }
+            }
+        }
+        match self.api_error() {
+            Ok(error) => TError::Response(error),
+            Err(e) => e,
+        }
+    }
+
+    // Like `error`, with the seats a full license lists. Servers that only send the
+    // error string list none.
+    fn activation_error(&self) -> TError {
+        match self.error() {
+            TError::Response(error) if error.is_seat_limit_reached() => {
+                let seats = self.json::<SeatsHeld>().map(|held| held.seats);
+                TError::NoSeatsAvailable {
+                    seats: seats.unwrap_or_default(),
+                    error: Box::new(error),
+                }
+            }
+            TError::Response(error) if error.code.as_deref() == Some(TRANSFER_COOLDOWN) => {
+                TError::TransferCooldown {
+                    retry_after: error.retry_after,
+                }
+            }
+            e => e,
+        }
+    }
+
+    pub fn success_json<T>(&self, endpoint: &str) -> SecureResult<T>
+    where
+        T: Send + DeserializeOwned,
+    {
+        match &self.body {
+            Some(_) => self.json(),
+            None => Err(TError::EmptyResponse {
+                endpoint: endpoint.to_string(),
+                status: self.status,
+            }),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "mock-server"))]
+mod tests {
+    use super::*;
+    use crate::mock::{MockConfig, MockServer, Scenario};
+
+    async fn server(scenario: Scenario) -> MockServer {
+        MockServer::start(MockConfig {
+            default_scenario: scenario,
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_mock_rejects_forged_authorization() {
+        let server = server(Scenario::Valid).await;
+        let license = Uuid::new_v4();
+        let url = format!("{}/subscriptions/validateapp/{}/my-app", server.url(), license);
+        let forged = VERSION.encryptor().encrypt_header(Uuid::new_v4()).await.unwrap();
+        for header in [forged.as_str(), "forged"] {
+            let response = reqwest_wasm::Client::new()
+                .get(&url)
+                .header(AUTHORIZATION, header)
+                .header(VERSION_STR, "v1")
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_validate_license_valid() {
+        let server = server(Scenario::Valid).await;
+        let client = TClient::new(server.url());
+        let token = client
+            .validate_license(Uuid::new_v4(), "my-app".to_string())
+            .await
+            .unwrap();
+        assert_eq!(token, "mock-token");
+        assert_eq!(server.request_count(), 1);
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_validate_license_scenarios() {
+        let server = server(Scenario::Valid).await;
+        let client = TClient::new(server.url());
+
+        let expired = client
+            .validate_license(Scenario::Expired.license(), "my-app".to_string())
+            .await;
+        assert!(matches!(&expired, Err(TError::Response(e)) if e.error == "License has expired"));
+        assert_eq!(
+            expired.unwrap_err().remediation(),
+            Remediation::Renew {
+                portal_url: Some("https://portal.example.com/renew".to_string())
+            }
+        );
+
+        let limited = client
+            .validate_license(Scenario::RateLimited.license(), "my-app".to_string())
+            .await;
+        assert!(matches!(limited, Err(TError::Response(_))));
+        assert_eq!(
+            limited.unwrap_err().remediation(),
+            Remediation::RetryAfter(Duration::from_secs(30))
+        );
+
+        let malformed = client
+            .validate_license(Scenario::Malformed.license(), "my-app".to_string())
+            .await;
+        assert!(matches!(malformed, Err(TError::Parsing(_))));
+
+        let empty = client
+            .validate_license(Scenario::Empty.license(), "my-app".to_string())
+            .await;
+        assert!(matches!(
+            empty,
+            Err(TError::EmptyResponse { endpoint, status })
+                if endpoint == "/subscriptions/validateapp" && status == StatusCode::OK
+        ));
+
+        let empty_error = client
+            .validate_license(Scenario::EmptyError.license(), "my-app".to_string())
+            .await;
+        assert!(matches!(empty_error, Err(TError::Parsing(_))));
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_validate_license_error_classes() {
+        let server = server(Scenario::Valid).await;
+        let client = TClient::new(server.url());
+        let validate = |scenario: Scenario| {
+            client.validate_license(scenario.license(), "my-app".to_string())
+        };
+
+        let expired = validate(Scenario::Expired).await.unwrap_err();
+        assert!(expired.is_expired());
+        assert_eq!(expired.status(), Some(StatusCode::GONE));
+        assert!(!expired.is_network() && !expired.is_unauthorized_app());
+
+        let unpaid = validate(Scenario::Unpaid).await.unwrap_err();
+        assert!(unpaid.is_expired());
+        assert_eq!(unpaid.status(), Some(StatusCode::PAYMENT_REQUIRED));
+        assert!(matches!(&unpaid, TError::Response(e) if e.code.as_deref() == Some("license_expired")));
+
+        let not_found = validate(Scenario::NotFound).await.unwrap_err();
+        assert!(not_found.is_license_not_found());
+        assert!(!not_found.is_expired());
+
+        let unauthorized = validate(Scenario::UnauthorizedApp).await.unwrap_err();
+        assert!(unauthorized.is_unauthorized_app());
+        assert_eq!(unauthorized.status(), Some(StatusCode::FORBIDDEN));
+        assert!(!unauthorized.is_expired() && !unauthorized.is_license_not_found());
+
+        let limited = validate(Scenario::RateLimited).await.unwrap_err();
+        assert!(!limited.is_expired() && !limited.is_unauthorized_app() && !limited.is_network());
+        server.stop().await;
+    }
+
+    #[test]
+    fn test_error_code_wins_over_status() {
+        let response = SecureResponse {
+            status: StatusCode::FORBIDDEN,
+            retry_after: None,
+            body: Some(r#"{ "error": "Expired", "code": "license_expired" }"#.to_string()),
+        };
+        let error = TError::NotValidated(Box::new(response.error()));
+        assert!(error.is_expired());
+        assert!(!error.is_unauthorized_app());
+        assert_eq!(error.status(), Some(StatusCode::FORBIDDEN));
+
+        let response = SecureResponse {
+            body: Some(r#"{ "error": "Seat limit reached", "code": "seat_limit" }"#.to_string()),
+            ..response
+        };
+        assert!(!response.error().is_unauthorized_app());
+    }
+
+    #[test]
+    fn test_seat_limit_without_seats() {
+        let response = SecureResponse {
+            status: StatusCode::CONFLICT,
+            retry_after: None,
+            body: Some(r#"{ "error": "Seat limit reached" }"#.to_string()),
+        };
+        let error = response.activation_error();
+        assert!(matches!(&error, TError::NoSeatsAvailable { seats, .. } if seats.is_empty()));
+        assert!(error.is_seat_limit_reached());
+        assert_eq!(error.kind(), "no_seats_available");
+        assert_eq!(error.to_string(), "Seat limit reached");
+
+        let response = SecureResponse {
+            status: StatusCode::NOT_FOUND,
+            body: Some(r#"{ "error": "Gone", "code": "machine_not_activated" }"#.to_string()),
+            ..response
+        };
+        assert!(matches!(response.activation_error(), TError::Response(_)));
+    }
+
+    #[tokio::test]
+    async fn test_validate_license_client_too_old() {
+        let server = server(Scenario::ClientTooOld).await;
+        let client = TClient::new(server.url());
+        let error = client
+            .validate_license(Uuid::new_v4(), "my-app".to_string())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            &error,
+            TError::ClientTooOld {
+                server_min_version: 2,
+                client_version: 1,
+                download_url: Some(url),
+            } if url == "https://example.com/download"
+        ));
+        assert_eq!(error.kind(), "client_too_old");
+        assert_eq!(error.remediation(), Remediation::UpdateApp);
+        assert_eq!(
+            error.to_string(),
+            "Client too old, the license server requires protocol version 2 or newer but this client uses version 1, download the latest version from https://example.com/download"
+        );
+
+        let context = client
+            .validate_license_with_context(Uuid::new_v4(), "my-app".to_string(), serde_json::json!({}))
+            .await;
+        assert!(matches!(context, Err(TError::ClientTooOld { .. })));
+        let raw = client
+            .validate_license_raw(Uuid::new_v4(), "my-app".to_string())
+            .await
+            .unwrap();
+        assert_eq!(raw.status, StatusCode::UPGRADE_REQUIRED);
+        assert_eq!(raw.body["code"], "unsupported_version");
+        server.stop().await;
+    }
+
+    #[test]
+    fn test_upgrade_required_without_version_code() {
+        let response = SecureResponse {
+            status: StatusCode::UPGRADE_REQUIRED,
+            retry_after: None,
+            body: Some(r#"{ "error": "Please update" }"#.to_string()),
+        };
+        let error = response.error();
+        assert!(matches!(&error, TError::Response(e) if e.error == "Please update"));
+        assert_eq!(error.remediation(), Remediation::UpdateApp);
+
+        let response = SecureResponse {
+            body: Some(r#"{ "error": "Unsupported", "code": "unsupported_version" }"#.to_string()),
+            ..response
+        };
+        assert!(matches!(
+            response.error(),
+            TError::ClientTooOld {
+                server_min_version: 2,
+                client_version: 1,
+                download_url: None,
+            }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_validate_license_key() {
+        let server = server(Scenario::Valid).await;
+        let client = TClient::new(server.url());
+        let key: LicenseId = "chipa-ab12-cd34-ef56-gh78".parse().unwrap();
+        let token = client
+            .validate_license(key.clone(), "my-app".to_string())
+            .await
+            .unwrap();
+        assert_eq!(token, "mock-token");
+        let context = client
+            .validate_license_with_context(key, "my-app".to_string(), serde_json::json!({}))
+            .await
+            .unwrap();
+        assert_eq!(context, "mock-token");
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_seat_usage() {
+        let server = server(Scenario::Valid).await;
+        let usage = TClient::new(server.url())
+            .seat_usage(Uuid::new_v4())
+            .await
+            .unwrap();
+        assert!(usage.is_known());
+        assert_eq!((usage.used, usage.total), (Some(3), Some(10)));
+        assert_eq!(usage.samples.len(), 3);
+        assert_eq!(usage.samples[2].used, 3);
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_seat_usage_older_server() {
+        let server = MockServer::start(MockConfig {
+            seats: None,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let client = TClient::new(server.url());
+        // Every call asks again, a 404 may be about the license and not the endpoint.
+        for attempt in 1..=2 {
+            let strict = client.seat_usage(Uuid::new_v4()).await;
+            assert!(matches!(
+                strict,
+                Err(TError::Response(e)) if e.status == Some(StatusCode::NOT_FOUND)
+            ));
+            assert_eq!(server.request_count(), attempt);
+        }
+        let lenient = client
+            .set_lenient(true)
+            .seat_usage(Uuid::new_v4())
+            .await
+            .unwrap();
+        assert_eq!(lenient, SeatUsage::unknown());
+        assert!(!lenient.is_known());
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_server_capabilities() {
+        let server = MockServer::start(MockConfig {
+            seats: None,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let client = TClient::new(server.url());
+        let known = client.server_capabilities().await.unwrap();
+        assert_eq!(known.server_version.as_deref(), Some("mock"));
+        assert!(!known.predates_discovery());
+        assert_eq!(known.supports(Capability::ValidateContext), Some(true));
+        assert_eq!(known.supports(Capability::Seats), Some(false));
+        client.server_capabilities().await.unwrap();
+        assert_eq!(server.request_count(), 1);
+
+        let strict = client.seat_usage(Uuid::new_v4()).await.unwrap_err();
+        assert_eq!(strict.kind(), "unsupported_by_server");
+        assert_eq!(strict.remediation(), Remediation::ContactSupport);
+        let lenient = client.set_lenient(true).seat_usage(Uuid::new_v4()).await;
+        assert_eq!(lenient.unwrap(), SeatUsage::unknown());
+        assert_eq!(server.request_count(), 1);
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_legacy_server_degrades() {
+        let server = MockServer::start(MockConfig {
+            legacy: true,
+            seats: None,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let client = TClient::new(server.url());
+        let known = client.server_capabilities().await.unwrap();
+        assert!(known.predates_discovery());
+        assert_eq!(known.server_version, None);
+        assert_eq!(known.supports(Capability::ValidateContext), None);
+
+        let context = || serde_json::json!({ "seat": 1 });
+        for _ in 0..2 {
+            let with_context = client
+                .validate_license_with_context(Uuid::new_v4(), "my-app".to_string(), context())
+                .await;
+            assert!(matches!(
+                with_context,
+                Err(TError::UnsupportedByServer {
+                    capability: Capability::ValidateContext,
+                    min_server_version: "1.2.0",
+                })
+            ));
+        }
+        // The second call failed fast, without asking the server again.
+        assert_eq!(server.request_count(), 2);
+        let known = client.server_capabilities().await.unwrap();
+        assert_eq!(known.supports(Capability::ValidateContext), Some(false));
+
+        let token = client
+            .validate_license(Uuid::new_v4(), "my-app".to_string())
+            .await
+            .unwrap();
+        assert_eq!(token, "mock-token");
+        let missing = client
+            .validate_license(Scenario::NotFound.license(), "my-app".to_string())
+            .await
+            .unwrap_err();
+        assert!(missing.is_license_not_found());
+
+        let license = Uuid::new_v4();
+        // A bare 404 may be an unknown license, so it is reported as is and cached nowhere.
+        for _ in 0..2 {
+            let activation = client
+                .activate_license(license, "my-app".to_string(), "machine".to_string())
+                .await;
+            assert!(matches!(
+                activation,
+                Err(TError::Response(e)) if e.status == Some(StatusCode::NOT_FOUND)
+            ));
+        }
+        let known = client.server_capabilities().await.unwrap();
+        assert_eq!(known.supports(Capability::Activation), None);
+        let heartbeat = client
+            .set_lenient(true)
+            .heartbeat(license, "my-app".to_string(), "kept".to_string())
+            .await
+            .unwrap();
+        assert_eq!(heartbeat.token, "kept");
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_deactivate_stale_seat() {
+        let server = MockServer::start(MockConfig {
+            seats: Some((0, 2)),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let license = Uuid::new_v4();
+        let app = || "my-app".to_string();
+        let on = |label: &str| {
+            TClient::new(server.url()).set_machine_label(Some(label.to_string()))
+        };
+        let old = on("Old laptop")
+            .activate_license(license, app(), "old".to_string())
+            .await
+            .unwrap();
+        on("Desktop")
+            .activate_license(license, app(), "desktop".to_string())
+            .await
+            .unwrap();
+
+        let client = on("New laptop");
+        let full = client
+            .activate_license(license, app(), "new".to_string())
+            .await
+            .unwrap_err();
+        assert_eq!(full.to_string(), "Seat limit reached, held by Old laptop, Desktop");
+        let TError::NoSeatsAvailable { seats, .. } = full else {
+            panic!("expected NoSeatsAvailable, got {:?}", full);
+        };
+        let labels: Vec<_> = seats.iter().map(|seat| seat.machine_label.as_deref()).collect();
+        assert_eq!(labels, [Some("Old laptop"), Some("Desktop")]);
+        assert!(seats.iter().all(|seat| seat.last_seen > 0));
+
+        let stale = seats[0].id;
+        assert_eq!(old.activation_id, Some(stale));
+        client.deactivate_activation(license, app(), stale).await.unwrap();
+        client
+            .activate_license(license, app(), "new".to_string())
+            .await
+            .unwrap();
+        let again = client
+            .deactivate_activation(license, app(), stale)
+            .await
+            .unwrap_err();
+        assert!(again.is_unknown_machine());
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_transfer_license() {
+        let server = MockServer::start(MockConfig {
+            seats: Some((0, 1)),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let client = TClient::new(server.url());
+        let license = Uuid::new_v4();
+        let app = || "my-app".to_string();
+        let machine = |id: &str| id.to_string();
+
+        let old = client
+            .activate_license(license, app(), machine("old"))
+            .await
+            .unwrap();
+        let old_id = old.activation_id.unwrap();
+        let new = client
+            .transfer_license(license, app(), old_id, machine("new"), false)
+            .await
+            .unwrap();
+        assert_ne!(new.activation_id, Some(old_id));
+        let stale = client.heartbeat(license, app(), old.token).await.unwrap_err();
+        assert!(stale.is_unknown_machine());
+        client.heartbeat(license, app(), new.token).await.unwrap();
+        let gone = client
+            .transfer_license(license, app(), old_id, machine("other"), false)
+            .await
+            .unwrap_err();
+        assert!(gone.is_unknown_machine());
+
+        let new_id = new.activation_id.unwrap();
+        let forced = client
+            .transfer_license(license, app(), new_id, machine("third"), true)
+            .await
+            .unwrap();
+        let cooldown = client
+            .transfer_license(license, app(), forced.activation_id.unwrap(), machine("x"), true)
+            .await
+            .unwrap_err();
+        let TError::TransferCooldown {
+            retry_after: Some(after),
+        } = cooldown
+        else {
+            panic!("expected TransferCooldown, got {:?}", cooldown);
+        };
+        assert!(after > Duration::from_secs(3600));
+        assert_eq!(cooldown.remediation(), Remediation::RetryAfter(after));
+        assert_eq!(cooldown.kind(), "transfer_cooldown");
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_activation_lifecycle() {
+        let server = MockServer::start(MockConfig {
+            seats: Some((0, 1)),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let client = TClient::new(server.url());
+        let license = Uuid::new_v4();
+        let app = || "my-app".to_string();
+
+        let seat = client
+            .activate_license(license, app(), "laptop".to_string())
+            .await
+            .unwrap();
+        assert_eq!(seat.heartbeat_interval, Some(Duration::from_secs(300)));
+        client
+            .activate_license(license, app(), "laptop".to_string())
+            .await
+            .unwrap();
+        let full = client
+            .activate_license(license, app(), "desktop".to_string())
+            .await
+            .unwrap_err();
+        assert!(full.is_seat_limit_reached());
+        assert_eq!(full.status(), Some(StatusCode::CONFLICT));
+        let TError::NoSeatsAvailable { seats, .. } = &full else {
+            panic!("expected NoSeatsAvailable, got {:?}", full);
+        };
+        assert_eq!(seats.len(), 1);
+        assert_eq!(Some(seats[0].id), seat.activation_id);
+
+        let refreshed = client.heartbeat(license, app(), seat.token).await.unwrap();
+        client
+            .deactivate_license(license, app(), "laptop".to_string())
+            .await
+            .unwrap();
+        let freed = client
+            .heartbeat(license, app(), refreshed.token)
+            .await
+            .unwrap_err();
+        assert!(freed.is_unknown_machine());
+        assert!(!freed.is_license_not_found());
+        let again = client
+            .deactivate_license(license, app(), "laptop".to_string())
+            .await
+            .unwrap_err();
+        assert!(again.is_unknown_machine());
+        client
+            .activate_license(license, app(), "desktop".to_string())
+            .await
+            .unwrap();
+
+        let expired = client
+            .activate_license(Scenario::Expired.license(), app(), "laptop".to_string())
+            .await
+            .unwrap_err();
+        assert!(expired.is_expired());
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_validate_license_raw() {
+        let server = server(Scenario::Valid).await;
+        let client = TClient::new(server.url());
+
+        let valid = client
+            .validate_license_raw(Uuid::new_v4(), "my-app".to_string())
+            .await
+            .unwrap();
+        assert_eq!(valid.status, StatusCode::OK);
+        assert_eq!(valid.body["token"], "mock-token");
+        assert_eq!(valid.body["experimental"]["seats"], 5);
+        let debug = format!("{:?}", valid);
+        assert!(!debug.contains("mock-token"), "{}", debug);
+        assert!(debug.contains("seats"), "{}", debug);
+
+        let expired = client
+            .validate_license_raw(Scenario::Expired.license(), "my-app".to_string())
+            .await
+            .unwrap();
+        assert_eq!(expired.status, StatusCode::GONE);
+        assert_eq!(expired.body["error"], "License has expired");
+
+        let empty = client
+            .validate_license_raw(Scenario::Empty.license(), "my-app".to_string())
+            .await
+            .unwrap();
+        assert_eq!(empty.body, Value::Null);
+
+        let proxied = client
+            .validate_license_raw(Scenario::BadGateway.license(), "my-app".to_string())
+            .await
+            .unwrap();
+        assert_eq!(proxied.status, StatusCode::BAD_GATEWAY);
+        assert_eq!(
+            proxied.body,
+            Value::String("<html><body><h1>502 Bad Gateway</h1></body></html>".to_string())
+        );
+        let malformed = client
+            .validate_license_raw(Scenario::Malformed.license(), "my-app".to_string())
+            .await;
+        assert!(matches!(malformed, Err(TError::Parsing(_))), "{:?}", malformed);
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_validate_license_with_context() {
+        let server = server(Scenario::Valid).await;
+        let client = TClient::new(server.url()).set_default_context(serde_json::json!({
+            "tenant": "acme",
+            "environment": "staging",
+        }));
+        let token = client
+            .validate_license_with_context(
+                Uuid::new_v4(),
+                "my-app".to_string(),
+                serde_json::json!({ "environment": "prod", "locale": "es-ES", "note": "añadido ✓ 日本" }),
+            )
+            .await
+            .unwrap();
+        assert_eq!(token, "mock-token");
+        assert_eq!(
+            server.received_contexts(),
+            vec![serde_json::json!({
+                "tenant": "acme",
+                "environment": "prod",
+                "locale": "es-ES",
+                "note": "añadido ✓ 日本",
+            })]
+        );
+
+        let too_large = client
+            .validate_license_with_context(
+                Uuid::new_v4(),
+                "my-app".to_string(),
+                serde_json::json!({ "blob": "x".repeat(MAX_CONTEXT_SIZE) }),
+            )
+            .await;
+        assert!(matches!(too_large, Err(TError::ContextTooLarge { .. })));
+        assert_eq!(server.request_count(), 1);
+        server.stop().await;
+    }
+
+    #[test]
+    fn test_merge_context() {
+        use serde_json::json;
+        assert_eq!(merge_context(&Value::Null, json!({ "a": 1 })), json!({ "a": 1 }));
+        assert_eq!(merge_context(&json!({ "a": 1 }), Value::Null), json!({ "a": 1 }));
+        assert_eq!(
+            merge_context(&json!({ "a": 1, "b": 2 }), json!({ "b": 3 })),
+            json!({ "a": 1, "b": 3 })
+        );
+    }
+
+    fn live_pools(base_url: &str) -> usize {
+        pools()
+            .get(&(base_url.to_string(), None))
+            .map_or(0, |pool| pool.strong_count())
+    }
+
+    #[tokio::test]
+    async fn test_clients_share_pools() {
+        let server = server(Scenario::Valid).await;
+        let url = server.url();
+        let first = TClient::new(url.clone());
+        let mut second = TClient::new(url.clone());
+        assert!(Arc::ptr_eq(
+            first.inner.as_ref().unwrap(),
+            second.inner.as_ref().unwrap()
+        ));
+        assert_eq!(live_pools(&url), 2);
+
+        second.close();
+        assert_eq!(live_pools(&url), 1);
+        let token = second
+            .validate_license(Uuid::new_v4(), "my-app".to_string())
+            .await
+            .unwrap();
+        assert_eq!(token, "mock-token");
+        drop(first);
+        assert_eq!(live_pools(&url), 0);
+        server.stop().await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_max_concurrency() {
+        let server = MockServer::start(MockConfig {
+            latency: Duration::from_millis(20),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let client = TClient::new(server.url()).set_max_concurrency(NonZeroUsize::new(10));
+        assert_eq!(client.max_concurrency(), NonZeroUsize::new(10));
+
+        let handles: Vec<_> = (0..200)
+            .map(|_| {
+                let client = client.clone();
+                tokio::spawn(async move {
+                    let token = client
+                        .validate_license(Uuid::new_v4(), "my-app".to_string())
+                        .await;
+                    (token, client.in_flight())
+                })
+            })
+            .collect();
+        for handle in handles {
+            let (token, in_flight) = handle.await.unwrap();
+            assert_eq!(token.unwrap(), "mock-token");
+            assert!(in_flight <= 10, "{} requests in flight", in_flight);
+        }
+        assert_eq!(server.request_count(), 200);
+        assert!(server.peak_concurrency() <= 10, "server saw {}", server.peak_concurrency());
+        assert!(server.peak_concurrency() > 1);
+        assert_eq!(client.in_flight(), 0);
+        server.stop().await;
+    }
+
+    async fn flaky_server(fail_first: usize) -> MockServer {
+        MockServer::start(MockConfig {
+            fail_first,
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_retry_then_succeed() {
+        let server = flaky_server(2).await;
+        let client = TClient::new(server.url())
+            .set_retries(3)
+            .set_retry_backoff(Duration::from_millis(10));
+        let token = client
+            .validate_license(Uuid::new_v4(), "my-app".to_string())
+            .await
+            .unwrap();
+        assert_eq!(token, "mock-token");
+        assert_eq!(server.request_count(), 3);
+        assert_eq!(client.in_flight(), 0);
+        server.stop().await;
+    }
+
+    #[cfg(feature = "fs")]
+    #[tokio::test]
+    async fn test_open_sealed_after_token_rotation() {
+        let license = Uuid::new_v4();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("payload.chipa").to_string_lossy().into_owned();
+        let issuing = |token: &str| {
+            MockServer::start(MockConfig {
+                token: token.to_string(),
+                ..Default::default()
+            })
+        };
+        ChipaFile::seal_for_license(crate::Version::LATEST, &"payload", license, "token-v1")
+            .unwrap()
+            .save_sealed(&path)
+            .unwrap();
+
+        let server = issuing("token-v1").await.unwrap();
+        let opened: String = TClient::new(server.url())
+            .open_sealed(&path, license, "my-app".to_string())
+            .await
+            .unwrap();
+        assert_eq!(opened, "payload");
+        server.stop().await;
+
+        // The server now issues another token for the same license.
+        let server = issuing("token-v2").await.unwrap();
+        let rotated = TClient::new(server.url())
+            .open_sealed::<String>(&path, license, "my-app".to_string())
+            .await;
+        assert!(
+            matches!(rotated, Err(TError::ChipaFile(ChipaError::Tampered(_)))),
+            "{:?}",
+            rotated
+        );
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_builder() {
+        let server = flaky_server(2).await;
+        let client = TClient::builder(server.url())
+            .timeout(Duration::from_secs(5))
+            .connect_timeout(Duration::from_secs(5))
+            .retries(3)
+            .retry_backoff(Duration::from_millis(10))
+            .header(
+                HeaderName::from_static("x-tenant"),
+                HeaderValue::from_static("acme"),
+            )
+            .build();
+        let token = client
+            .validate_license(Uuid::new_v4(), "my-app".to_string())
+            .await
+            .unwrap();
+        assert_eq!(token, "mock-token");
+        assert_eq!(server.request_count(), 3);
+        assert_eq!(server.last_headers().unwrap()["x-tenant"], "acme");
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_retries_exhausted() {
+        let server = flaky_server(10).await;
+        let started = std::time::Instant::now();
+        let result = TClient::new(server.url())
+            .set_retries(2)
+            .set_retry_backoff(Duration::from_millis(20))
+            .validate_license(Uuid::new_v4(), "my-app".to_string())
+            .await;
+        assert!(matches!(
+            result,
+            Err(TError::Response(e)) if e.status == Some(StatusCode::SERVICE_UNAVAILABLE)
+        ));
+        assert_eq!(server.request_count(), 3);
+        assert!(started.elapsed() >= Duration::from_millis(60), "backoff 20ms then 40ms");
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_no_retry_on_client_error_or_body() {
+        let server = flaky_server(0).await;
+        let client = TClient::new(server.url())
+            .set_retries(3)
+            .set_retry_backoff(Duration::from_millis(10));
+        let expired = client
+            .validate_license(Scenario::Expired.license(), "my-app".to_string())
+            .await;
+        assert!(matches!(expired, Err(TError::Response(_))));
+        assert_eq!(server.request_count(), 1);
+        server.stop().await;
+
+        let server = flaky_server(1).await;
+        let client = TClient::new(server.url()).set_retries(3);
+        let with_context = client
+            .validate_license_with_context(Uuid::new_v4(), "my-app".to_string(), serde_json::json!({}))
+            .await;
+        assert!(matches!(
+            with_context,
+            Err(TError::Response(e)) if e.status == Some(StatusCode::SERVICE_UNAVAILABLE)
+        ));
+        assert_eq!(server.request_count(), 1);
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_timeout_then_error() {
+        let server = MockServer::start(MockConfig {
+            latency: Duration::from_millis(500),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let started = std::time::Instant::now();
+        let error = TClient::new(server.url())
+            .set_timeout(Some(Duration::from_millis(50)))
+            .set_retries(1)
+            .set_retry_backoff(Duration::from_millis(10))
+            .validate_license(Uuid::new_v4(), "my-app".to_string())
+            .await
+            .unwrap_err();
+        assert!(matches!(&error, TError::Request(e) if e.is_timeout()), "{}", error);
+        assert_eq!(error.remediation(), Remediation::CheckInternet);
+        assert!(started.elapsed() < Duration::from_millis(500));
+        assert_eq!(server.request_count(), 2);
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_retry_policy() {
+        let server = server(Scenario::Valid).await;
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let seen = events.clone();
+        let client = TClient::new(server.url())
+            .set_retries(2)
+            .set_retry_backoff(Duration::from_millis(10))
+            .set_max_retry_backoff(Some(Duration::from_millis(15)))
+            .set_on_retry(Some(Arc::new(move |event: &RetryEvent| {
+                seen.lock().unwrap().push(*event)
+            })));
+
+        // 429 is not retried unless asked for.
+        let limited = client
+            .validate_license(Scenario::RateLimited.license(), "my-app".to_string())
+            .await;
+        assert!(matches!(limited, Err(TError::Response(_))));
+        assert_eq!(server.request_count(), 1);
+        assert!(events.lock().unwrap().is_empty());
+
+        // Its 30s `Retry-After` outlasts the cap, so the cap wins.
+        let limited = client
+            .clone()
+            .set_retry_on([RetryOn::RateLimited])
+            .validate_license(Scenario::RateLimited.license(), "my-app".to_string())
+            .await;
+        assert!(matches!(limited, Err(TError::Response(_))));
+        assert_eq!(server.request_count(), 4);
+        let event = |attempt| RetryEvent {
+            attempt,
+            delay: Duration::from_millis(15),
+            reason: RetryOn::RateLimited,
+        };
+        assert_eq!(*events.lock().unwrap(), vec![event(1), event(2)]);
+        server.stop().await;
+
+        assert_eq!(RetryOn::from_name("5xx"), Some(RetryOn::ServerError));
+        assert_eq!(RetryOn::from_name("4xx"), None);
+    }
+
+    #[tokio::test]
+    async fn test_operation_timeout() {
+        let server = MockServer::start(MockConfig {
+            latency: Duration::from_millis(200),
+            fail_first: usize::MAX,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let started = std::time::Instant::now();
+        let error = TClient::new(server.url())
+            .set_retries(10)
+            .set_retry_backoff(Duration::from_millis(10))
+            .set_operation_timeout(Some(Duration::from_millis(500)))
+            .validate_license(Uuid::new_v4(), "my-app".to_string())
+            .await
+            .unwrap_err();
+        let elapsed = started.elapsed();
+        assert!(
+            matches!(error, TError::DeadlineExceeded { attempts: 3, endpoints_tried: 1 }),
+            "{}",
+            error
+        );
+        assert!(error.is_network());
+        assert!(elapsed >= Duration::from_millis(500), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_millis(650), "{:?}", elapsed);
+
+        // A backoff that would end past the deadline is not waited out.
+        let started = std::time::Instant::now();
+        let error = TClient::new(server.url())
+            .set_retries(10)
+            .set_retry_backoff(Duration::from_secs(5))
+            .set_operation_timeout(Some(Duration::from_secs(1)))
+            .validate_license(Uuid::new_v4(), "my-app".to_string())
+            .await
+            .unwrap_err();
+        assert!(matches!(error, TError::DeadlineExceeded { attempts: 1, .. }), "{}", error);
+        assert!(started.elapsed() < Duration::from_millis(500));
+        server.stop().await;
+    }
+
+    // The observer runs on the thread that timed the operation, so the whole test stays
+    // on a current-thread runtime of its own.
+    #[test]
+    fn test_failed_validations_are_timed() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let ops = crate::perf::observe_slow_ops(Duration::ZERO, || {
+            runtime.block_on(async {
+                let server = MockServer::start(MockConfig {
+                    latency: Duration::from_millis(200),
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+                let client = TClient::new(server.url())
+                    .set_timeout(Some(Duration::from_millis(20)))
+                    .set_retries(1)
+                    .set_retry_backoff(Duration::from_millis(10));
+                let license = Uuid::new_v4();
+                let validate = client.validate_license(license, "my-app".to_string()).await;
+                assert!(matches!(validate, Err(TError::Request(_))));
+                let raw = client.validate_license_raw(license, "my-app".to_string()).await;
+                assert!(matches!(raw, Err(TError::Request(_))));
+                let context = serde_json::json!({ "hostname": "build-01" });
+                let with_context = client
+                    .validate_license_with_context(license, "my-app".to_string(), context)
+                    .await;
+                assert!(matches!(with_context, Err(TError::Request(_))));
+                server.stop().await;
+            })
+        });
+        let timed: Vec<_> = ops.iter().map(|op| (op.operation, op.payload_bytes)).collect();
+        assert_eq!(timed, vec![(Operation::Validate, None); 3]);
+        assert!(ops[0].duration >= Duration::from_millis(50));
+    }
+
+    #[tokio::test]
+    async fn test_custom_headers() {
+        let server = server(Scenario::Valid).await;
+        let client = TClient::new(server.url())
+            .set_connect_timeout(Some(Duration::from_secs(5)))
+            .set_header(
+                HeaderName::from_static("x-tenant"),
+                HeaderValue::from_static("acme"),
+            )
+            .set_header(AUTHORIZATION, HeaderValue::from_static("overridden"));
+        let check = |headers: HeaderMap| {
+            assert_eq!(headers.get("x-tenant").unwrap(), "acme");
+            assert_eq!(headers.get_all(AUTHORIZATION).iter().count(), 1);
+            assert_ne!(headers.get(AUTHORIZATION).unwrap(), "overridden");
+        };
+        client
+            .validate_license(Uuid::new_v4(), "my-app".to_string())
+            .await
+            .unwrap();
+        check(server.last_headers().unwrap());
+        client.seat_usage(Uuid::new_v4()).await.unwrap();
+        check(server.last_headers().unwrap());
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_validate_license_unreachable() {
+        let server = server(Scenario::Valid).await;
+        let url = server.url();
+        server.stop().await;
+        let result = TClient::new(url)
+            .validate_license(Uuid::new_v4(), "my-app".to_string())
+            .await;
+        let error = result.unwrap_err();
+        assert!(matches!(error, TError::Request(_)));
+        assert!(error.is_network());
+        assert!(!error.is_expired());
+        assert_eq!(error.status(), None);
+        assert_eq!(error.remediation(), Remediation::CheckInternet);
+        assert_eq!(error.address_family(), Some("ipv4"));
+    }
+
+    #[cfg(feature = "fs")]
+    async fn unreachable_url() -> String {
+        let server = server(Scenario::Valid).await;
+        let url = server.url();
+        server.stop().await;
+        url
+    }
+
+    #[cfg(feature = "fs")]
+    fn write_cache(path: &str, license: Uuid, age: Duration) {
+        let validated_at = SystemTime::now() - age;
+        CachedValidation {
+            license,
+            application: "my-app".to_string(),
+            token: "cached-token".to_string(),
+            validated_at: validated_at.duration_since(UNIX_EPOCH).unwrap().as_secs(),
+        }
+        .save(path, &RealFs)
+        .unwrap();
+    }
+
+    #[cfg(feature = "fs")]
+    #[tokio::test]
+    async fn test_validate_license_cached_hit() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = dir.path().join("validation.chipa");
+        let cache = cache.to_str().unwrap();
+        let license = Uuid::new_v4();
+        let grace = Duration::from_secs(3600);
+
+        let server = server(Scenario::Valid).await;
+        let token = TClient::new(server.url())
+            .validate_license_cached(license, "my-app".to_string(), grace, cache)
+            .await
+            .unwrap();
+        assert_eq!(token, "mock-token");
+        server.stop().await;
+
+        let offline = TClient::new(unreachable_url().await);
+        let token = offline
+            .validate_license_cached(license, "my-app".to_string(), grace, cache)
+            .await
+            .unwrap();
+        assert_eq!(token, "mock-token");
+
+        let other_app = offline
+            .validate_license_cached(license, "other-app".to_string(), grace, cache)
+            .await;
+        assert!(matches!(other_app, Err(TError::Request(_))));
+        let other_license = offline
+            .validate_license_cached(Uuid::new_v4(), "my-app".to_string(), grace, cache)
+            .await;
+        assert!(matches!(other_license, Err(TError::Request(_))));
+    }
+
+    #[cfg(feature = "fs")]
+    #[tokio::test]
+    async fn test_validate_license_cached_expired() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = dir.path().join("validation.chipa");
+        let cache = cache.to_str().unwrap();
+        let license = Uuid::new_v4();
+        write_cache(cache, license, Duration::from_secs(2 * 3600));
+
+        let offline = TClient::new(unreachable_url().await);
+        let stale = offline
+            .validate_license_cached(license, "my-app".to_string(), Duration::from_secs(3600), cache)
+            .await
+            .unwrap_err();
+        assert!(matches!(stale, TError::Request(_)));
+        assert_eq!(stale.remediation(), Remediation::CheckInternet);
+
+        let token = offline
+            .validate_license_cached(license, "my-app".to_string(), Duration::from_secs(3 * 3600), cache)
+            .await
+            .unwrap();
+        assert_eq!(token, "cached-token");
+
+        write_cache(cache, license, Duration::ZERO);
+        let future = SystemTime::now() + Duration::from_secs(3600);
+        let mut cached = CachedValidation::load(cache, license, "my-app", &RealFs)
+            .unwrap()
+            .unwrap();
+        cached.validated_at = future.duration_since(UNIX_EPOCH).unwrap().as_secs();
+        cached.save(cache, &RealFs).unwrap();
+        let from_the_future = offline
+            .validate_license_cached(license, "my-app".to_string(), Duration::from_secs(3600), cache)
+            .await;
+        assert!(matches!(from_the_future, Err(TError::Request(_))));
+    }
+
+    #[cfg(feature = "fs")]
+    #[tokio::test]
+    async fn test_validate_license_cached_rejection_overrides_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = dir.path().join("validation.chipa");
+        let cache = cache.to_str().unwrap();
+        let license = Scenario::Expired.license();
+        write_cache(cache, license, Duration::ZERO);
+
+        let server = server(Scenario::Valid).await;
+        let rejected = TClient::new(server.url())
+            .validate_license_cached(license, "my-app".to_string(), Duration::from_secs(3600), cache)
+            .await;
+        assert!(matches!(&rejected, Err(TError::Response(e)) if e.error == "License has expired"));
+        assert!(!RealFs.exists(cache.as_ref()));
+        server.stop().await;
+
+        let offline = TClient::new(unreachable_url().await)
+            .validate_license_cached(license, "my-app".to_string(), Duration::from_secs(3600), cache)
+            .await;
+        assert!(matches!(offline, Err(TError::Request(_))));
+    }
+
+    #[cfg(feature = "fs")]
+    #[tokio::test]
+    async fn test_validate_license_cached_corrupted() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = dir.path().join("validation.chipa");
+        let license = Uuid::new_v4();
+        std::fs::write(&cache, b"not a chipa file").unwrap();
+        let cache = cache.to_str().unwrap();
+
+        let offline = TClient::new(unreachable_url().await);
+        let corrupted = offline
+            .validate_license_cached(license, "my-app".to_string(), Duration::from_secs(3600), cache)
+            .await;
+        assert!(matches!(corrupted, Err(TError::Request(_))));
+        let quarantined = offline.quarantined_files();
+        assert_eq!(quarantined.len(), 1);
+        assert_eq!(quarantined[0].path, Path::new(cache));
+        assert!(!quarantined[0].reason.is_empty());
+        assert_eq!(std::fs::read(&quarantined[0].moved_to).unwrap(), b"not a chipa file");
+
+        let server = server(Scenario::Valid).await;
+        let token = TClient::new(server.url())
+            .validate_license_cached(license, "my-app".to_string(), Duration::from_secs(3600), cache)
+            .await
+            .unwrap();
+        assert_eq!(token, "mock-token");
+        let cached = CachedValidation::load(cache, license, "my-app", &RealFs).unwrap();
+        assert_eq!(cached.map(|c| c.token), Some("mock-token".to_string()));
+        server.stop().await;
+    }
+
+    #[cfg(feature = "fs")]
+    #[tokio::test]
+    async fn test_validate_license_cached_quarantines_before_overwriting() {
+        let fs = crate::fs::MemoryFs::new();
+        fs.write_atomic("validation.chipa".as_ref(), b"not a chipa file").unwrap();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let observed = seen.clone();
+        let server = server(Scenario::Valid).await;
+        let client = TClient::new(server.url()).set_quarantine_observer(Some(Arc::new(
+            move |quarantined: &Quarantined| observed.lock().unwrap().push(quarantined.clone()),
+        )));
+
+        let token = client
+            .validate_license_cached_with(
+                Uuid::new_v4(),
+                "my-app".to_string(),
+                Duration::from_secs(3600),
+                "validation.chipa",
+                &fs,
+            )
+            .await
+            .unwrap();
+        assert_eq!(token, "mock-token");
+        let quarantined = client.quarantined_files();
+        assert_eq!(*seen.lock().unwrap(), quarantined);
+        assert_eq!(quarantined.len(), 1);
+        let moved_to = quarantined[0].moved_to.to_str().unwrap();
+        assert!(moved_to.starts_with("validation.chipa.corrupt-"), "{}", moved_to);
+        assert_eq!(fs.read(&quarantined[0].moved_to).unwrap(), b"not a chipa file");
+        assert!(fs.exists("validation.chipa".as_ref()));
+        server.stop().await;
+    }
+
+    #[cfg(feature = "fs")]
+    #[tokio::test]
+    async fn test_validate_license_cached_without_extension() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = dir.path().join("validation");
+        let cache = cache.to_str().unwrap();
+        let stored = dir.path().join("validation.chipa");
+        let license = Uuid::new_v4();
+        let grace = Duration::from_secs(3600);
+
+        let online = server(Scenario::Valid).await;
+        TClient::new(online.url())
+            .validate_license_cached(license, "my-app".to_string(), grace, cache)
+            .await
+            .unwrap();
+        assert!(stored.exists());
+        online.stop().await;
+        let token = TClient::new(unreachable_url().await)
+            .validate_license_cached(license, "my-app".to_string(), grace, cache)
+            .await
+            .unwrap();
+        assert_eq!(token, "mock-token");
+
+        let revoked = Scenario::Expired.license();
+        write_cache(cache, revoked, Duration::ZERO);
+        let server = server(Scenario::Valid).await;
+        let rejected = TClient::new(server.url())
+            .validate_license_cached(revoked, "my-app".to_string(), grace, cache)
+            .await;
+        assert!(rejected.unwrap_err().is_expired());
+        assert!(!stored.exists());
+        server.stop().await;
+    }
+
+    #[cfg(all(unix, feature = "fs"))]
+    #[tokio::test]
+    async fn test_validate_license_cached_unwritable() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let read_only = dir.path().join("read-only");
+        std::fs::create_dir(&read_only).unwrap();
+        std::fs::set_permissions(&read_only, std::fs::Permissions::from_mode(0o555)).unwrap();
+        // Root ignores permissions, a path below a regular file fails for everyone.
+        let not_a_dir = dir.path().join("file");
+        std::fs::write(&not_a_dir, b"").unwrap();
+
+        let server = server(Scenario::Valid).await;
+        let client = TClient::new(server.url());
+        for parent in [&read_only, &not_a_dir] {
+            let cache = parent.join("validation.chipa");
+            let token = client
+                .validate_license_cached(
+                    Uuid::new_v4(),
+                    "my-app".to_string(),
+                    Duration::from_secs(3600),
+                    cache.to_str().unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(token, "mock-token");
+        }
+        std::fs::set_permissions(&read_only, std::fs::Permissions::from_mode(0o755)).unwrap();
+        server.stop().await;
+    }
+
+    #[cfg(feature = "fs")]
+    #[tokio::test]
+    async fn test_validate_license_cached_with_fs() {
+        let fs = crate::fs::MemoryFs::new();
+        let license = Uuid::new_v4();
+        let grace = Duration::from_secs(3600);
+
+        let server = server(Scenario::Valid).await;
+        TClient::new(server.url())
+            .validate_license_cached_with(license, "my-app".to_string(), grace, "cache", &fs)
+            .await
+            .unwrap();
+        assert!(fs.exists("cache.chipa".as_ref()));
+        server.stop().await;
+
+        let token = TClient::new(unreachable_url().await)
+            .validate_license_cached_with(license, "my-app".to_string(), grace, "cache", &fs)
+            .await
+            .unwrap();
+        assert_eq!(token, "mock-token");
+    }
+
+    #[test]
+    fn test_endpoint_urls() {
+        let seats = |base: &str| {
+            TClient::new(base.to_string())
+                .endpoint(&["subscriptions", "seats", "my app"])
+                .map(|url| url.to_string())
+        };
+        assert_eq!(
+            seats("https://[2001:db8::1]:8443").unwrap(),
+            "https://[2001:db8::1]:8443/subscriptions/seats/my%20app"
+        );
+        assert_eq!(
+            seats("http://192.0.2.10:8080/").unwrap(),
+            "http://192.0.2.10:8080/subscriptions/seats/my%20app"
+        );
+        assert_eq!(
+            seats("https://license.example.com/api/").unwrap(),
+            "https://license.example.com/api/subscriptions/seats/my%20app"
+        );
+        for invalid in ["license.example.com", "https://[2001:db8::1", "mailto:x@example.com"] {
+            assert!(
+                matches!(seats(invalid), Err(TError::InvalidUrl(_))),
+                "{} should be rejected",
+                invalid
+            );
+        }
+    }
+
+    // CI runs it on a runner known to have `::1`.
+    #[tokio::test]
+    #[ignore = "needs an IPv6 loopback, run with --ignored"]
+    async fn test_validate_license_ipv6() {
+        let server = MockServer::start(MockConfig {
+            host: "::1".parse().unwrap(),
+            ..Default::default()
+        })
+        .await
+        .expect("IPv6 loopback");
+        let url = server.url();
+        assert!(url.starts_with("http://[::1]:"));
+        for base in [url.clone(), format!("{}/", url)] {
+            let client = TClient::new(base);
+            let token = client
+                .validate_license(Uuid::new_v4(), "my app".to_string())
+                .await
+                .unwrap();
+            assert_eq!(token, "mock-token");
+            assert!(client.seat_usage(Uuid::new_v4()).await.unwrap().is_known());
+        }
+        server.stop().await;
+
+        let error = TClient::new(url)
+            .validate_license(Uuid::new_v4(), "my-app".to_string())
+            .await
+            .unwrap_err();
+        assert_eq!(error.address_family(), Some("ipv6"));
+    }
+
+    fn api_error(status: Option<StatusCode>, body: Value) -> TError {
+        let mut error: ApiError = serde_json::from_value(body).unwrap();
+        error.status = status;
+        error.retry_after = Some(Duration::from_secs(5));
+        TError::Response(error)
+    }
+
+    #[test]
+    fn test_remediation_mapping() {
+        use serde_json::json;
+        let error = json!({ "error": "nope" });
+        let renew = Remediation::Renew { portal_url: None };
+        let cases = vec![
+            (TError::from(anyhow::anyhow!("boom")), Remediation::ContactSupport),
+            (
+                TError::from(serde_json::from_str::<Value>("{").unwrap_err()),
+                Remediation::ContactSupport,
+            ),
+            (
+                TError::from(Uuid::parse_str("not-a-uuid").unwrap_err()),
+                Remediation::ContactSupport,
+            ),
+            (
+                TError::InvalidLicense("garbage".to_string()),
+                Remediation::ContactSupport,
+            ),
+            (
+                TError::from(crate::encryption::ChipaError::Tampered("x".to_string())),
+                Remediation::ContactSupport,
+            ),
+            (
+                TError::EmptyResponse {
+                    endpoint: "/subscriptions/validateapp".to_string(),
+                    status: StatusCode::OK,
+                },
+                Remediation::ContactSupport,
+            ),
+            (
+                TError::ContextTooLarge { size: 2, max: 1 },
+                Remediation::ContactSupport,
+            ),
+            (
+                TError::ClientTooOld {
+                    server_min_version: 2,
+                    client_version: 1,
+                    download_url: None,
+                },
+                Remediation::UpdateApp,
+            ),
+            (
+                TError::InvalidUrl("nope".to_string()),
+                Remediation::ContactSupport,
+            ),
+            (
+                api_error(Some(StatusCode::TOO_MANY_REQUESTS), error.clone()),
+                Remediation::RetryAfter(Duration::from_secs(5)),
+            ),
+            (api_error(Some(StatusCode::GONE), error.clone()), renew.clone()),
+            (
+                api_error(Some(StatusCode::PAYMENT_REQUIRED), error.clone()),
+                renew.clone(),
+            ),
+            (
+                api_error(Some(StatusCode::UPGRADE_REQUIRED), error.clone()),
+                Remediation::UpdateApp,
+            ),
+            (
+                api_error(Some(StatusCode::BAD_GATEWAY), error.clone()),
+                Remediation::RetryLater,
+            ),
+            (
+                api_error(Some(StatusCode::FORBIDDEN), error.clone()),
+                Remediation::ContactSupport,
+            ),
+            (api_error(None, error.clone()), Remediation::ContactSupport),
+            (
+                api_error(
+                    Some(StatusCode::FORBIDDEN),
+                    json!({ "error": "nope", "code": "license_expired" }),
+                ),
+                renew.clone(),
+            ),
+            (
+                api_error(
+                    Some(StatusCode::GONE),
+                    json!({ "error": "nope", "code": "application_unauthorized" }),
+                ),
+                Remediation::ContactSupport,
+            ),
+            (
+                TError::NotValidated(Box::new(api_error(Some(StatusCode::GONE), error.clone()))),
+                renew,
+            ),
+            (
+                api_error(
+                    Some(StatusCode::FORBIDDEN),
+                    json!({ "error": "nope", "remediation": { "action": "update_app" } }),
+                ),
+                Remediation::UpdateApp,
+            ),
+            (
+                api_error(
+                    Some(StatusCode::GONE),
+                    json!({ "error": "nope", "remediation": { "action": "retry_after", "seconds": 60 } }),
+                ),
+                Remediation::RetryAfter(Duration::from_secs(60)),
+            ),
+        ];
+        for (error, expected) in cases {
+            assert_eq!(error.remediation(), expected, "{}", error);
+        }
+    }
 }
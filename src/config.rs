@@ -0,0 +1,97 @@
+include!(concat!(env!("OUT_DIR"), "/obfuscation_key.rs"));
+
+pub const fn obfuscate<const N: usize>(input: &[u8]) -> [u8; N] {
+    let mut output = [0u8; N];
+    let mut i = 0;
+    while i < N {
+        output[i] = input[i] ^ OBFUSCATION_KEY[i % OBFUSCATION_KEY.len()];
+        i += 1;
+    }
+    output
+}
+
+pub fn deobfuscate(input: &[u8]) -> String {
+    let bytes = input
+        .iter()
+        .enumerate()
+        .map(|(i, b)| b ^ OBFUSCATION_KEY[i % OBFUSCATION_KEY.len()])
+        .collect();
+    String::from_utf8(bytes).expect("Obfuscated config value is not valid UTF-8")
+}
+
+#[macro_export]
+macro_rules! chipa_config {
+    (@env [$($vis:tt)*]) => {
+        $crate::chipa_config!(
+            @items $($vis)*,
+            env!(
+                "CHIPA_DEFAULT_URL",
+                "CHIPA_DEFAULT_URL must be set at compile time to the license server URL, e.g. CHIPA_DEFAULT_URL=https://license.example.com"
+            ),
+            env!(
+                "CHIPA_APPLICATION",
+                "CHIPA_APPLICATION must be set at compile time to the application id, e.g. CHIPA_APPLICATION=my-app"
+            )
+        );
+    };
+    (@items $vis:vis, $url:expr, $application:expr) => {
+        $vis const DEFAULT_APPLICATION: &str = $application;
+
+        $vis fn default_url() -> String {
+            const URL: &str = $url;
+            const OBFUSCATED: [u8; URL.len()] = $crate::__private::obfuscate(URL.as_bytes());
+            $crate::__private::deobfuscate(&OBFUSCATED)
+        }
+
+        $crate::__chipa_default_client!($vis);
+    };
+    () => {
+        $crate::chipa_config!(@env []);
+    };
+    ($vis:vis) => {
+        $crate::chipa_config!(@env [$vis]);
+    };
+}
+
+#[cfg(feature = "client")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __chipa_default_client {
+    ($vis:vis) => {
+        $vis fn default_client() -> $crate::LicenseClient {
+            $crate::LicenseClient::new(default_url())
+        }
+    };
+}
+
+#[cfg(not(feature = "client"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __chipa_default_client {
+    ($vis:vis) => {};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod generated {
+        crate::chipa_config!(@items pub, "https://license.example.com", "my-app");
+    }
+
+    #[test]
+    fn test_obfuscation_roundtrip() {
+        const URL: &str = "https://license.example.com";
+        const OBFUSCATED: [u8; URL.len()] = obfuscate(URL.as_bytes());
+        assert_ne!(&OBFUSCATED[..], URL.as_bytes());
+        assert_eq!(deobfuscate(&OBFUSCATED), URL);
+    }
+
+    #[test]
+    fn test_generated_items() {
+        assert_eq!(generated::default_url(), "https://license.example.com");
+        assert_eq!(generated::DEFAULT_APPLICATION, "my-app");
+        #[cfg(feature = "client")]
+        let _client = generated::default_client().set_url("https://staging.example.com".to_string());
+    }
+}
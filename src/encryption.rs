@@ -1,13 +1,78 @@
-use std::{fs::OpenOptions, io::Write, path::PathBuf};
+use std::path::{Path, PathBuf};
 
 use bytes::Bytes;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
-use tenacity_utils::security::{middleware::traits::VersionTrait, TenacityMiddleware, Version};
-
-#[derive(Serialize, Deserialize, Debug)]
+use tenacity_utils::security::{
+    middleware::traits::VersionTrait, TenacityMiddleware, Version as UpstreamVersion,
+};
+use uuid::Uuid;
+
+#[cfg(feature = "fs")]
+use crate::fs::RealFs;
+use crate::{
+    fs::ChipaFs,
+    legacy::{self, LegacyPolicy},
+    limits::{self, ValueLimits},
+    perf::{Operation, Timer},
+    stream::STREAM_FLAG,
+    version::Version,
+};
+
+// The outer layer of a sealed file. It is hard-coded and ships in every binary, so it
+// adds no secrecy; only the inner `sealing_key` layer keeps the payload private.
+#[cfg(feature = "fs")]
+const SEALED_KEY: &str = "chipa-sealed-envelope";
+
+/// A serialized value encrypted with a key, stored as a `.chipa` file or kept as bytes.
+///
+/// ```
+/// # #[cfg(all(feature = "test-util", feature = "fs"))]
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use chipa_license_validator::{test_util::TempDir, ChipaError, ChipaFile, Version};
+///
+/// let dir = TempDir::new()?;
+/// let path = dir.file("settings.chipa");
+/// ChipaFile::new(Version::LATEST, &vec!["feature-a", "feature-b"])?.save(&path, "secret")?;
+///
+/// let features: Vec<String> = ChipaFile::load(&path, "secret")?.read()?;
+/// assert_eq!(features, ["feature-a", "feature-b"]);
+///
+/// match ChipaFile::load(&path, "wrong key") {
+///     Err(ChipaError::Decryption(_)) => {}
+///     other => panic!("expected a decryption error, found {:?}", other),
+/// }
+/// # Ok(())
+/// # }
+/// # #[cfg(not(all(feature = "test-util", feature = "fs")))]
+/// # fn main() {}
+/// ```
+///
+/// Without a filesystem, `to_bytes` and `from_bytes` produce and read the same format:
+///
+/// ```
+/// use chipa_license_validator::{ChipaFile, Version};
+///
+/// let bytes = ChipaFile::new(Version::LATEST, &42u32)?.to_bytes("secret")?;
+/// let value: u32 = ChipaFile::from_bytes(&bytes, "secret")?.read()?;
+/// assert_eq!(value, 42);
+/// assert!(ChipaFile::from_bytes(&bytes, "wrong key").is_err());
+/// # Ok::<(), chipa_license_validator::ChipaError>(())
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ChipaFile {
-    version: Version,
+    version: UpstreamVersion,
     body: Bytes,
+    // Only known from how the file was read, never written out.
+    #[serde(skip)]
+    legacy: bool,
+}
+
+/// How [`ChipaFile::load_with_options`] and [`ChipaFile::from_bytes_with_options`] read
+/// a file. The plain `load` and `from_bytes` use the defaults.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LoadOptions {
+    /// What to do with a file in the pre-streaming layout.
+    pub legacy: LegacyPolicy,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -24,9 +89,56 @@ pub enum ChipaError {
     FileCreation(#[from] std::io::Error),
     #[error("Invalid file format, {0}")]
     InvalidFileFormat(String),
+    #[error("Wrong license, file is sealed for '{found}' but '{expected}' was provided")]
+    WrongLicense { expected: Uuid, found: Uuid },
+    #[error("Tampered file, {0}")]
+    Tampered(String),
+    #[error("No key could open the file, tried {}: [{}]", .0.len(), .0.join(", "))]
+    AllKeysFailed(Vec<String>),
+    #[error("Transaction conflict, '{}' is locked by another transaction", .0.display())]
+    Conflict(PathBuf),
+    #[error("Limit exceeded, the value goes over {limit} = {max}")]
+    LimitExceeded { limit: &'static str, max: usize },
+    #[error(
+        "Legacy file format rejected, {} uses the pre-streaming layout, rewrite it once with \
+         ChipaFile::upgrade_in_place",
+        .0.as_ref().map_or("the file".to_string(), |p| format!("'{}'", p.display()))
+    )]
+    LegacyFormatRejected(Option<PathBuf>),
+}
+
+impl ChipaError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ChipaError::Encode(_) => "encode",
+            ChipaError::Decode(_) => "decode",
+            ChipaError::Encryption(_) => "encryption",
+            ChipaError::Decryption(_) => "decryption",
+            ChipaError::FileCreation(_) => "file_creation",
+            ChipaError::InvalidFileFormat(_) => "invalid_file_format",
+            ChipaError::WrongLicense { .. } => "wrong_license",
+            ChipaError::Tampered(_) => "tampered",
+            ChipaError::AllKeysFailed(_) => "all_keys_failed",
+            ChipaError::Conflict(_) => "conflict",
+            ChipaError::LimitExceeded { .. } => "limit_exceeded",
+            ChipaError::LegacyFormatRejected(_) => "legacy_format_rejected",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct SealedEnvelope {
+    license: Uuid,
+    payload: Bytes,
 }
 
-type ChipaResult<T> = Result<T, ChipaError>;
+pub(crate) type ChipaResult<T> = Result<T, ChipaError>;
+
+// The inner key of a sealed file: only someone holding the token issued for the
+// license can derive it.
+pub(crate) fn sealing_key(license: Uuid, token: &str) -> String {
+    format!("{}:{}", license, token)
+}
 
 impl ChipaFile {
     fn encrypt_body(&self, key: &str) -> ChipaResult<Bytes> {
@@ -47,12 +159,37 @@ impl ChipaFile {
         let body = rmp_serde::to_vec(body)
             .map_err(|e| ChipaError::Encode(e.to_string()))?;
         Ok(Self {
-            version,
+            version: version.upstream(),
             body: Bytes::from(body),
+            legacy: false,
         })
     }
 
+    pub fn version(&self) -> ChipaResult<Version> {
+        Version::from_upstream(self.version)
+            .map_err(|e| ChipaError::InvalidFileFormat(e.to_string()))
+    }
+
+    /// Whether the file was read from the pre-streaming layout. Saving it writes the
+    /// current layout, see `upgrade_in_place`.
+    pub fn is_legacy(&self) -> bool {
+        self.legacy
+    }
+
+    #[cfg(feature = "fs")]
     pub fn save(&self, path: &str, key: &str) -> ChipaResult<()> {
+        self.save_with(path, key, &RealFs)
+    }
+
+    pub fn save_with(&self, path: &str, key: &str, fs: &dyn ChipaFs) -> ChipaResult<()> {
+        let timer = Timer::start();
+        let data = self.to_bytes(key)?;
+        fs.write_atomic(&Self::chipa_path(path), &data)?;
+        timer.finish(Operation::FileSave, Some(data.len()));
+        Ok(())
+    }
+
+    pub(crate) fn chipa_path(path: &str) -> PathBuf {
         let mut path = PathBuf::from(path);
         match path.extension() {
             Some(e) => {
@@ -64,10 +201,25 @@ impl ChipaFile {
                 path.set_extension("chipa");
             }
         }
+        path
+    }
+
+    /// Encrypts the file in the streamed layout, the one `save` writes.
+    pub fn to_bytes(&self, key: &str) -> ChipaResult<Vec<u8>> {
+        let mut buffer = Vec::with_capacity(self.body.len() + 64);
+        Self::encrypt_stream(self.version()?, key, self.body.as_ref(), &mut buffer)?;
+        Ok(buffer)
+    }
+
+    // The pre-streaming layout: the version, then the file with its body encrypted
+    // with `key`, serialized and encrypted again with the version's base key.
+    #[cfg(test)]
+    pub(crate) fn to_legacy_bytes(&self, key: &str) -> ChipaResult<Vec<u8>> {
         let start = u16::from(self.version).to_be_bytes();
         let file = ChipaFile {
             version: self.version,
             body: self.encrypt_body(key)?,
+            legacy: true,
         };
         let data = rmp_serde::encode::to_vec(&file)
             .map_err(|e| ChipaError::Encode(e.to_string()))?;
@@ -75,19 +227,163 @@ impl ChipaFile {
             .version
             .base_encrypt_bytes(&data)
             .map_err(|e| ChipaError::Encryption(anyhow::Error::from(e)))?;
-        let mut file = OpenOptions::new()
-            .create(true)
-            .write(true)
-            .truncate(true)
-            .open(path)?;
-        file.write_all(start.as_slice())?;
-        file.flush()?;
-        file.write_all(data_encrypted.as_ref())?;
-        file.flush()?;
-        Ok(())
+        let mut buffer = Vec::with_capacity(start.len() + data_encrypted.as_ref().len());
+        buffer.extend_from_slice(start.as_slice());
+        buffer.extend_from_slice(data_encrypted.as_ref());
+        Ok(buffer)
     }
 
+    #[cfg(feature = "fs")]
     pub fn load(path: &str, key: &str) -> ChipaResult<Self> {
+        Self::load_with(path, key, &RealFs)
+    }
+
+    pub fn load_with(path: &str, key: &str, fs: &dyn ChipaFs) -> ChipaResult<Self> {
+        Self::load_with_options(path, key, fs, &LoadOptions::default())
+    }
+
+    /// [`ChipaFile::load_with`] with `options` instead of the defaults.
+    ///
+    /// ```
+    /// # #[cfg(all(feature = "test-util", feature = "fs"))]
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use chipa_license_validator::{
+    ///     test_util::TempDir, ChipaFile, LegacyPolicy, LoadOptions, RealFs, Version,
+    /// };
+    ///
+    /// let dir = TempDir::new()?;
+    /// let path = dir.file("settings.chipa");
+    /// ChipaFile::new(Version::LATEST, &"value")?.save(&path, "secret")?;
+    ///
+    /// let options = LoadOptions { legacy: LegacyPolicy::Deny };
+    /// let file = ChipaFile::load_with_options(&path, "secret", &RealFs, &options)?;
+    /// assert!(!file.is_legacy());
+    /// # Ok(())
+    /// # }
+    /// # #[cfg(not(all(feature = "test-util", feature = "fs")))]
+    /// # fn main() {}
+    /// ```
+    pub fn load_with_options(
+        path: &str,
+        key: &str,
+        fs: &dyn ChipaFs,
+        options: &LoadOptions,
+    ) -> ChipaResult<Self> {
+        let timer = Timer::start();
+        let path = Self::existing_chipa_path(path)?;
+        let data = fs.read(&path)?;
+        let chipa_file = Encrypted::decode(&data, Some(&path), options)?.decrypt(key)?;
+        timer.finish(Operation::FileLoad, Some(data.len()));
+        Ok(chipa_file)
+    }
+
+    pub fn from_bytes(data: &[u8], key: &str) -> ChipaResult<Self> {
+        Self::from_bytes_with_options(data, key, &LoadOptions::default())
+    }
+
+    /// [`ChipaFile::from_bytes`] with `options` instead of the defaults.
+    pub fn from_bytes_with_options(
+        data: &[u8],
+        key: &str,
+        options: &LoadOptions,
+    ) -> ChipaResult<Self> {
+        Encrypted::decode(data, None, options)?.decrypt(key)
+    }
+
+    #[cfg(feature = "fs")]
+    /// Rewrites the `.chipa` file at `path` in the current layout if it is in the
+    /// pre-streaming one, keeping its value and key, and returns whether it did. It
+    /// ignores the `LegacyPolicy`, so applications that `Deny` legacy files can still
+    /// migrate them.
+    ///
+    /// ```
+    /// # #[cfg(all(feature = "test-util", feature = "fs"))]
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use chipa_license_validator::{test_util::TempDir, ChipaFile, Version};
+    ///
+    /// let dir = TempDir::new()?;
+    /// let path = dir.file("settings.chipa");
+    /// ChipaFile::new(Version::LATEST, &"value")?.save(&path, "secret")?;
+    ///
+    /// // Already in the current layout.
+    /// assert!(!ChipaFile::upgrade_in_place(&path, "secret")?);
+    /// # Ok(())
+    /// # }
+    /// # #[cfg(not(all(feature = "test-util", feature = "fs")))]
+    /// # fn main() {}
+    /// ```
+    pub fn upgrade_in_place(path: &str, key: &str) -> ChipaResult<bool> {
+        Self::upgrade_in_place_with(path, key, &RealFs)
+    }
+
+    /// [`ChipaFile::upgrade_in_place`] on any [`ChipaFs`].
+    pub fn upgrade_in_place_with(path: &str, key: &str, fs: &dyn ChipaFs) -> ChipaResult<bool> {
+        let options = LoadOptions {
+            legacy: LegacyPolicy::Allow,
+        };
+        let file = Self::load_with_options(path, key, fs, &options)?;
+        if !file.is_legacy() {
+            return Ok(false);
+        }
+        file.save_with(path, key, fs)?;
+        Ok(true)
+    }
+
+    #[cfg(feature = "fs")]
+    /// Opens `path` with the first key in `keys` that works, returning the file and the
+    /// index of that key. Use it to rotate keys without rewriting every file at once.
+    ///
+    /// ```
+    /// # #[cfg(all(feature = "test-util", feature = "fs"))]
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use chipa_license_validator::{test_util::TempDir, ChipaError, ChipaFile, Version};
+    ///
+    /// let dir = TempDir::new()?;
+    /// let path = dir.file("cache.chipa");
+    /// ChipaFile::new(Version::LATEST, &"cached")?.save(&path, "old key")?;
+    ///
+    /// let (file, index) = ChipaFile::load_with_keys(&path, &["new key", "old key"])?;
+    /// assert_eq!(index, 1);
+    /// file.save(&path, "new key")?;
+    ///
+    /// match ChipaFile::load_with_keys(&path, &["old key", "other key"]) {
+    ///     Err(ChipaError::AllKeysFailed(failures)) => assert_eq!(failures.len(), 2),
+    ///     other => panic!("expected every key to fail, found {:?}", other),
+    /// }
+    /// # Ok(())
+    /// # }
+    /// # #[cfg(not(all(feature = "test-util", feature = "fs")))]
+    /// # fn main() {}
+    /// ```
+    pub fn load_with_keys(path: &str, keys: &[&str]) -> ChipaResult<(Self, usize)> {
+        Self::load_with_keys_with(path, keys, &RealFs)
+    }
+
+    /// [`ChipaFile::load_with_keys`] on any [`ChipaFs`].
+    pub fn load_with_keys_with(
+        path: &str,
+        keys: &[&str],
+        fs: &dyn ChipaFs,
+    ) -> ChipaResult<(Self, usize)> {
+        let timer = Timer::start();
+        let path = Self::existing_chipa_path(path)?;
+        let data = fs.read(&path)?;
+        let encrypted = Encrypted::decode(&data, Some(&path), &LoadOptions::default())?;
+        let mut failures = Vec::with_capacity(keys.len());
+        for (index, key) in keys.iter().enumerate() {
+            match encrypted.decrypt(key) {
+                Ok(chipa_file) => {
+                    timer.finish(Operation::FileLoad, Some(data.len()));
+                    return Ok((chipa_file, index));
+                }
+                Err(e) => failures.push(format!("key #{}: {}", index, e.kind())),
+            }
+        }
+        Err(ChipaError::AllKeysFailed(failures))
+    }
+
+    // Unlike `chipa_path`, refuses paths that do not already end with `.chipa`.
+    pub(crate) fn existing_chipa_path(path: &str) -> ChipaResult<PathBuf> {
         let path = PathBuf::from(path);
         match path.extension() {
             Some(e) => {
@@ -104,24 +400,23 @@ impl ChipaFile {
                 ))
             }
         }
-        let file = std::fs::read(path)?;
+        Ok(path)
+    }
+
+    fn decode_legacy(file: &[u8]) -> ChipaResult<Self> {
         if file.len() < 2 {
             return Err(ChipaError::InvalidFileFormat(
                 "File is too small".to_string(),
             ));
         }
         let version: u16 = file[0] as u16 * 256  + file[1] as u16;
-        let version = Version::try_from(version).map_err(|e| ChipaError::Decryption(anyhow::Error::from(e)))?;
+        let version = UpstreamVersion::try_from(version).map_err(|e| ChipaError::Decryption(anyhow::Error::from(e)))?;
         let slice = version
             .base_decrypt_bytes(&file[2..])
             .map_err(|e| ChipaError::Decryption(anyhow::Error::from(e)))?;
         let chipa_file: ChipaFile =
             rmp_serde::from_slice(slice.as_ref())
                 .map_err(|e| ChipaError::Decode(e.to_string()))?;
-        let chipa_file = ChipaFile {
-            version: chipa_file.version,
-            body: chipa_file.decrypt_body(key)?,
-        };
         Ok(chipa_file)
     }
 
@@ -131,12 +426,118 @@ impl ChipaFile {
         Ok(data)
     }
 
+    /// Reads the value held by the file like `read::<serde_json::Value>()`, but fails with
+    /// `ChipaError::LimitExceeded` as soon as it nests deeper or holds more nodes than
+    /// `limits` allow. Use it for files that may come from an untrusted source.
+    pub fn read_bounded(&self, limits: ValueLimits) -> ChipaResult<serde_json::Value> {
+        limits::decode(self.body.as_ref(), limits)
+    }
+
     pub fn write<T: Serialize>(&mut self, data: &T) -> ChipaResult<()> {
         let data = rmp_serde::to_vec(data)
             .map_err(|e| ChipaError::Encode(e.to_string()))?;
         self.body = Bytes::from(data);
         Ok(())
     }
+
+    /// Seals `body` so that only `open_sealed` with `license` and the token the license
+    /// server issued for it can read it. `issuer_key` must be that token.
+    ///
+    /// The key is derived from the token itself, so a sealed file is bound to one token,
+    /// not to the license: once the server rotates the license's token, files sealed
+    /// for the old one fail to open with `ChipaError::Tampered` and must be sealed again
+    /// with the new token.
+    pub fn seal_for_license<T: Serialize>(
+        version: Version,
+        body: &T,
+        license: Uuid,
+        issuer_key: &str,
+    ) -> ChipaResult<Self> {
+        let inner = Self::new(version, body)?;
+        let payload = inner.encrypt_body(&sealing_key(license, issuer_key))?;
+        Self::new(version, &SealedEnvelope { license, payload })
+    }
+
+    #[cfg(feature = "fs")]
+    pub fn save_sealed(&self, path: &str) -> ChipaResult<()> {
+        self.save(path, SEALED_KEY)
+    }
+
+    #[cfg(feature = "fs")]
+    /// Opens a file written by `seal_for_license` and `save_sealed`. `token` must be the
+    /// one the file was sealed with: a file sealed for another license fails with
+    /// `ChipaError::WrongLicense`, and one sealed with a token that has since been
+    /// rotated fails with `ChipaError::Tampered`, like a modified file.
+    pub fn open_sealed<T: DeserializeOwned>(
+        path: &str,
+        license: Uuid,
+        token: &str,
+    ) -> ChipaResult<T> {
+        let file = Self::load(path, SEALED_KEY).map_err(|e| match e {
+            ChipaError::Decryption(e) => ChipaError::Tampered(e.to_string()),
+            ChipaError::Decode(e) => ChipaError::Tampered(e),
+            e => e,
+        })?;
+        let envelope: SealedEnvelope = file
+            .read()
+            .map_err(|e| ChipaError::Tampered(e.to_string()))?;
+        if envelope.license != license {
+            return Err(ChipaError::WrongLicense {
+                expected: license,
+                found: envelope.license,
+            });
+        }
+        let sealed = ChipaFile {
+            version: file.version,
+            body: envelope.payload,
+            legacy: file.legacy,
+        };
+        let opened = ChipaFile {
+            version: file.version,
+            body: sealed
+                .decrypt_body(&sealing_key(license, token))
+                .map_err(|e| ChipaError::Tampered(e.to_string()))?,
+            legacy: file.legacy,
+        };
+        opened.read()
+    }
+}
+
+// A file as read, before a key is tried on it.
+enum Encrypted<'a> {
+    Streamed(&'a [u8]),
+    // Only the body is still encrypted.
+    Legacy(ChipaFile),
+}
+
+impl<'a> Encrypted<'a> {
+    fn decode(data: &'a [u8], path: Option<&Path>, options: &LoadOptions) -> ChipaResult<Self> {
+        if data.len() >= 2 && u16::from_be_bytes([data[0], data[1]]) & STREAM_FLAG != 0 {
+            return Ok(Encrypted::Streamed(data));
+        }
+        let file = ChipaFile::decode_legacy(data)?;
+        legacy::check(options.legacy, path, file.version()?)?;
+        Ok(Encrypted::Legacy(file))
+    }
+
+    fn decrypt(&self, key: &str) -> ChipaResult<ChipaFile> {
+        match self {
+            Encrypted::Streamed(data) => {
+                let mut body = Vec::with_capacity(data.len());
+                let (version, _) = ChipaFile::decrypt_versioned(key, *data, &mut body)?;
+                Ok(ChipaFile {
+                    version: version.upstream(),
+                    body: Bytes::from(body),
+                    legacy: false,
+                })
+            }
+            Encrypted::Legacy(file) => Ok(ChipaFile {
+                version: file.version,
+                body: file.decrypt_body(key)?,
+                legacy: true,
+            }),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -158,20 +559,23 @@ mod tests {
     use uuid::Uuid;
 
     use super::*;
+    use crate::fs::MemoryFs;
 
     #[test]
     fn test_different_data_types() {
+        let fs = MemoryFs::new();
         let file_path = "test_types.chipa";
         let key = "test_key_123";
 
         // Test with String
         let string_data = "Hello, World!".to_string();
         let chipa_file = ChipaFile::new(Version::V1, &string_data).unwrap();
-        chipa_file.save(file_path, key).unwrap();
+        chipa_file.save_with(file_path, key, &fs).unwrap();
         // let decrypted = Version::V1.base_decrypt_bytes(&data).unwrap();
         // let (pseudo, _) = dbg!(bincode::serde::decode_from_slice::<ChipaFile, Configuration>(decrypted.as_ref(), config::standard()).unwrap());
         // let enc = Version::V1.encryptor();
-        let loaded_file = ChipaFile::load(file_path, key).unwrap();
+        let loaded_file = ChipaFile::load_with(file_path, key, &fs).unwrap();
+        assert_eq!(loaded_file.version().unwrap(), Version::V1);
         let loaded_string: String = loaded_file.read().unwrap();
         println!("Loaded String: {}", loaded_string);
         assert_eq!(loaded_string, string_data);
@@ -179,16 +583,29 @@ mod tests {
         // Test with Vec<i32>
         let vec_data = vec![1, 2, 3, 4, 5];
         let chipa_file = ChipaFile::new(Version::V1, &vec_data).unwrap();
-        chipa_file.save(file_path, key).unwrap();
+        chipa_file.save_with(file_path, key, &fs).unwrap();
 
-        let loaded_file = ChipaFile::load(file_path, key).unwrap();
+        let loaded_file = ChipaFile::load_with(file_path, key, &fs).unwrap();
         let loaded_vec: Vec<i32> = loaded_file.read().unwrap();
         assert_eq!(loaded_vec, vec_data);
-
-        // Cleanup
-        // std::fs::remove_file(file_path)?;
     }
 
+    #[test]
+    fn test_bytes_roundtrip() {
+        let data = vec!["segment".to_string(); 4];
+        let bytes = ChipaFile::new(Version::V1, &data)
+            .unwrap()
+            .to_bytes("key")
+            .unwrap();
+        let loaded: Vec<String> = ChipaFile::from_bytes(&bytes, "key").unwrap().read().unwrap();
+        assert_eq!(loaded, data);
+
+        assert!(ChipaFile::from_bytes(&bytes, "other").is_err());
+        assert!(matches!(
+            ChipaFile::from_bytes(&bytes[..1], "key"),
+            Err(ChipaError::InvalidFileFormat(_))
+        ));
+    }
 
     fn complex() -> Value {
         json!({
@@ -324,18 +741,19 @@ mod tests {
 
     #[test]
     fn test_complex_json() {
+        let fs = MemoryFs::new();
         let file_path = "test_complex.chipa";
         let key = "test_key_123";
 
         // Create and save complex JSON
         let chipa_file = ChipaFile::new(Version::V1, &complex()).unwrap();
-        chipa_file.save(file_path, key).unwrap();
+        chipa_file.save_with(file_path, key, &fs).unwrap();
         // Load and verify
-        let loaded_file = ChipaFile::load(file_path, "test_key_123").unwrap();
+        let loaded_file = ChipaFile::load_with(file_path, "test_key_123", &fs).unwrap();
         let loaded_data: Value = loaded_file.read().unwrap();
         // Verify specific nested values
         dbg!(complex().to_string().len());
-        dbg!(std::fs::read(file_path).unwrap().len());
+        dbg!(fs.read(std::path::Path::new(file_path)).unwrap().len());
 
         assert_eq!(loaded_data["company"]["name"], "TechCorp Industries");
         assert_eq!(loaded_data["employees"][0]["name"], "John Doe");
@@ -365,12 +783,8 @@ mod tests {
             loaded_data["metrics"]["growth_rate"].as_f64().unwrap(),
             34.5
         );
-
-        // Cleanup
-        // let _ = std::fs::remove_file(file_path);
     }
 
-    const TEST_FILE_PATH: &str = "chipa/test_data.chipa";
     const TEST_PRIMITIVE_PATH: &str = "chipa/test_primitive.chipa";
     const TEST_COMPOUND_PATH: &str = "chipa/test_compound_types.chipa";
     const TEST_STANDARD_PATH: &str = "chipa/test_standard_types.chipa";
@@ -379,26 +793,20 @@ mod tests {
     const TEST_ENUM_PATH: &str = "chipa/test_enum.chipa";
     const TEST_KEY: &str = "test_encryption_key";
 
-    fn cleanup_file() {
-        let _ = std::fs::remove_file(TEST_FILE_PATH);
-    }
-
     // Helper function to perform the save and load test
     fn test_serde_roundtrip<T>(path: &str, data: &T)
     where
         T: Serialize + DeserializeOwned + PartialEq + std::fmt::Debug + 'static,
     {
-        cleanup_file();
+        let fs = MemoryFs::new();
 
         let chipa_file = ChipaFile::new(Version::V1, data).unwrap();
-        chipa_file.save(path, TEST_KEY).unwrap();
+        chipa_file.save_with(path, TEST_KEY, &fs).unwrap();
 
-        let loaded_file = ChipaFile::load(path, TEST_KEY).unwrap();
+        let loaded_file = ChipaFile::load_with(path, TEST_KEY, &fs).unwrap();
         let loaded_data: T = loaded_file.read().unwrap();
 
         assert_eq!(data, &loaded_data);
-
-        cleanup_file();
     }
 
     #[test]
@@ -516,4 +924,88 @@ mod tests {
             value: "hello".to_string(),
         });
     }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_sealed_for_license() {
+        let license = Uuid::new_v4();
+        let token = "issued_token";
+        let data = CustomStruct {
+            name: "Sealed Struct".to_string(),
+            age: 7,
+            data: vec![1, 2, 3],
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let sealed_path = dir.path().join("test_sealed.chipa");
+        let sealed_path = sealed_path.to_str().unwrap();
+        let sealed = ChipaFile::seal_for_license(Version::V1, &data, license, token).unwrap();
+        sealed.save_sealed(sealed_path).unwrap();
+
+        let opened: CustomStruct = ChipaFile::open_sealed(sealed_path, license, token).unwrap();
+        assert_eq!(opened, data);
+
+        let other = Uuid::new_v4();
+        match ChipaFile::open_sealed::<CustomStruct>(sealed_path, other, token) {
+            Err(ChipaError::WrongLicense { expected, found }) => {
+                assert_eq!(expected, other);
+                assert_eq!(found, license);
+            }
+            r => panic!("Expected WrongLicense, found {:?}", r),
+        }
+        assert!(matches!(
+            ChipaFile::open_sealed::<CustomStruct>(sealed_path, license, "other_token"),
+            Err(ChipaError::Tampered(_))
+        ));
+
+        let mut raw = std::fs::read(sealed_path).unwrap();
+        let last = raw.len() - 1;
+        raw[last] ^= 0xff;
+        std::fs::write(sealed_path, raw).unwrap();
+        assert!(matches!(
+            ChipaFile::open_sealed::<CustomStruct>(sealed_path, license, token),
+            Err(ChipaError::Tampered(_))
+        ));
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_load_with_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test_rotated.chipa");
+        let path = path.to_str().unwrap();
+        let data = vec![1, 2, 3];
+        ChipaFile::new(Version::V1, &data)
+            .unwrap()
+            .save(path, "current_key")
+            .unwrap();
+
+        let (file, index) =
+            ChipaFile::load_with_keys(path, &["new_key", "current_key", "old_key"]).unwrap();
+        assert_eq!(index, 1);
+        assert_eq!(file.read::<Vec<i32>>().unwrap(), data);
+
+        match ChipaFile::load_with_keys(path, &["new_key", "old_key"]) {
+            Err(e @ ChipaError::AllKeysFailed(_)) => {
+                let message = e.to_string();
+                assert!(message.contains("key #0: decryption"));
+                assert!(message.contains("key #1: decryption"));
+                assert!(!message.contains("new_key") && !message.contains("old_key"));
+            }
+            r => panic!("Expected AllKeysFailed, found {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_load_with_keys_with_store() {
+        let fs = MemoryFs::new();
+        ChipaFile::new(Version::V1, &"rotated")
+            .unwrap()
+            .save_with("state/rotated.chipa", "current_key", &fs)
+            .unwrap();
+        let (file, index) =
+            ChipaFile::load_with_keys_with("state/rotated.chipa", &["new_key", "current_key"], &fs)
+                .unwrap();
+        assert_eq!(index, 1);
+        assert_eq!(file.read::<String>().unwrap(), "rotated");
+    }
 }
@@ -0,0 +1,530 @@
+//! Device fingerprinting for node-locked licenses.
+//!
+//! A [`DeviceFingerprint`] is made of several independently hashed components so
+//! that a single component changing does not turn a machine into a stranger. Two
+//! fingerprints are compared component by component and a [`FingerprintPolicy`]
+//! decides how many of them have to match.
+//!
+//! Stability of each component per platform:
+//!
+//! | Component     | Linux                          | macOS                              | Windows                                   |
+//! |---------------|--------------------------------|------------------------------------|-------------------------------------------|
+//! | machine id    | stable (`/etc/machine-id`), changes on reinstall | stable (`IOPlatformUUID`) | mostly stable (`MachineGuid`), may change on feature updates |
+//! | volume serial | stable (root fs UUID), changes on reformat | stable (root `Volume UUID`) | stable (`vol C:`), changes on reformat |
+//! | MAC set       | changes with USB/virtual NICs  | changes with interfaces and private addresses | changes with adapters and VPN clients |
+//! | hostname      | user editable                  | user editable, follows network name | user editable                            |
+//!
+//! The MAC set matches when at least one address is shared, so adding or removing a
+//! single adapter keeps it matching. Use [`fingerprint_override`] when even that is
+//! not stable enough: it persists the first fingerprint and reuses it afterwards.
+
+use std::collections::BTreeSet;
+#[cfg(feature = "fs")]
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+#[cfg(feature = "fs")]
+use crate::{
+    encryption::{ChipaError, ChipaFile, ChipaResult},
+    fs::{ChipaFs, RealFs},
+    version::Version,
+};
+
+// Obfuscation, not protection: the key ships in every binary, so it only stops the
+// file from being edited by hand, not by anyone who reads it out of the library.
+#[cfg(feature = "fs")]
+const OVERRIDE_KEY: &str = "chipa-fingerprint-override";
+
+pub trait ComponentSource {
+    fn machine_id(&self) -> Option<String>;
+    fn volume_serial(&self) -> Option<String>;
+    fn mac_addresses(&self) -> Vec<String>;
+    fn hostname(&self) -> Option<String>;
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DeviceFingerprint {
+    machine_id: Option<String>,
+    volume_serial: Option<String>,
+    mac_addresses: BTreeSet<String>,
+    hostname: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FingerprintPolicy {
+    pub min_matching_components: usize,
+}
+
+impl Default for FingerprintPolicy {
+    fn default() -> Self {
+        Self {
+            min_matching_components: 3,
+        }
+    }
+}
+
+impl FingerprintPolicy {
+    pub fn accepts(&self, known: &DeviceFingerprint, current: &DeviceFingerprint) -> bool {
+        known.matching_components(current) >= self.min_matching_components
+    }
+}
+
+fn hash_component(value: &str) -> String {
+    let digest = Sha256::digest(value.trim().to_lowercase().as_bytes());
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+impl DeviceFingerprint {
+    pub const COMPONENTS: usize = 4;
+
+    #[cfg(feature = "fs")]
+    pub fn collect() -> Self {
+        Self::collect_from(&SystemSource)
+    }
+
+    pub fn collect_from<S: ComponentSource>(source: &S) -> Self {
+        let non_empty = |v: Option<String>| v.filter(|v| !v.trim().is_empty());
+        Self {
+            machine_id: non_empty(source.machine_id()).map(|v| hash_component(&v)),
+            volume_serial: non_empty(source.volume_serial()).map(|v| hash_component(&v)),
+            mac_addresses: source
+                .mac_addresses()
+                .iter()
+                .filter(|m| !m.trim().is_empty() && !is_null_mac(m))
+                .map(|m| hash_component(m))
+                .collect(),
+            hostname: non_empty(source.hostname()).map(|v| hash_component(&v)),
+        }
+    }
+
+    pub fn id(&self) -> String {
+        let mut hasher = Sha256::new();
+        for component in [&self.machine_id, &self.volume_serial, &self.hostname] {
+            hasher.update(component.as_deref().unwrap_or_default());
+            hasher.update([0]);
+        }
+        for mac in &self.mac_addresses {
+            hasher.update(mac);
+            hasher.update([0]);
+        }
+        hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    /// A stable identifier for `TClient::activate_license`, made of hashes only. It
+    /// follows the machine id and falls back to the volume serial, so adapters and
+    /// hostname can change without the machine needing a second seat.
+    pub fn activation_id(&self) -> String {
+        match self.machine_id.as_ref().or(self.volume_serial.as_ref()) {
+            Some(component) => hash_component(component),
+            None => self.id(),
+        }
+    }
+
+    pub fn matching_components(&self, other: &DeviceFingerprint) -> usize {
+        let same = |a: &Option<String>, b: &Option<String>| matches!((a, b), (Some(a), Some(b)) if a == b);
+        [
+            same(&self.machine_id, &other.machine_id),
+            same(&self.volume_serial, &other.volume_serial),
+            !self.mac_addresses.is_disjoint(&other.mac_addresses),
+            same(&self.hostname, &other.hostname),
+        ]
+        .into_iter()
+        .filter(|m| *m)
+        .count()
+    }
+
+    pub fn similarity(&self, other: &DeviceFingerprint) -> f32 {
+        self.matching_components(other) as f32 / Self::COMPONENTS as f32
+    }
+}
+
+/// The fingerprint persisted at `path`, with a `.chipa` extension. The first call
+/// collects one and writes it there; later calls read it back.
+///
+/// A file that exists but cannot be read or decrypted is an error and is left alone,
+/// so that a transient failure never replaces the fingerprint a license is bound to.
+#[cfg(feature = "fs")]
+pub fn fingerprint_override(path: &str) -> ChipaResult<DeviceFingerprint> {
+    fingerprint_override_with(path, &RealFs)
+}
+
+/// [`fingerprint_override`] on any [`ChipaFs`].
+#[cfg(feature = "fs")]
+pub fn fingerprint_override_with(path: &str, fs: &dyn ChipaFs) -> ChipaResult<DeviceFingerprint> {
+    let mut path = PathBuf::from(path);
+    path.set_extension("chipa");
+    let path = path
+        .to_str()
+        .ok_or_else(|| ChipaError::InvalidFileFormat("Path is not valid UTF-8".to_string()))?;
+    if fs.exists(std::path::Path::new(path)) {
+        return ChipaFile::load_with(path, OVERRIDE_KEY, fs)?.read();
+    }
+    let fingerprint = DeviceFingerprint::collect();
+    ChipaFile::new(Version::V1, &fingerprint)?.save_with(path, OVERRIDE_KEY, fs)?;
+    Ok(fingerprint)
+}
+
+fn is_null_mac(mac: &str) -> bool {
+    mac.chars().all(|c| c == '0' || c == ':' || c == '-')
+}
+
+#[cfg(feature = "fs")]
+fn run(program: &str, args: &[&str]) -> Option<String> {
+    let output = std::process::Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8(output.stdout).ok()
+}
+
+#[cfg(feature = "fs")]
+pub struct SystemSource;
+
+#[cfg(all(feature = "fs", target_os = "linux"))]
+impl ComponentSource for SystemSource {
+    fn machine_id(&self) -> Option<String> {
+        std::fs::read_to_string("/etc/machine-id")
+            .or_else(|_| std::fs::read_to_string("/var/lib/dbus/machine-id"))
+            .ok()
+    }
+
+    fn volume_serial(&self) -> Option<String> {
+        run("findmnt", &["-no", "UUID", "/"])
+    }
+
+    fn mac_addresses(&self) -> Vec<String> {
+        let Ok(entries) = std::fs::read_dir("/sys/class/net") else {
+            return Vec::new();
+        };
+        entries
+            .flatten()
+            .filter(|e| e.file_name() != "lo")
+            .filter_map(|e| std::fs::read_to_string(e.path().join("address")).ok())
+            .collect()
+    }
+
+    fn hostname(&self) -> Option<String> {
+        std::fs::read_to_string("/proc/sys/kernel/hostname")
+            .or_else(|_| std::fs::read_to_string("/etc/hostname"))
+            .ok()
+    }
+}
+
+#[cfg(all(feature = "fs", target_os = "macos"))]
+impl ComponentSource for SystemSource {
+    fn machine_id(&self) -> Option<String> {
+        parse_ioreg_uuid(&run("ioreg", &["-rd1", "-c", "IOPlatformExpertDevice"])?)
+    }
+
+    fn volume_serial(&self) -> Option<String> {
+        parse_diskutil_volume_uuid(&run("diskutil", &["info", "/"])?)
+    }
+
+    fn mac_addresses(&self) -> Vec<String> {
+        run("ifconfig", &[])
+            .map(|o| parse_ifconfig_macs(&o))
+            .unwrap_or_default()
+    }
+
+    fn hostname(&self) -> Option<String> {
+        run("hostname", &[])
+    }
+}
+
+#[cfg(all(feature = "fs", target_os = "windows"))]
+impl ComponentSource for SystemSource {
+    fn machine_id(&self) -> Option<String> {
+        parse_reg_machine_guid(&run(
+            "reg",
+            &[
+                "query",
+                r"HKLM\SOFTWARE\Microsoft\Cryptography",
+                "/v",
+                "MachineGuid",
+            ],
+        )?)
+    }
+
+    fn volume_serial(&self) -> Option<String> {
+        parse_vol_serial(&run("cmd", &["/C", "vol", "C:"])?)
+    }
+
+    fn mac_addresses(&self) -> Vec<String> {
+        run("getmac", &["/fo", "csv", "/nh"])
+            .map(|o| parse_getmac_macs(&o))
+            .unwrap_or_default()
+    }
+
+    fn hostname(&self) -> Option<String> {
+        std::env::var("COMPUTERNAME").ok()
+    }
+}
+
+#[cfg(all(
+    feature = "fs",
+    not(any(target_os = "linux", target_os = "macos", target_os = "windows"))
+))]
+impl ComponentSource for SystemSource {
+    fn machine_id(&self) -> Option<String> {
+        None
+    }
+
+    fn volume_serial(&self) -> Option<String> {
+        None
+    }
+
+    fn mac_addresses(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    fn hostname(&self) -> Option<String> {
+        run("hostname", &[])
+    }
+}
+
+#[cfg_attr(not(any(target_os = "macos", test)), allow(dead_code))]
+fn parse_ioreg_uuid(output: &str) -> Option<String> {
+    output
+        .lines()
+        .find(|l| l.contains("\"IOPlatformUUID\""))
+        .and_then(|l| l.split('=').nth(1))
+        .map(|v| v.trim().trim_matches('"').to_string())
+}
+
+#[cfg_attr(not(any(target_os = "macos", test)), allow(dead_code))]
+fn parse_diskutil_volume_uuid(output: &str) -> Option<String> {
+    output
+        .lines()
+        .find(|l| l.trim_start().starts_with("Volume UUID:"))
+        .and_then(|l| l.split_once(':'))
+        .map(|(_, v)| v.trim().to_string())
+}
+
+#[cfg_attr(not(any(target_os = "macos", test)), allow(dead_code))]
+fn parse_ifconfig_macs(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|l| l.trim().strip_prefix("ether "))
+        .filter_map(|l| l.split_whitespace().next())
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg_attr(not(any(target_os = "windows", test)), allow(dead_code))]
+fn parse_reg_machine_guid(output: &str) -> Option<String> {
+    output
+        .lines()
+        .find(|l| l.contains("MachineGuid"))
+        .and_then(|l| l.split_whitespace().last())
+        .map(str::to_string)
+}
+
+#[cfg_attr(not(any(target_os = "windows", test)), allow(dead_code))]
+fn parse_vol_serial(output: &str) -> Option<String> {
+    output
+        .lines()
+        .find(|l| l.contains("Serial Number"))
+        .and_then(|l| l.split_whitespace().last())
+        .map(str::to_string)
+}
+
+#[cfg_attr(not(any(target_os = "windows", test)), allow(dead_code))]
+fn parse_getmac_macs(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|l| l.split(',').next())
+        .map(|m| m.trim().trim_matches('"').to_string())
+        .filter(|m| m.len() == 17)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct MockSource {
+        machine_id: Option<String>,
+        volume_serial: Option<String>,
+        macs: Vec<String>,
+        hostname: Option<String>,
+    }
+
+    impl ComponentSource for MockSource {
+        fn machine_id(&self) -> Option<String> {
+            self.machine_id.clone()
+        }
+
+        fn volume_serial(&self) -> Option<String> {
+            self.volume_serial.clone()
+        }
+
+        fn mac_addresses(&self) -> Vec<String> {
+            self.macs.clone()
+        }
+
+        fn hostname(&self) -> Option<String> {
+            self.hostname.clone()
+        }
+    }
+
+    fn machine() -> MockSource {
+        MockSource {
+            machine_id: Some("4c4c4544-0042-3510-8052-b4c04f4e3432".to_string()),
+            volume_serial: Some("A1B2-C3D4".to_string()),
+            macs: vec!["a4:83:e7:12:34:56".to_string(), "02:42:ac:11:00:02".to_string()],
+            hostname: Some("trading-laptop".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_identical_machine() {
+        let a = DeviceFingerprint::collect_from(&machine());
+        let b = DeviceFingerprint::collect_from(&machine());
+        assert_eq!(a, b);
+        assert_eq!(a.id(), b.id());
+        assert_eq!(a.similarity(&b), 1.0);
+        assert!(FingerprintPolicy::default().accepts(&a, &b));
+    }
+
+    #[test]
+    fn test_components_are_hashed() {
+        let fingerprint = DeviceFingerprint::collect_from(&machine());
+        let serialized = serde_json::to_string(&fingerprint).unwrap();
+        assert!(!serialized.contains("trading-laptop"));
+        assert!(!serialized.contains("a4:83:e7:12:34:56"));
+    }
+
+    #[test]
+    fn test_activation_id_is_stable() {
+        let known = DeviceFingerprint::collect_from(&machine());
+        let mut updated = machine();
+        updated.hostname = Some("renamed".to_string());
+        updated.macs = vec!["3e:22:fb:aa:bb:cc".to_string()];
+        let current = DeviceFingerprint::collect_from(&updated);
+        assert_ne!(known.id(), current.id());
+        assert_eq!(known.activation_id(), current.activation_id());
+        assert!(!known.activation_id().contains("4c4c4544"));
+
+        updated.machine_id = None;
+        let without_machine_id = DeviceFingerprint::collect_from(&updated);
+        assert_ne!(without_machine_id.activation_id(), known.activation_id());
+    }
+
+    #[test]
+    fn test_windows_machine_guid_change() {
+        let known = DeviceFingerprint::collect_from(&machine());
+        let mut updated = machine();
+        updated.machine_id = Some("9f1c7e2a-5d3b-4e8f-a1c6-0b2d4e6f8a10".to_string());
+        let current = DeviceFingerprint::collect_from(&updated);
+        assert_eq!(known.matching_components(&current), 3);
+        assert!(FingerprintPolicy::default().accepts(&known, &current));
+        assert!(!FingerprintPolicy { min_matching_components: 4 }.accepts(&known, &current));
+    }
+
+    #[test]
+    fn test_macos_interface_change() {
+        let known = DeviceFingerprint::collect_from(&machine());
+        let mut updated = machine();
+        updated.macs = vec!["a4:83:e7:12:34:56".to_string(), "3e:22:fb:aa:bb:cc".to_string()];
+        let current = DeviceFingerprint::collect_from(&updated);
+        assert_eq!(known.matching_components(&current), 4);
+
+        updated.macs = vec!["3e:22:fb:aa:bb:cc".to_string()];
+        let current = DeviceFingerprint::collect_from(&updated);
+        assert_eq!(known.matching_components(&current), 3);
+    }
+
+    #[test]
+    fn test_linux_missing_components() {
+        let known = DeviceFingerprint::collect_from(&machine());
+        let current = DeviceFingerprint::collect_from(&MockSource {
+            hostname: Some("trading-laptop".to_string()),
+            macs: vec!["00:00:00:00:00:00".to_string()],
+            ..Default::default()
+        });
+        assert_eq!(known.matching_components(&current), 1);
+        assert_eq!(current.matching_components(&current), 1);
+        assert!(!FingerprintPolicy::default().accepts(&known, &current));
+    }
+
+    #[test]
+    fn test_different_machine() {
+        let known = DeviceFingerprint::collect_from(&machine());
+        let current = DeviceFingerprint::collect_from(&MockSource {
+            machine_id: Some("other".to_string()),
+            volume_serial: Some("FFFF-0000".to_string()),
+            macs: vec!["11:22:33:44:55:66".to_string()],
+            hostname: Some("office-desktop".to_string()),
+        });
+        assert_eq!(known.similarity(&current), 0.0);
+    }
+
+    #[test]
+    fn test_macos_parsers() {
+        let ioreg = r#"+-o J314sAP  <class IOPlatformExpertDevice>
+    {
+      "IOPlatformSerialNumber" = "C02XXXXXX"
+      "IOPlatformUUID" = "1A2B3C4D-0000-1111-2222-333344445555"
+    }"#;
+        assert_eq!(
+            parse_ioreg_uuid(ioreg).as_deref(),
+            Some("1A2B3C4D-0000-1111-2222-333344445555")
+        );
+        let diskutil = "   Volume Name:               Macintosh HD\n   Volume UUID:               0A81F3B1-51D9-3335-B3E3-169C3640360D\n";
+        assert_eq!(
+            parse_diskutil_volume_uuid(diskutil).as_deref(),
+            Some("0A81F3B1-51D9-3335-B3E3-169C3640360D")
+        );
+        let ifconfig = "en0: flags=8863<UP>\n\tether a4:83:e7:12:34:56 \n\tinet 10.0.0.2\nen1: flags=8863<UP>\n\tether 3e:22:fb:aa:bb:cc\n";
+        assert_eq!(
+            parse_ifconfig_macs(ifconfig),
+            vec!["a4:83:e7:12:34:56", "3e:22:fb:aa:bb:cc"]
+        );
+    }
+
+    #[test]
+    fn test_windows_parsers() {
+        let reg = "\r\nHKEY_LOCAL_MACHINE\\SOFTWARE\\Microsoft\\Cryptography\r\n    MachineGuid    REG_SZ    6e9a1f3c-1b2d-4c5e-9f80-a1b2c3d4e5f6\r\n";
+        assert_eq!(
+            parse_reg_machine_guid(reg).as_deref(),
+            Some("6e9a1f3c-1b2d-4c5e-9f80-a1b2c3d4e5f6")
+        );
+        let vol = " Volume in drive C has no label.\r\n Volume Serial Number is A1B2-C3D4\r\n";
+        assert_eq!(parse_vol_serial(vol).as_deref(), Some("A1B2-C3D4"));
+        let getmac = "\"A4-83-E7-12-34-56\",\"\\Device\\Tcpip_{1}\"\r\n\"N/A\",\"Media disconnected\"\r\n";
+        assert_eq!(parse_getmac_macs(getmac), vec!["A4-83-E7-12-34-56"]);
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_fingerprint_override_is_reused() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test_fingerprint.chipa");
+        let path = path.to_str().unwrap();
+        let first = fingerprint_override(path).unwrap();
+        let second = fingerprint_override(path).unwrap();
+        assert_eq!(first, second);
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_fingerprint_override_with_store() {
+        let fs = crate::fs::MemoryFs::new();
+        let first = fingerprint_override_with("state/fingerprint", &fs).unwrap();
+        assert!(fs.exists(std::path::Path::new("state/fingerprint.chipa")));
+        assert_eq!(fingerprint_override_with("state/fingerprint", &fs).unwrap(), first);
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_fingerprint_override_unreadable_is_kept() {
+        let fs = crate::fs::MemoryFs::new();
+        let path = std::path::Path::new("state/fingerprint.chipa");
+        fs.write_atomic(path, b"not a chipa file").unwrap();
+        assert!(fingerprint_override_with("state/fingerprint", &fs).is_err());
+        assert_eq!(fs.read(path).unwrap(), b"not a chipa file");
+    }
+}
@@ -0,0 +1,282 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+#[cfg(any(test, feature = "test-util"))]
+use std::{collections::HashMap, sync::Mutex};
+
+/// Storage backend for every file this crate persists. `RealFs` is the default and
+/// `MemoryFs` (feature `test-util`) is meant for tests; implement it to keep the
+/// state somewhere else, e.g. a database.
+///
+/// Paths are opaque names: a backend may map them to rows or keys however it likes,
+/// as long as the same path always names the same blob. Implementations must
+/// guarantee that:
+///
+/// - `write_atomic` replaces the whole blob or nothing. A concurrent or later `read`
+///   sees either the previous contents or the new ones, never a mix or a prefix.
+/// - once `write_atomic` returns `Ok`, the data survives the process exiting. Surviving
+///   power loss is best effort; `RealFs` syncs the file before renaming it into place.
+/// - `read` and `remove` of a missing path fail with `io::ErrorKind::NotFound`.
+/// - `list(dir)` returns the paths directly inside `dir` that `read` can open, in
+///   any order, without temporary files left behind by `write_atomic`.
+///
+/// `ChipaTxn` also needs `create_new`, `rename` and `sync_dir`. Their defaults are
+/// built on the methods above and are not atomic: override `create_new` and `rename`
+/// if the backend can do them in one step, or transactions on it can race.
+///
+/// Run `check_chipa_fs` (feature `test-util`) against a backend to verify it.
+pub trait ChipaFs: Send + Sync {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+    fn write_atomic(&self, path: &Path, data: &[u8]) -> io::Result<()>;
+    fn exists(&self, path: &Path) -> bool;
+    fn remove(&self, path: &Path) -> io::Result<()>;
+    fn list(&self, dir: &Path) -> io::Result<Vec<PathBuf>>;
+
+    /// Writes `data` to `path` only if nothing is there yet, failing with
+    /// `io::ErrorKind::AlreadyExists` otherwise.
+    fn create_new(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        if self.exists(path) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                path.display().to_string(),
+            ));
+        }
+        self.write_atomic(path, data)
+    }
+
+    /// Moves the blob at `from` to `to`, replacing whatever `to` held.
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.write_atomic(to, &self.read(from)?)?;
+        self.remove(from)
+    }
+
+    /// Makes the renames and removals inside `dir` so far survive power loss. Backends
+    /// without directories can keep the default, which does nothing.
+    fn sync_dir(&self, _dir: &Path) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "fs")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RealFs;
+
+#[cfg(feature = "fs")]
+impl ChipaFs for RealFs {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        std::fs::read(path)
+    }
+
+    fn write_atomic(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        use std::io::Write;
+
+        replace_file(path, |file| file.write_all(data))
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        std::fs::remove_file(path)
+    }
+
+    fn list(&self, dir: &Path) -> io::Result<Vec<PathBuf>> {
+        let mut paths = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            if entry.file_type()?.is_file() && path.extension().is_none_or(|e| e != "tmp") {
+                paths.push(path);
+            }
+        }
+        Ok(paths)
+    }
+
+    fn create_new(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        use std::io::Write;
+
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)?;
+        file.write_all(data)?;
+        file.sync_all()
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        std::fs::rename(from, to)
+    }
+
+    #[cfg(unix)]
+    fn sync_dir(&self, dir: &Path) -> io::Result<()> {
+        std::fs::File::open(dir)?.sync_all()
+    }
+}
+
+/// Writes a sibling `.tmp` file with `write`, syncs it and renames it over `path`, so
+/// readers see the old or the new contents and never a partial file.
+#[cfg(feature = "fs")]
+pub(crate) fn replace_file<T, E: From<io::Error>>(
+    path: &Path,
+    write: impl FnOnce(&mut std::fs::File) -> Result<T, E>,
+) -> Result<T, E> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    let written = std::fs::File::create(&tmp)
+        .map_err(E::from)
+        .and_then(|mut file| {
+            let value = write(&mut file)?;
+            file.sync_all()?;
+            Ok(value)
+        });
+    let renamed = written.and_then(|value| {
+        std::fs::rename(&tmp, path)?;
+        Ok(value)
+    });
+    if renamed.is_err() {
+        let _ = std::fs::remove_file(&tmp);
+    }
+    renamed
+}
+
+#[cfg(any(test, feature = "test-util"))]
+#[derive(Debug, Default)]
+pub struct MemoryFs {
+    files: Mutex<HashMap<PathBuf, Vec<u8>>>,
+}
+
+#[cfg(any(test, feature = "test-util"))]
+impl MemoryFs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[cfg(any(test, feature = "test-util"))]
+impl ChipaFs for MemoryFs {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.files
+            .lock()
+            .unwrap()
+            .get(path)
+            .cloned()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, path.display().to_string()))
+    }
+
+    fn write_atomic(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        self.files
+            .lock()
+            .unwrap()
+            .insert(path.to_path_buf(), data.to_vec());
+        Ok(())
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.files.lock().unwrap().contains_key(path)
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        self.files
+            .lock()
+            .unwrap()
+            .remove(path)
+            .map(|_| ())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, path.display().to_string()))
+    }
+
+    fn list(&self, dir: &Path) -> io::Result<Vec<PathBuf>> {
+        Ok(self
+            .files
+            .lock()
+            .unwrap()
+            .keys()
+            .filter(|path| path.parent() == Some(dir))
+            .cloned()
+            .collect())
+    }
+
+    fn create_new(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        let mut files = self.files.lock().unwrap();
+        if files.contains_key(path) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                path.display().to_string(),
+            ));
+        }
+        files.insert(path.to_path_buf(), data.to_vec());
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let mut files = self.files.lock().unwrap();
+        let data = files
+            .remove(from)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, from.display().to_string()))?;
+        files.insert(to.to_path_buf(), data);
+        Ok(())
+    }
+}
+
+#[cfg(any(test, feature = "test-util"))]
+pub fn check_chipa_fs<S: ChipaFs + ?Sized>(fs: &S, dir: &Path) {
+    let a = dir.join("conformance-a.chipa");
+    let b = dir.join("conformance-b.chipa");
+
+    let missing = fs.read(&a).expect_err("read of a missing path should fail");
+    assert_eq!(missing.kind(), io::ErrorKind::NotFound, "read of a missing path");
+    assert!(!fs.exists(&a), "exists before the first write");
+
+    fs.write_atomic(&a, b"first version, longer").unwrap();
+    assert!(fs.exists(&a), "exists after write_atomic");
+    assert_eq!(fs.read(&a).unwrap(), b"first version, longer");
+
+    fs.write_atomic(&a, b"second").unwrap();
+    assert_eq!(fs.read(&a).unwrap(), b"second", "write_atomic must replace the whole blob");
+
+    fs.write_atomic(&b, &[]).unwrap();
+    assert_eq!(fs.read(&b).unwrap(), b"", "empty blobs are stored as written");
+
+    let mut listed = fs.list(dir).unwrap();
+    listed.sort();
+    assert_eq!(listed, vec![a.clone(), b.clone()], "list returns exactly the written paths");
+
+    fs.remove(&a).unwrap();
+    assert!(!fs.exists(&a), "exists after remove");
+    let missing = fs.remove(&a).expect_err("remove of a missing path should fail");
+    assert_eq!(missing.kind(), io::ErrorKind::NotFound, "remove of a missing path");
+    assert_eq!(fs.list(dir).unwrap(), vec![b.clone()], "list after remove");
+
+    fs.create_new(&a, b"created").unwrap();
+    let exists = fs.create_new(&a, b"again").expect_err("create_new over a blob should fail");
+    assert_eq!(exists.kind(), io::ErrorKind::AlreadyExists, "create_new over a blob");
+    assert_eq!(fs.read(&a).unwrap(), b"created", "create_new must not replace a blob");
+
+    fs.rename(&a, &b).unwrap();
+    assert!(!fs.exists(&a), "exists of the source after rename");
+    assert_eq!(fs.read(&b).unwrap(), b"created", "rename must replace the target");
+    fs.sync_dir(dir).unwrap();
+    fs.remove(&b).unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_real_fs_conformance() {
+        let dir = tempfile::tempdir().unwrap();
+        check_chipa_fs(&RealFs, dir.path());
+    }
+
+    #[test]
+    fn test_memory_fs_conformance() {
+        let fs = MemoryFs::new();
+        check_chipa_fs(&fs, Path::new("state"));
+        check_chipa_fs(&fs as &dyn ChipaFs, Path::new("other/state"));
+    }
+}
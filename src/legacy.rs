@@ -0,0 +1,178 @@
+//! Deprecation of the pre-streaming `.chipa` layout.
+//!
+//! Files written before the streamed layout have no flag in their version prefix and
+//! repeat the version inside the encrypted envelope. `ChipaFile::load` and `from_bytes`
+//! still read them and mark them with `ChipaFile::is_legacy`; the [`LegacyPolicy`] in
+//! `LoadOptions` decides what else happens. By default each one is handed to the
+//! observer set with [`set_legacy_file_observer`], so an application can count the
+//! files still in the wild before the layout is dropped. `ChipaFile::upgrade_in_place`
+//! rewrites a file in the current layout.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::RwLock,
+};
+
+use crate::{
+    encryption::{ChipaError, ChipaResult},
+    version::Version,
+};
+
+static OBSERVER: RwLock<Option<LegacyFileObserver>> = RwLock::new(None);
+
+/// Called with every legacy file read under `LegacyPolicy::WarnViaObserver`, on the
+/// thread that read it.
+pub type LegacyFileObserver = Box<dyn Fn(&LegacyFile) + Send + Sync>;
+
+/// What loading a file in the pre-streaming layout does.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LegacyPolicy {
+    /// Reads it silently.
+    Allow,
+    /// Reads it and hands it to the observer set with `set_legacy_file_observer`.
+    #[default]
+    WarnViaObserver,
+    /// Refuses it with `ChipaError::LegacyFormatRejected`.
+    Deny,
+}
+
+/// A legacy file read under `LegacyPolicy::WarnViaObserver`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LegacyFile {
+    /// `None` for files read with `from_bytes`.
+    pub path: Option<PathBuf>,
+    pub version: Version,
+}
+
+/// Hands every legacy file read under `LegacyPolicy::WarnViaObserver` to `observer`,
+/// e.g. to report how many are left. `None` removes the observer.
+///
+/// ```
+/// use chipa_license_validator::set_legacy_file_observer;
+///
+/// set_legacy_file_observer(Some(Box::new(|file| {
+///     eprintln!("legacy .chipa file {:?}, version {:?}", file.path, file.version);
+/// })));
+/// # set_legacy_file_observer(None);
+/// ```
+pub fn set_legacy_file_observer(observer: Option<LegacyFileObserver>) {
+    *OBSERVER.write().unwrap_or_else(|e| e.into_inner()) = observer;
+}
+
+// Applies `policy` to a legacy file about to be decrypted.
+pub(crate) fn check(
+    policy: LegacyPolicy,
+    path: Option<&Path>,
+    version: Version,
+) -> ChipaResult<()> {
+    match policy {
+        LegacyPolicy::Allow => Ok(()),
+        LegacyPolicy::WarnViaObserver => {
+            if let Some(observer) = &*OBSERVER.read().unwrap_or_else(|e| e.into_inner()) {
+                observer(&LegacyFile {
+                    path: path.map(Path::to_path_buf),
+                    version,
+                });
+            }
+            Ok(())
+        }
+        LegacyPolicy::Deny => Err(ChipaError::LegacyFormatRejected(path.map(Path::to_path_buf))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::{
+        encryption::{ChipaFile, LoadOptions},
+        fs::{ChipaFs, MemoryFs},
+    };
+
+    const KEY: &str = "legacy key";
+
+    fn options(legacy: LegacyPolicy) -> LoadOptions {
+        LoadOptions { legacy }
+    }
+
+    fn legacy_fs(path: &str) -> MemoryFs {
+        let fs = MemoryFs::new();
+        let file = ChipaFile::new(Version::LATEST, &"old layout").unwrap();
+        fs.write_atomic(Path::new(path), &file.to_legacy_bytes(KEY).unwrap()).unwrap();
+        fs
+    }
+
+    #[test]
+    fn test_legacy_policies() {
+        let path = "/policies/settings.chipa";
+        let fs = legacy_fs(path);
+
+        let file = ChipaFile::load_with_options(path, KEY, &fs, &options(LegacyPolicy::Allow))
+            .unwrap();
+        assert!(file.is_legacy());
+        assert_eq!(file.read::<String>().unwrap(), "old layout");
+
+        match ChipaFile::load_with_options(path, KEY, &fs, &options(LegacyPolicy::Deny)) {
+            Err(e @ ChipaError::LegacyFormatRejected(_)) => {
+                assert_eq!(e.kind(), "legacy_format_rejected");
+                assert!(e.to_string().contains(path), "{}", e);
+                assert!(e.to_string().contains("upgrade_in_place"), "{}", e);
+            }
+            other => panic!("expected the legacy file to be rejected, found {:?}", other),
+        }
+
+        let current = ChipaFile::new(Version::LATEST, &"new layout").unwrap();
+        let bytes = current.to_bytes(KEY).unwrap();
+        let file = ChipaFile::from_bytes_with_options(&bytes, KEY, &options(LegacyPolicy::Deny))
+            .unwrap();
+        assert!(!file.is_legacy());
+        assert_eq!(file.read::<String>().unwrap(), "new layout");
+    }
+
+    #[test]
+    fn test_legacy_observer() {
+        let path = "/observer/settings.chipa";
+        let fs = legacy_fs(path);
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = seen.clone();
+        set_legacy_file_observer(Some(Box::new(move |file| {
+            // Other tests may read legacy files while this observer is set.
+            if file.path.as_deref() == Some(Path::new(path)) {
+                sink.lock().unwrap().push(file.clone());
+            }
+        })));
+
+        ChipaFile::load_with(path, KEY, &fs).unwrap();
+        ChipaFile::load_with_options(path, KEY, &fs, &options(LegacyPolicy::Allow)).unwrap();
+        ChipaFile::load_with_keys_with(path, &["other key", KEY], &fs).unwrap();
+        set_legacy_file_observer(None);
+
+        let expected = LegacyFile {
+            path: Some(PathBuf::from(path)),
+            version: Version::LATEST,
+        };
+        assert_eq!(*seen.lock().unwrap(), [expected.clone(), expected]);
+    }
+
+    #[test]
+    fn test_upgrade_in_place() {
+        let path = "/upgrade/settings.chipa";
+        let fs = legacy_fs(path);
+
+        assert!(ChipaFile::upgrade_in_place_with(path, KEY, &fs).unwrap());
+        let file = ChipaFile::load_with_options(path, KEY, &fs, &options(LegacyPolicy::Deny))
+            .unwrap();
+        assert!(!file.is_legacy());
+        assert_eq!(file.read::<String>().unwrap(), "old layout");
+        assert!(!ChipaFile::upgrade_in_place_with(path, KEY, &fs).unwrap());
+
+        let fs = legacy_fs(path);
+        assert!(matches!(
+            ChipaFile::upgrade_in_place_with(path, "wrong key", &fs),
+            Err(ChipaError::Decryption(_))
+        ));
+        let file = ChipaFile::load_with_options(path, KEY, &fs, &options(LegacyPolicy::Allow));
+        assert!(file.unwrap().is_legacy());
+    }
+}
@@ -1,19 +1,128 @@
+#[cfg(all(feature = "js", feature = "py"))]
+compile_error!(
+    "features `js` and `py` are mutually exclusive; build the Node.js binding with `--no-default-features --features js`"
+);
+
+#[cfg(all(
+    target_arch = "wasm32",
+    any(
+        feature = "fs",
+        feature = "tokio",
+        feature = "js",
+        feature = "py",
+        feature = "mock-server"
+    )
+))]
+compile_error!(
+    "features `fs`, `tokio`, `js`, `py` and `mock-server` need a native target; build for wasm32 with `--no-default-features` (optionally `--features client`) and use `chipa_license_validator::portable`"
+);
+
 mod client;
+mod config;
 mod encryption;
+mod fingerprint;
+mod fs;
+mod legacy;
+mod license;
+mod limits;
+mod perf;
+mod stream;
+#[cfg(feature = "fs")]
+mod txn;
+mod version;
+#[cfg(feature = "mock-server")]
+pub mod mock;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
 
-#[cfg(not(any(feature = "js", feature = "py")))]
-pub use client::{SecureResponse as Response, TClient as LicenseClient, TError as Error};
-#[cfg(not(any(feature = "js", feature = "py")))]
-pub use encryption::{ChipaError, ChipaFile};
+pub use portable::*;
+#[cfg(feature = "fs")]
+pub use fingerprint::{fingerprint_override, fingerprint_override_with, SystemSource};
+#[cfg(feature = "fs")]
+pub use fs::RealFs;
+#[cfg(all(feature = "client", feature = "fs"))]
+pub use client::{QuarantineObserver, Quarantined};
+#[cfg(feature = "fs")]
+pub use txn::{ChipaTxn, TxnRecovery};
 
-#[cfg(feature = "js")]
-pub use js::LicenseClient;
+/// The part of the API that builds for `wasm32-unknown-unknown`. Everything here works
+/// on bytes, in memory or over HTTP: use `ChipaFile::to_bytes`/`ChipaFile::from_bytes`,
+/// `encrypt_stream`/`decrypt_stream` over any reader and writer, or
+/// `save_with`/`load_with`/`load_with_keys_with` with your own `ChipaFs`.
+///
+/// With every feature off the crate is only this core: encryption, `ChipaFile`,
+/// licenses, errors and fingerprint comparison, without an HTTP stack or async
+/// runtime. The `client` feature adds `LicenseClient` and `Response` and also builds
+/// on wasm32. Items that need the native filesystem or OS (`RealFs`, `ChipaTxn`,
+/// `SystemSource`, `fingerprint_override` and the path-based `ChipaFile::save`,
+/// `load`, `load_with_keys`, `upgrade_in_place`, `save_stream`, `load_stream`,
+/// `save_sealed`, `open_sealed` and `ChipaReader::open`) need the `fs` feature, which
+/// refuses to build for wasm32 like `tokio` and the bindings.
+pub mod portable {
+    pub use crate::client::{
+        ActivationToken, Capability, RawValidation, Remediation, SeatInfo, SeatSample,
+        SeatUsage, ServerCapabilities, TError as Error, SUPPORT_MATRIX,
+    };
+    #[cfg(feature = "client")]
+    pub use crate::client::{SecureResponse as Response, TClient as LicenseClient};
+    #[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+    pub use crate::client::{
+        RetryEvent, RetryHook, RetryOn, TClientBuilder as LicenseClientBuilder,
+    };
+    pub use crate::encryption::{ChipaError, ChipaFile, LoadOptions};
+    pub use crate::fingerprint::{ComponentSource, DeviceFingerprint, FingerprintPolicy};
+    #[cfg(feature = "test-util")]
+    pub use crate::fs::{check_chipa_fs, MemoryFs};
+    pub use crate::fs::ChipaFs;
+    pub use crate::legacy::{
+        set_legacy_file_observer, LegacyFile, LegacyFileObserver, LegacyPolicy,
+    };
+    pub use crate::license::{LicenseId, LICENSE_KEY_NAMESPACE};
+    pub use crate::limits::ValueLimits;
+    pub use crate::stream::{ChipaReader, CHUNK_SIZE};
+    pub use crate::perf::{
+        set_slow_op_observer, set_slow_threshold, slow_threshold, EnvironmentHints, Operation,
+        PerformanceReport, SlowOp, SlowOpObserver, SLOW_OP_CAPACITY,
+    };
+    pub use crate::version::{Encryptor, UnknownVersion, Version};
+}
+
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::config::{deobfuscate, obfuscate};
+}
 
 #[cfg(feature = "js")]
-mod js {
+pub mod js {
+
+    use std::{
+        collections::HashMap,
+        num::NonZeroUsize,
+        sync::{
+            atomic::{AtomicU32, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
 
-    use crate::client::{TClient, TError};
+    use crate::{
+        client::{self, ActivationToken, Remediation, RetryHook, RetryOn, TClient, TError},
+        encryption::{self, ChipaError},
+        fingerprint::DeviceFingerprint,
+        license::LicenseId,
+        version::Version,
+    };
+    use http::header::{HeaderName, HeaderValue};
+    use napi::{
+        bindgen_prelude::{AsyncTask, ToNapiValue, TypeName},
+        sys,
+        threadsafe_function::{
+            ErrorStrategy, ThreadSafeCallContext, ThreadsafeFunction, ThreadsafeFunctionCallMode,
+        },
+        Env, JsFunction, JsUnknown, Status, Task, ValueType,
+    };
     use napi_derive::napi;
+    use serde_json::Value;
     use uuid::Uuid;
 
     impl From<TError> for napi::Error {
@@ -22,6 +131,267 @@ mod js {
         }
     }
 
+    /// The result of a license server call. A failure rejects the promise with an
+    /// `Error` carrying a `code` (see `error_code`), the Rust `kind`, the HTTP
+    /// `status`, if any, a `remediation` like the Python binding's and the number of
+    /// `attempts` made.
+    ///
+    /// Async functions can only reject with a napi `Status`, so the error object is
+    /// built here, on the JS thread, and the rejection reuses it as is.
+    pub struct Coded<T>(Result<T, TError>, u32);
+
+    // Counts the attempts of one call through the client's retry hook, which calls the
+    // user's `onRetry` as well.
+    struct Attempts(Arc<AtomicU32>);
+
+    impl Attempts {
+        fn track(client: &LicenseClient) -> (TClient, Self) {
+            let attempts = Arc::new(AtomicU32::new(1));
+            let counter = attempts.clone();
+            let on_retry = client.on_retry.clone();
+            let hook: RetryHook = Arc::new(move |event| {
+                counter.fetch_add(1, Ordering::Relaxed);
+                if let Some(on_retry) = &on_retry {
+                    on_retry(event);
+                }
+            });
+            (client.client.clone().set_on_retry(Some(hook)), Self(attempts))
+        }
+
+        fn coded<T>(self, result: Result<T, TError>) -> Coded<T> {
+            Coded(result, self.0.load(Ordering::Relaxed))
+        }
+    }
+
+    impl<T: ToNapiValue> ToNapiValue for Coded<T> {
+        unsafe fn to_napi_value(env: sys::napi_env, val: Self) -> napi::Result<sys::napi_value> {
+            match val.0 {
+                Ok(value) => T::to_napi_value(env, value),
+                Err(e) => Err(coded_error(Env::from_raw(env), e, val.1)?),
+            }
+        }
+    }
+
+    impl<T: TypeName> TypeName for Coded<T> {
+        fn type_name() -> &'static str {
+            T::type_name()
+        }
+
+        fn value_type() -> ValueType {
+            T::value_type()
+        }
+    }
+
+    fn error_code(e: &TError) -> &'static str {
+        match e {
+            TError::ClientTooOld { .. } => "ClientTooOld",
+            TError::UnsupportedByServer { .. } => "UnsupportedByServer",
+            TError::TransferCooldown { .. } => "TransferCooldown",
+            e if e.is_seat_limit_reached() => "SeatLimitReached",
+            e if e.is_unknown_machine() => "UnknownMachine",
+            e if e.is_expired() => "LicenseExpired",
+            e if e.is_license_not_found() => "LicenseNotFound",
+            e if e.is_unauthorized_app() => "ApplicationUnauthorized",
+            e if e.is_network() => "Network",
+            _ => "GenericFailure",
+        }
+    }
+
+    fn coded_error(env: Env, e: TError, attempts: u32) -> napi::Result<napi::Error> {
+        let mut error = env.create_error(napi::Error::from_reason(e.to_string()))?;
+        error.set_named_property("code", error_code(&e))?;
+        error.set_named_property("kind", e.kind())?;
+        error.set_named_property("attempts", attempts)?;
+        if let Some(status) = e.status() {
+            error.set_named_property("status", status.as_u16() as u32)?;
+        }
+        let remediation = e.remediation();
+        let mut hint = env.create_object()?;
+        hint.set_named_property("action", remediation.action())?;
+        match remediation {
+            Remediation::RetryAfter(after) => {
+                hint.set_named_property("retryAfter", after.as_secs_f64())?;
+            }
+            Remediation::Renew { portal_url: Some(url) } => {
+                hint.set_named_property("portalUrl", url)?;
+            }
+            _ => {}
+        }
+        error.set_named_property("remediation", hint)?;
+        if let TError::NoSeatsAvailable { seats, .. } = &e {
+            let mut held = env.create_array_with_length(seats.len())?;
+            for (i, seat) in seats.iter().enumerate() {
+                let mut info = env.create_object()?;
+                info.set_named_property("id", seat.id.to_string())?;
+                info.set_named_property("machineLabel", seat.machine_label.as_deref())?;
+                info.set_named_property("lastSeen", seat.last_seen as i64)?;
+                held.set_element(i as u32, info)?;
+            }
+            error.set_named_property("seats", held)?;
+        }
+        Ok(napi::Error::from(error.into_unknown()))
+    }
+
+    impl From<ChipaError> for napi::Error {
+        fn from(e: ChipaError) -> Self {
+            napi::Error::from_reason(e.to_string())
+        }
+    }
+
+    /// The undecoded response of a license validation.
+    #[napi(object)]
+    pub struct RawValidation {
+        /// The HTTP status code returned by the license server.
+        pub status: u32,
+        /// The decrypted response body, or `null` if the server sent none. It contains
+        /// the license token in clear, so keep it out of logs and error reports.
+        pub body: serde_json::Value,
+    }
+
+    /// Options accepted by the `LicenseClient` constructor. Every field is optional.
+    #[napi(object)]
+    #[derive(Default)]
+    pub struct ClientOptions {
+        /// Maximum number of requests the client has in flight at once. Further requests
+        /// wait for a free slot instead of failing. Defaults to no limit.
+        pub max_concurrency: Option<u32>,
+        /// Milliseconds each attempt may take, from sending the request to reading the
+        /// whole response. Defaults to no timeout.
+        pub timeout_ms: Option<u32>,
+        /// Milliseconds to wait for a connection to the license server. Defaults to no
+        /// timeout.
+        pub connect_timeout_ms: Option<u32>,
+        /// How validations and seat queries are retried. Defaults to no retries.
+        pub retries: Option<RetryOptions>,
+        /// Milliseconds a request may take with all of its retries and backoff. Past it
+        /// the request fails with the `Network` code and kind `deadline_exceeded`.
+        /// Defaults to no deadline.
+        pub operation_timeout_ms: Option<u32>,
+        /// Extra headers sent with every request. `Authorization`, `Content-Type` and the
+        /// protocol version header cannot be overridden.
+        pub headers: Option<HashMap<String, String>>,
+        /// A name for this machine, e.g. the device name the user chose, sent with
+        /// activations. Other machines see it in `SeatInfo.machineLabel`.
+        pub machine_label: Option<String>,
+    }
+
+    /// How a `LicenseClient` retries validations and seat queries. Every field is
+    /// optional.
+    #[napi(object)]
+    #[derive(Default)]
+    pub struct RetryOptions {
+        /// Attempts per request, the first one included. Defaults to 1, no retries.
+        pub max_attempts: Option<u32>,
+        /// Milliseconds to wait before the first retry, doubled for each one after.
+        /// Defaults to 200.
+        pub base_delay_ms: Option<u32>,
+        /// Milliseconds the wait before a retry never exceeds. Defaults to no cap.
+        pub max_delay_ms: Option<u32>,
+        /// Which failures are retried: "network" for an unreachable server or a timeout,
+        /// "5xx" and "429", which waits at least its `Retry-After`. Defaults to
+        /// `["network", "5xx"]`.
+        #[napi(ts_type = "Array<'network' | '5xx' | '429'>")]
+        pub retry_on: Option<Vec<String>>,
+    }
+
+    /// A retry about to be made, passed to the `onRetry` callback.
+    #[napi(object)]
+    pub struct RetryEvent {
+        /// The attempt that failed, counting from 1.
+        pub attempt: u32,
+        /// Milliseconds until the retry.
+        pub delay_ms: u32,
+        /// What failed: "network", "5xx" or "429".
+        pub error_code: String,
+    }
+
+    impl From<&client::RetryEvent> for RetryEvent {
+        fn from(event: &client::RetryEvent) -> Self {
+            Self {
+                attempt: event.attempt,
+                delay_ms: u32::try_from(event.delay.as_millis()).unwrap_or(u32::MAX),
+                error_code: event.reason.name().to_string(),
+            }
+        }
+    }
+
+    /// A single seat occupancy sample.
+    #[napi(object)]
+    pub struct SeatSample {
+        /// Unix timestamp, in seconds, at which the sample was taken.
+        pub timestamp: i64,
+        /// Number of seats in use at that time.
+        pub used: u32,
+    }
+
+    /// Seat occupancy of a license.
+    #[napi(object)]
+    pub struct SeatUsage {
+        /// Number of seats currently in use, or `null` if unknown.
+        pub used: Option<u32>,
+        /// Number of seats the license allows, or `null` if unknown.
+        pub total: Option<u32>,
+        /// Recent samples, oldest first, if the server provides them.
+        pub samples: Vec<SeatSample>,
+    }
+
+    /// A seat of a license held by this machine.
+    #[napi(object)]
+    pub struct Activation {
+        /// The token to pass to `heartbeat`.
+        pub token: String,
+        /// Milliseconds between heartbeats, or `null` if the server does not expire seats.
+        pub heartbeat_interval_ms: Option<u32>,
+        /// The id other machines see in `SeatInfo.id`, or `null` if the server does not
+        /// list seats.
+        pub activation_id: Option<String>,
+    }
+
+    impl From<ActivationToken> for Activation {
+        fn from(activation: ActivationToken) -> Self {
+            Self {
+                token: activation.token,
+                heartbeat_interval_ms: activation
+                    .heartbeat_interval
+                    .map(|interval| interval.as_millis().try_into().unwrap_or(u32::MAX)),
+                activation_id: activation.activation_id.map(|id| id.to_string()),
+            }
+        }
+    }
+
+    /// A seat held by another machine, listed in the `seats` of a "SeatLimitReached"
+    /// error.
+    #[napi(object)]
+    pub struct SeatInfo {
+        /// The activation holding the seat, to pass to `deactivateActivation`.
+        pub id: String,
+        /// The `machineLabel` the machine was activated with, if any.
+        pub machine_label: Option<String>,
+        /// Unix timestamp, in seconds, of the machine's last activation or heartbeat.
+        pub last_seen: i64,
+    }
+
+    fn machine_id(machine_id: Option<String>) -> String {
+        machine_id.unwrap_or_else(|| DeviceFingerprint::collect().activation_id())
+    }
+
+    impl From<crate::client::SeatUsage> for SeatUsage {
+        fn from(usage: crate::client::SeatUsage) -> Self {
+            Self {
+                used: usage.used,
+                total: usage.total,
+                samples: usage
+                    .samples
+                    .into_iter()
+                    .map(|sample| SeatSample {
+                        timestamp: sample.timestamp as i64,
+                        used: sample.used,
+                    })
+                    .collect(),
+            }
+        }
+    }
+
     /// A client for validating licenses against the Chipa License Server.
     ///
     /// This client provides methods to validate license keys for specific applications
@@ -45,6 +415,7 @@ mod js {
     #[napi]
     pub struct LicenseClient {
         client: TClient,
+        on_retry: Option<RetryHook>,
     }
 
     #[napi]
@@ -53,14 +424,115 @@ mod js {
         ///
         /// # Arguments
         /// * `baseUrl` - The base URL of the license server (e.g., "https://license.example.com")
+        /// * `options` - Optional `ClientOptions` with timeouts, retries, extra headers and
+        ///   `maxConcurrency`.
         ///
         /// # Returns
         /// A new `LicenseClient` instance configured with the specified base URL.
+        ///
+        /// # Throws
+        /// Throws an error if `maxConcurrency` is 0 or a header name or value is invalid.
+        ///
+        /// # Example
+        /// ```typescript
+        /// const client = new LicenseClient("https://license.example.com", {
+        ///     timeoutMs: 5000,
+        ///     retries: { maxAttempts: 4, retryOn: ["network", "5xx", "429"] },
+        ///     headers: { "X-Tenant": "acme" },
+        /// });
+        /// ```
         #[napi(constructor)]
-        pub fn new(base_url: String) -> Self {
-            Self {
-                client: TClient::new(base_url),
+        pub fn new(base_url: String, options: Option<ClientOptions>) -> napi::Result<Self> {
+            let options = options.unwrap_or_default();
+            let max_concurrency = match options.max_concurrency {
+                Some(max) => Some(NonZeroUsize::new(max as usize).ok_or_else(|| {
+                    napi::Error::from_reason("maxConcurrency must be at least 1")
+                })?),
+                None => None,
+            };
+            let millis = |ms: Option<u32>| ms.map(|ms| Duration::from_millis(ms as u64));
+            let mut client = TClient::new(base_url)
+                .set_max_concurrency(max_concurrency)
+                .set_timeout(millis(options.timeout_ms))
+                .set_connect_timeout(millis(options.connect_timeout_ms))
+                .set_operation_timeout(millis(options.operation_timeout_ms));
+            if let Some(retries) = options.retries {
+                client = client
+                    .set_retries(retries.max_attempts.unwrap_or(1).saturating_sub(1))
+                    .set_max_retry_backoff(millis(retries.max_delay_ms));
+                if let Some(backoff) = millis(retries.base_delay_ms) {
+                    client = client.set_retry_backoff(backoff);
+                }
+                if let Some(retry_on) = retries.retry_on {
+                    let retry_on = retry_on
+                        .iter()
+                        .map(|name| {
+                            RetryOn::from_name(name).ok_or_else(|| {
+                                napi::Error::from_reason(format!(
+                                    "Invalid retryOn '{}', expected {}",
+                                    name, "\"network\", \"5xx\" or \"429\""
+                                ))
+                            })
+                        })
+                        .collect::<napi::Result<Vec<_>>>()?;
+                    client = client.set_retry_on(retry_on);
+                }
             }
+            for (name, value) in options.headers.unwrap_or_default() {
+                let invalid = |e: &dyn std::fmt::Display| {
+                    napi::Error::from_reason(format!("Invalid header '{}', {}", name, e))
+                };
+                let header = HeaderName::from_bytes(name.as_bytes()).map_err(|e| invalid(&e))?;
+                let value = HeaderValue::from_str(&value).map_err(|e| invalid(&e))?;
+                client = client.set_header(header, value);
+            }
+            Ok(Self {
+                client: client.set_machine_label(options.machine_label),
+                on_retry: None,
+            })
+        }
+
+        /// Calls `callback` before every retry with a `RetryEvent`. It is queued on the
+        /// event loop, so it never holds up the request, and it does not keep the
+        /// process alive. Pass `null` to remove it.
+        ///
+        /// # Example
+        /// ```typescript
+        /// const client = new LicenseClient(url, { retries: { maxAttempts: 3 } });
+        /// client.onRetry(({ attempt, delayMs, errorCode }) => {
+        ///     console.warn(`attempt ${attempt} failed (${errorCode}), retrying in ${delayMs}ms`);
+        /// });
+        /// ```
+        #[napi(ts_args_type = "callback: ((event: RetryEvent) => void) | null")]
+        pub fn on_retry(&mut self, env: Env, callback: Option<JsFunction>) -> napi::Result<()> {
+            self.on_retry = match callback {
+                Some(callback) => {
+                    let mut callback: ThreadsafeFunction<RetryEvent, ErrorStrategy::Fatal> =
+                        callback.create_threadsafe_function(
+                            0,
+                            |ctx: ThreadSafeCallContext<RetryEvent>| Ok(vec![ctx.value]),
+                        )?;
+                    callback.unref(&env)?;
+                    let hook: RetryHook = Arc::new(move |event| {
+                        callback.call(
+                            RetryEvent::from(event),
+                            ThreadsafeFunctionCallMode::NonBlocking,
+                        );
+                    });
+                    Some(hook)
+                }
+                None => None,
+            };
+            Ok(())
+        }
+
+        /// The number of requests this client currently has in flight.
+        ///
+        /// Requests waiting for a slot under `maxConcurrency` are not counted, so this
+        /// never exceeds the limit.
+        #[napi(getter)]
+        pub fn in_flight(&self) -> u32 {
+            self.client.in_flight() as u32
         }
 
         /// Updates the base URL of the license server.
@@ -74,95 +546,1110 @@ mod js {
         pub fn set_url(&self, url: String) -> Self {
             Self {
                 client: self.client.clone().set_url(url),
+                on_retry: self.on_retry.clone(),
             }
         }
 
+        /// Releases this client's hold on its shared connection pool.
+        ///
+        /// Clients with the same base URL share one connection pool, which is freed once
+        /// no client uses it. Calling `close` releases this client's share immediately
+        /// instead of waiting for garbage collection. A closed client can still be used;
+        /// it reacquires a pool for the duration of each request.
+        #[napi]
+        pub fn close(&mut self) {
+            self.client.close();
+        }
+
         /// Validates a license key for a specific application.
         ///
         /// # Arguments
-        /// * `license` - The UUID of the license to validate
+        /// * `license` - The license to validate, either a UUID or a `CHIPA-XXXX-XXXX-XXXX-XXXX` key
         /// * `application` - The identifier of the application requesting validation
+        /// * `cachePath` - Optional path of an encrypted file keeping the last successful
+        ///   validation. It is refreshed on every successful validation and used only when
+        ///   the server cannot be reached. Requires `graceSeconds`.
+        /// * `graceSeconds` - How old, in seconds, a cached validation may be to stand in
+        ///   for the server. Requires `cachePath`.
         ///
         /// # Returns
         /// A Promise that resolves to a validation token string if successful.
         ///
         /// # Throws
         /// Throws an error if:
-        /// - The license UUID is invalid
-        /// - The server cannot be reached
-        /// - The license is invalid or expired
+        /// - The license is neither a valid UUID nor a valid key
+        /// - The server cannot be reached and there is no cached validation within the grace period
+        /// - The license is invalid or expired, even if a cached validation exists
         /// - The application is not authorized
-        #[napi]
+        /// - The license server no longer supports this client's protocol version. The
+        ///   message starts with "Client too old" and ends with the download URL when the
+        ///   server provides one
+        /// - Only one of `cachePath` and `graceSeconds` is given, or `graceSeconds` is negative
+        ///
+        /// Errors from the license server have a `code` to switch on: "LicenseExpired",
+        /// "LicenseNotFound", "ApplicationUnauthorized", "Network", "ClientTooOld",
+        /// "UnsupportedByServer" or "GenericFailure", and a numeric `status` when the
+        /// server answered. Seat activations add "SeatLimitReached" and "UnknownMachine",
+        /// and transfers "TransferCooldown".
+        /// Every error, a malformed license included, also has the finer `kind` used by
+        /// the Rust and Python libraries, e.g. "invalid_license", and a `remediation`
+        /// whose `action` is e.g. "renew", with `portalUrl`, or "retry_after", with
+        /// `retryAfter` in seconds. `attempts` counts the requests made, retries included.
+        ///
+        /// # Example
+        /// ```typescript
+        /// // Keep working for up to a day without network.
+        /// const token = await client.validateLicense(license, "my-app", "./license.chipa", 24 * 3600);
+        ///
+        /// try {
+        ///     await client.validateLicense(license, "my-app");
+        /// } catch (error) {
+        ///     switch (error.code) {
+        ///         case "LicenseExpired": showRenewDialog(); break;
+        ///         case "Network": showOfflineBanner(); break;
+        ///         default: throw error;
+        ///     }
+        /// }
+        /// ```
+        #[napi(ts_return_type = "Promise<string>")]
         pub async fn validate_license(
             &self,
             license: String,
             application: String,
-        ) -> napi::Result<String> {
-            self.client
-                .validate_license(
-                    Uuid::parse_str(&license).map_err(TError::from)?,
-                    application,
-                )
-                .await
-                .map_err(|e| e.into())
+            cache_path: Option<String>,
+            grace_seconds: Option<f64>,
+        ) -> napi::Result<Coded<String>> {
+            let (client, attempts) = Attempts::track(self);
+            let license = match license.parse::<LicenseId>() {
+                Ok(license) => license,
+                Err(e) => return Ok(attempts.coded(Err(e))),
+            };
+            match (cache_path, grace_seconds) {
+                (None, None) => {
+                    Ok(attempts.coded(client.validate_license(license, application).await))
+                }
+                (Some(cache_path), Some(grace)) => {
+                    let grace = Duration::try_from_secs_f64(grace).map_err(|e| {
+                        napi::Error::from_reason(format!("Invalid graceSeconds, {}", e))
+                    })?;
+                    Ok(attempts.coded(
+                        client
+                            .validate_license_cached(license, application, grace, &cache_path)
+                            .await,
+                    ))
+                }
+                _ => Err(napi::Error::from_reason(
+                    "cachePath and graceSeconds must be given together",
+                )),
+            }
+        }
+
+        /// Validates a license key and returns the server's response without interpreting it.
+        ///
+        /// Unlike `validateLicense`, this does not fail on an error status or on fields
+        /// the client does not know about, which makes it possible to read experimental
+        /// response fields before the typed API supports them.
+        ///
+        /// # Arguments
+        /// * `license` - The license to validate, either a UUID or a `CHIPA-XXXX-XXXX-XXXX-XXXX` key
+        /// * `application` - The identifier of the application requesting validation
+        ///
+        /// # Returns
+        /// A Promise that resolves to `{ status, body }`, where `body` is the decrypted
+        /// JSON response or `null` if the server sent an empty body.
+        ///
+        /// # Throws
+        /// Throws an error if the license is malformed, the server cannot be reached
+        /// (`code` "Network"), or the response body cannot be decrypted or is not JSON.
+        #[napi(ts_return_type = "Promise<RawValidation>")]
+        pub async fn validate_license_raw(
+            &self,
+            license: String,
+            application: String,
+        ) -> napi::Result<Coded<RawValidation>> {
+            let (client, attempts) = Attempts::track(self);
+            let raw = match license.parse::<LicenseId>() {
+                Ok(license) => client.validate_license_raw(license, application).await,
+                Err(e) => Err(e),
+            };
+            Ok(attempts.coded(raw.map(|raw| RawValidation {
+                status: raw.status.as_u16() as u32,
+                body: raw.body,
+            })))
+        }
+
+        /// Fetches the seat occupancy of a license.
+        ///
+        /// # Arguments
+        /// * `license` - The license to query, either a UUID or a `CHIPA-XXXX-XXXX-XXXX-XXXX` key
+        /// * `lenient` - When `true`, servers that do not support seat reporting yield an
+        ///   unknown usage (`used` and `total` are `null`) instead of an error
+        ///
+        /// # Returns
+        /// A Promise that resolves to the current `SeatUsage`.
+        ///
+        /// # Throws
+        /// Throws an error if the license is malformed, the server cannot be reached,
+        /// or the server rejects the request. The error has the same `code` and
+        /// `status` as those of `validateLicense`.
+        #[napi(ts_return_type = "Promise<SeatUsage>")]
+        pub async fn seat_usage(
+            &self,
+            license: String,
+            lenient: Option<bool>,
+        ) -> napi::Result<Coded<SeatUsage>> {
+            let (client, attempts) = Attempts::track(self);
+            let client = client.set_lenient(lenient.unwrap_or(false));
+            let usage = match license.parse::<LicenseId>() {
+                Ok(license) => client.seat_usage(license).await,
+                Err(e) => Err(e),
+            };
+            Ok(attempts.coded(usage.map(SeatUsage::from)))
+        }
+
+        /// Takes a seat of a seat-limited license for this machine.
+        ///
+        /// Activating a machine that already holds a seat refreshes its token.
+        ///
+        /// # Arguments
+        /// * `license` - The license to activate, either a UUID or a `CHIPA-XXXX-XXXX-XXXX-XXXX` key
+        /// * `application` - The identifier of the application requesting the seat
+        /// * `machineId` - Optional identifier of the machine. Defaults to a hash of the
+        ///   machine id or volume serial, which never leaves the machine in clear
+        ///
+        /// # Returns
+        /// A Promise that resolves to the `Activation`, whose `token` is sent to
+        /// `heartbeat` every `heartbeatIntervalMs`.
+        ///
+        /// # Throws
+        /// Throws an error with `code` "SeatLimitReached" if every seat is held by other
+        /// machines, "UnsupportedByServer" if the server has no seat-limited licenses,
+        /// or one of the codes of `validateLicense`. A "SeatLimitReached" error lists the
+        /// seats in `seats`, an array of `SeatInfo` that is empty if the server does not
+        /// send them.
+        ///
+        /// # Example
+        /// ```typescript
+        /// const seat = await client.activateLicense(license, "my-app");
+        /// let token = seat.token;
+        /// setInterval(async () => {
+        ///     token = (await client.heartbeat(license, "my-app", token)).token;
+        /// }, seat.heartbeatIntervalMs ?? 300_000);
+        /// ```
+        #[napi(ts_return_type = "Promise<Activation>")]
+        pub async fn activate_license(
+            &self,
+            license: String,
+            application: String,
+            machine_id: Option<String>,
+        ) -> napi::Result<Coded<Activation>> {
+            let (client, attempts) = Attempts::track(self);
+            let activation = match license.parse::<LicenseId>() {
+                Ok(license) => {
+                    let machine_id = self::machine_id(machine_id);
+                    client.activate_license(license, application, machine_id).await
+                }
+                Err(e) => Err(e),
+            };
+            Ok(attempts.coded(activation.map(Activation::from)))
+        }
+
+        /// Frees the seat held by this machine, e.g. when the user signs out.
+        ///
+        /// # Arguments
+        /// * `license` - The activated license, either a UUID or a `CHIPA-XXXX-XXXX-XXXX-XXXX` key
+        /// * `application` - The identifier of the application holding the seat
+        /// * `machineId` - Optional identifier of the machine, the same as given to
+        ///   `activateLicense`
+        ///
+        /// # Throws
+        /// Throws an error with `code` "UnknownMachine" if the machine holds no seat, or
+        /// one of the codes of `activateLicense`.
+        #[napi(ts_return_type = "Promise<void>")]
+        pub async fn deactivate_license(
+            &self,
+            license: String,
+            application: String,
+            machine_id: Option<String>,
+        ) -> napi::Result<Coded<()>> {
+            let (client, attempts) = Attempts::track(self);
+            let deactivation = match license.parse::<LicenseId>() {
+                Ok(license) => {
+                    let machine_id = self::machine_id(machine_id);
+                    client.deactivate_license(license, application, machine_id).await
+                }
+                Err(e) => Err(e),
+            };
+            Ok(attempts.coded(deactivation))
+        }
+
+        /// Frees the seat of another machine, e.g. a stale one listed in the `seats` of a
+        /// "SeatLimitReached" error.
+        ///
+        /// # Arguments
+        /// * `license` - The activated license, either a UUID or a `CHIPA-XXXX-XXXX-XXXX-XXXX` key
+        /// * `application` - The identifier of the application holding the seat
+        /// * `activationId` - The `id` of the seat's `SeatInfo`
+        ///
+        /// # Throws
+        /// Throws an error with `code` "UnknownMachine" if the seat is already free, or
+        /// one of the codes of `activateLicense`.
+        ///
+        /// # Example
+        /// ```typescript
+        /// try {
+        ///     await client.activateLicense(license, "my-app");
+        /// } catch (error) {
+        ///     if (error.code !== "SeatLimitReached") throw error;
+        ///     const stale = await pickSeatToFree(error.seats);
+        ///     await client.deactivateActivation(license, "my-app", stale.id);
+        ///     await client.activateLicense(license, "my-app");
+        /// }
+        /// ```
+        #[napi(ts_return_type = "Promise<void>")]
+        pub async fn deactivate_activation(
+            &self,
+            license: String,
+            application: String,
+            activation_id: String,
+        ) -> napi::Result<Coded<()>> {
+            let (client, attempts) = Attempts::track(self);
+            let activation_id = activation_id
+                .parse::<Uuid>()
+                .map_err(|e| napi::Error::from_reason(format!("Invalid activationId, {}", e)))?;
+            let deactivation = match license.parse::<LicenseId>() {
+                Ok(license) => {
+                    client.deactivate_activation(license, application, activation_id).await
+                }
+                Err(e) => Err(e),
+            };
+            Ok(attempts.coded(deactivation))
+        }
+
+        /// Moves the seat of another machine to this one in a single server call, so there
+        /// is no moment when neither machine holds it, e.g. when the user replaces a
+        /// laptop. The old machine's token stops working.
+        ///
+        /// # Arguments
+        /// * `license` - The activated license, either a UUID or a `CHIPA-XXXX-XXXX-XXXX-XXXX` key
+        /// * `application` - The identifier of the application holding the seat
+        /// * `fromActivation` - The `activationId` of the seat to move
+        /// * `machineId` - Optional identifier of this machine, as for `activateLicense`
+        /// * `force` - When `true`, asks the server to move the seat even though the old
+        ///   machine has not released it, e.g. because it is broken. Servers allow that
+        ///   only so often
+        ///
+        /// # Returns
+        /// A Promise that resolves to this machine's `Activation`.
+        ///
+        /// # Throws
+        /// Throws an error with `code` "TransferCooldown" if a forced transfer was made
+        /// too recently, with `remediation.retryAfter` in seconds when the server says,
+        /// "UnknownMachine" if the seat to move is already free, or one of the codes of
+        /// `activateLicense`.
+        #[napi(ts_return_type = "Promise<Activation>")]
+        pub async fn transfer_license(
+            &self,
+            license: String,
+            application: String,
+            from_activation: String,
+            machine_id: Option<String>,
+            force: Option<bool>,
+        ) -> napi::Result<Coded<Activation>> {
+            let (client, attempts) = Attempts::track(self);
+            let from_activation = from_activation
+                .parse::<Uuid>()
+                .map_err(|e| napi::Error::from_reason(format!("Invalid fromActivation, {}", e)))?;
+            let activation = match license.parse::<LicenseId>() {
+                Ok(license) => {
+                    let machine_id = self::machine_id(machine_id);
+                    let force = force.unwrap_or(false);
+                    client
+                        .transfer_license(license, application, from_activation, machine_id, force)
+                        .await
+                }
+                Err(e) => Err(e),
+            };
+            Ok(attempts.coded(activation.map(Activation::from)))
+        }
+
+        /// Confirms that this machine still holds its seat and refreshes the token.
+        ///
+        /// # Arguments
+        /// * `license` - The activated license, either a UUID or a `CHIPA-XXXX-XXXX-XXXX-XXXX` key
+        /// * `application` - The identifier of the application holding the seat
+        /// * `token` - The token of the last activation or heartbeat
+        /// * `lenient` - When `true`, servers without seat-limited licenses hand back
+        ///   `token` instead of an error
+        ///
+        /// # Returns
+        /// A Promise that resolves to the refreshed `Activation`.
+        ///
+        /// # Throws
+        /// Throws an error with `code` "UnknownMachine" once the server has freed the
+        /// seat, or one of the codes of `activateLicense`.
+        #[napi(ts_return_type = "Promise<Activation>")]
+        pub async fn heartbeat(
+            &self,
+            license: String,
+            application: String,
+            token: String,
+            lenient: Option<bool>,
+        ) -> napi::Result<Coded<Activation>> {
+            let (client, attempts) = Attempts::track(self);
+            let client = client.set_lenient(lenient.unwrap_or(false));
+            let activation = match license.parse::<LicenseId>() {
+                Ok(license) => client.heartbeat(license, application, token).await,
+                Err(e) => Err(e),
+            };
+            Ok(attempts.coded(activation.map(Activation::from)))
+        }
+    }
+
+    fn json_data(env: &Env, data: JsUnknown) -> napi::Result<Value> {
+        env.from_js_value(data).map_err(|e| {
+            napi::Error::new(
+                Status::InvalidArg,
+                format!("ChipaFile data must be JSON-compatible, {}", e.reason),
+            )
+        })
+    }
+
+    /// An encrypted `.chipa` file holding any JSON-compatible value.
+    ///
+    /// The same files are read and written by the Rust and Python libraries, so
+    /// configuration encrypted by one can be shipped to the others.
+    ///
+    /// `load` and `save` block the event loop while the file is read or written and
+    /// decrypted or encrypted. Use `loadAsync` and `saveAsync` for large files or on a
+    /// server, they do the work on the libuv thread pool.
+    ///
+    /// # Example
+    /// ```typescript
+    /// const file = new ChipaFile({ strategy: "momentum", risk: 0.02 });
+    /// file.save("strategy.chipa", key);
+    ///
+    /// const strategy = ChipaFile.load("strategy.chipa", key).read();
+    /// console.log(strategy.risk);
+    /// ```
+    #[napi]
+    pub struct ChipaFile {
+        file: encryption::ChipaFile,
+    }
+
+    #[napi]
+    impl ChipaFile {
+        /// Creates a file holding `data`, which defaults to `null`.
+        ///
+        /// # Throws
+        /// Throws an error if `data` is not JSON-compatible, e.g. a function or a symbol.
+        #[napi(constructor)]
+        pub fn new(env: Env, data: Option<JsUnknown>) -> napi::Result<Self> {
+            let data = match data {
+                Some(data) => json_data(&env, data)?,
+                None => Value::Null,
+            };
+            Ok(Self {
+                file: encryption::ChipaFile::new(Version::LATEST, &data)?,
+            })
+        }
+
+        /// Reads and decrypts the file at `path` with `key`.
+        ///
+        /// # Throws
+        /// Throws an error if the file cannot be read, or with a message starting with
+        /// "Decryption error" if `key` is not the key it was saved with.
+        #[napi(factory)]
+        pub fn load(path: String, key: String) -> napi::Result<Self> {
+            Ok(Self {
+                file: encryption::ChipaFile::load(&path, &key)?,
+            })
+        }
+
+        /// Like `load`, without blocking the event loop.
+        #[napi(ts_return_type = "Promise<ChipaFile>")]
+        pub fn load_async(path: String, key: String) -> AsyncTask<LoadFile> {
+            AsyncTask::new(LoadFile { path, key })
+        }
+
+        /// Encrypts the file with `key` and writes it to `path`, replacing any existing file.
+        #[napi]
+        pub fn save(&self, path: String, key: String) -> napi::Result<()> {
+            Ok(self.file.save(&path, &key)?)
+        }
+
+        /// Like `save`, without blocking the event loop. Later changes to the file are
+        /// not part of the write.
+        #[napi(ts_return_type = "Promise<void>")]
+        pub fn save_async(&self, path: String, key: String) -> AsyncTask<SaveFile> {
+            AsyncTask::new(SaveFile {
+                file: self.file.clone(),
+                path,
+                key,
+            })
+        }
+
+        /// Returns the value held by the file as plain JavaScript objects.
+        #[napi]
+        pub fn read(&self) -> napi::Result<Value> {
+            Ok(self.file.read()?)
+        }
+
+        /// Replaces the value held by the file. Call `save` to persist it.
+        ///
+        /// # Throws
+        /// Throws an error if `data` is not JSON-compatible, e.g. a function or a symbol.
+        #[napi]
+        pub fn write(&mut self, env: Env, data: JsUnknown) -> napi::Result<()> {
+            Ok(self.file.write(&json_data(&env, data)?)?)
+        }
+    }
+
+    pub struct LoadFile {
+        path: String,
+        key: String,
+    }
+
+    impl Task for LoadFile {
+        type Output = encryption::ChipaFile;
+        type JsValue = ChipaFile;
+
+        fn compute(&mut self) -> napi::Result<Self::Output> {
+            Ok(encryption::ChipaFile::load(&self.path, &self.key)?)
+        }
+
+        fn resolve(&mut self, _env: Env, file: Self::Output) -> napi::Result<Self::JsValue> {
+            Ok(ChipaFile { file })
+        }
+    }
+
+    pub struct SaveFile {
+        file: encryption::ChipaFile,
+        path: String,
+        key: String,
+    }
+
+    impl Task for SaveFile {
+        type Output = ();
+        type JsValue = ();
+
+        fn compute(&mut self) -> napi::Result<()> {
+            Ok(self.file.save(&self.path, &self.key)?)
+        }
+
+        fn resolve(&mut self, _env: Env, _output: ()) -> napi::Result<()> {
+            Ok(())
         }
     }
 }
 
 #[cfg(feature = "py")]
 pub mod py {
-    use crate::client::{TClient, TError};
-    use pyo3::{exceptions::PyException, prelude::*};
+    use std::{
+        collections::HashMap,
+        future::Future,
+        num::NonZeroUsize,
+        pin::Pin,
+        sync::Mutex,
+        time::Duration,
+    };
+
+    use crate::{
+        client::{self, ActivationToken, Remediation, TClient, TError},
+        encryption::{self, ChipaError},
+        fingerprint::DeviceFingerprint,
+        license::LicenseId,
+        limits::ValueLimits,
+        version::Version,
+    };
+    use http::header::{HeaderName, HeaderValue};
+    use pyo3::{
+        exceptions::{PyException, PyRuntimeError, PyTypeError, PyValueError},
+        prelude::*,
+        types::{PyDict, PyList, PyTuple},
+    };
+    use pyo3_async_runtimes::{generic, TaskLocals};
     use pyo3_stub_gen::{
         create_exception, define_stub_info_gatherer,
-        derive::{gen_stub_pyclass, gen_stub_pymethods},
+        derive::{gen_stub_pyclass, gen_stub_pyfunction, gen_stub_pymethods},
     };
-    // use pythonize::pythonize;
-    // use serde_json::Value;
+    use pythonize::{depythonize_bound, pythonize};
+    use serde_json::Value;
+    use tokio::runtime::Handle;
     use uuid::Uuid;
 
     pub struct ValidationError {
         msg: String,
+        kind: &'static str,
+        remediation: Remediation,
+        status: Option<u16>,
+        class: ErrorClass,
+    }
+
+    enum ErrorClass {
+        Generic,
+        Expired,
+        NotFound,
+        UnauthorizedApp,
+        SeatLimitReached(Vec<SeatInfo>),
+        UnknownMachine,
+        TransferCooldown,
+        DeadlineExceeded,
+        ClientTooOld(Upgrade),
+    }
+
+    struct Upgrade {
+        server_min_version: u16,
+        client_version: u16,
+        download_url: Option<String>,
+    }
+
+    impl From<TError> for ValidationError {
+        fn from(e: TError) -> Self {
+            let class = match &e {
+                TError::ClientTooOld {
+                    server_min_version,
+                    client_version,
+                    download_url,
+                } => ErrorClass::ClientTooOld(Upgrade {
+                    server_min_version: *server_min_version,
+                    client_version: *client_version,
+                    download_url: download_url.clone(),
+                }),
+                TError::DeadlineExceeded { .. } => ErrorClass::DeadlineExceeded,
+                TError::TransferCooldown { .. } => ErrorClass::TransferCooldown,
+                TError::NoSeatsAvailable { seats, .. } => {
+                    ErrorClass::SeatLimitReached(seats.iter().map(SeatInfo::from).collect())
+                }
+                e if e.is_seat_limit_reached() => ErrorClass::SeatLimitReached(Vec::new()),
+                e if e.is_unknown_machine() => ErrorClass::UnknownMachine,
+                e if e.is_expired() => ErrorClass::Expired,
+                e if e.is_license_not_found() => ErrorClass::NotFound,
+                e if e.is_unauthorized_app() => ErrorClass::UnauthorizedApp,
+                _ => ErrorClass::Generic,
+            };
+            Self {
+                msg: e.to_string(),
+                kind: e.kind(),
+                remediation: e.remediation(),
+                status: e.status().map(|status| status.as_u16()),
+                class,
+            }
+        }
+    }
+
+    impl From<ValidationError> for PyErr {
+        fn from(e: ValidationError) -> Self {
+            let err = match e.class {
+                ErrorClass::Generic => PyErr::new::<LicenseValidationError, _>(e.msg),
+                ErrorClass::Expired => PyErr::new::<LicenseExpiredError, _>(e.msg),
+                ErrorClass::NotFound => PyErr::new::<LicenseNotFoundError, _>(e.msg),
+                ErrorClass::UnauthorizedApp => {
+                    PyErr::new::<ApplicationUnauthorizedError, _>(e.msg)
+                }
+                ErrorClass::SeatLimitReached(seats) => {
+                    let err = PyErr::new::<SeatLimitReachedError, _>(e.msg);
+                    Python::with_gil(|py| {
+                        let _ = err.value_bound(py).setattr("seats", seats.into_py(py));
+                    });
+                    err
+                }
+                ErrorClass::UnknownMachine => PyErr::new::<UnknownMachineError, _>(e.msg),
+                ErrorClass::TransferCooldown => PyErr::new::<TransferCooldownError, _>(e.msg),
+                ErrorClass::DeadlineExceeded => PyErr::new::<ValidationTimeoutError, _>(e.msg),
+                ErrorClass::ClientTooOld(upgrade) => {
+                    let err = PyErr::new::<ClientTooOldError, _>(e.msg);
+                    Python::with_gil(|py| {
+                        let value = err.value_bound(py);
+                        let _ = value.setattr("server_min_version", upgrade.server_min_version);
+                        let _ = value.setattr("client_version", upgrade.client_version);
+                        let _ = value.setattr("download_url", upgrade.download_url);
+                    });
+                    err
+                }
+            };
+            Python::with_gil(|py| {
+                let value = err.value_bound(py);
+                let _ = value.setattr("status", e.status);
+                let _ = value.setattr("kind", e.kind);
+            });
+            with_remediation(err, &e.remediation)
+        }
+    }
+
+    // The runtime of the process that built it. A forked child inherits the parent's but
+    // none of its worker threads, so it builds its own; the parent's is leaked, like the
+    // rest of the memory inherited by fork.
+    static RUNTIME: Mutex<Option<(u32, Handle)>> = Mutex::new(None);
+    static WORKER_THREADS: Mutex<Option<usize>> = Mutex::new(None);
+
+    tokio::task_local! {
+        static TASK_LOCALS: TaskLocals;
+    }
+
+    fn ensure_runtime() -> PyResult<Handle> {
+        let pid = std::process::id();
+        let mut runtime = RUNTIME.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((started, handle)) = &*runtime {
+            if *started == pid {
+                return Ok(handle.clone());
+            }
+        }
+        let mut builder = tokio::runtime::Builder::new_multi_thread();
+        builder.enable_all();
+        if let Some(threads) = *WORKER_THREADS.lock().unwrap_or_else(|e| e.into_inner()) {
+            builder.worker_threads(threads);
+        }
+        let built = builder.build().map_err(|e| {
+            PyRuntimeError::new_err(format!("Couldn't start the async runtime, {}", e))
+        })?;
+        let handle = Box::leak(Box::new(built)).handle().clone();
+        *runtime = Some((pid, handle.clone()));
+        Ok(handle)
+    }
+
+    // Runs futures on this process's runtime instead of the one pyo3_async_runtimes keeps,
+    // which is set once and would outlive a fork.
+    struct ForkSafeRuntime;
+
+    impl generic::Runtime for ForkSafeRuntime {
+        type JoinError = tokio::task::JoinError;
+        type JoinHandle = tokio::task::JoinHandle<()>;
+
+        fn spawn<F>(fut: F) -> Self::JoinHandle
+        where
+            F: Future<Output = ()> + Send + 'static,
+        {
+            // `spawn` starts the runtime before handing anything to pyo3_async_runtimes.
+            ensure_runtime()
+                .expect("the async runtime is started before spawning")
+                .spawn(fut)
+        }
+    }
+
+    impl generic::ContextExt for ForkSafeRuntime {
+        fn scope<F, R>(locals: TaskLocals, fut: F) -> Pin<Box<dyn Future<Output = R> + Send>>
+        where
+            F: Future<Output = R> + Send + 'static,
+        {
+            Box::pin(TASK_LOCALS.scope(locals, fut))
+        }
+
+        fn get_task_locals() -> Option<TaskLocals> {
+            TASK_LOCALS.try_with(TaskLocals::clone).ok()
+        }
+    }
+
+    fn spawn<F, T>(py: Python<'_>, fut: F) -> PyResult<Bound<'_, PyAny>>
+    where
+        F: Future<Output = PyResult<T>> + Send + 'static,
+        T: IntoPy<PyObject>,
+    {
+        ensure_runtime()?;
+        generic::future_into_py::<ForkSafeRuntime, _, _>(py, fut)
+    }
+
+    /// Configures the async runtime used by every LicenseClient in this process.
+    ///
+    /// The runtime is created lazily on the first request made in each process, and a
+    /// forked child (e.g. a gunicorn worker with `preload_app`) starts its own even if
+    /// the parent already made requests. Clients themselves are not shared: create
+    /// them after forking. Call this before the first request of a process, typically
+    /// at import time or in a post-fork hook; children inherit the setting.
+    ///
+    /// Args:
+    ///     worker_threads (int, optional): Number of runtime worker threads.
+    ///         Defaults to the number of CPU cores.
+    ///
+    /// Raises:
+    ///     ValueError: If `worker_threads` is 0
+    ///     RuntimeError: If this process has already started the runtime
+    ///
+    /// Example:
+    ///     ```python
+    ///     import chipa_license_validator
+    ///
+    ///     chipa_license_validator.configure_runtime(worker_threads=2)
+    ///     ```
+    #[gen_stub_pyfunction]
+    #[pyfunction]
+    #[pyo3(signature = (worker_threads=None))]
+    pub fn configure_runtime(worker_threads: Option<usize>) -> PyResult<()> {
+        if worker_threads == Some(0) {
+            return Err(PyValueError::new_err("worker_threads must be at least 1"));
+        }
+        let runtime = RUNTIME.lock().unwrap_or_else(|e| e.into_inner());
+        if runtime.as_ref().is_some_and(|(pid, _)| *pid == std::process::id()) {
+            return Err(PyRuntimeError::new_err(
+                "The async runtime has already been started, call configure_runtime before the first request",
+            ));
+        }
+        *WORKER_THREADS.lock().unwrap_or_else(|e| e.into_inner()) = worker_threads;
+        Ok(())
+    }
+
+    fn parse_seconds(name: &str, seconds: Option<f64>) -> PyResult<Option<Duration>> {
+        seconds
+            .map(Duration::try_from_secs_f64)
+            .transpose()
+            .map_err(|e| PyValueError::new_err(format!("Invalid {}, {}", name, e)))
+    }
+
+    async fn with_timeout<T>(
+        timeout: Option<Duration>,
+        validation: impl Future<Output = Result<T, TError>>,
+    ) -> PyResult<T> {
+        let result = match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, validation)
+                .await
+                .map_err(|_| {
+                    with_remediation(
+                        PyErr::new::<ValidationTimeoutError, _>(format!(
+                            "License validation timed out after {:?}",
+                            timeout
+                        )),
+                        &Remediation::CheckInternet,
+                    )
+                })?,
+            None => validation.await,
+        };
+        Ok(result.map_err(ValidationError::from)?)
+    }
+
+    fn with_remediation(err: PyErr, remediation: &Remediation) -> PyErr {
+        Python::with_gil(|py| {
+            let dict = PyDict::new_bound(py);
+            let _ = dict.set_item("action", remediation.action());
+            match remediation {
+                Remediation::RetryAfter(after) => {
+                    let _ = dict.set_item("retry_after", after.as_secs_f64());
+                }
+                Remediation::Renew { portal_url } => {
+                    let _ = dict.set_item("portal_url", portal_url);
+                }
+                _ => {}
+            }
+            let _ = err.value_bound(py).setattr("remediation", dict);
+        });
+        err
+    }
+
+    // / Exception raised when license validation fails.
+    // /
+    // / This exception is raised when there are issues validating a license, which can
+    // / include:
+    // /
+    // / - Invalid license UUID format
+    // / - Network connectivity issues
+    // / - Server-side validation failures
+    // / - Expired licenses
+    // / - Unauthorized applications
+    // /
+    // / Expired, unknown and unauthorized licenses raise the `LicenseExpiredError`,
+    // / `LicenseNotFoundError` and `ApplicationUnauthorizedError` subclasses, seat
+    // / activations `SeatLimitReachedError` and `UnknownMachineError`, transfers
+    // / `TransferCooldownError`. Every
+    // / instance has a `status` attribute with the server's HTTP status, or None if
+    // / the server was not reached.
+    // /
+    // / # Example
+    // / ```python
+    // / from chipa_license_validator import LicenseClient, LicenseValidationError
+    // /
+    // / try:
+    // /     client = LicenseClient("https://license.example.com")
+    // /     token = await client.validate_license(
+    // /         "550e8400-e29b-41d4-a716-446655440000",
+    // /         "my-app"
+    // /     )
+    // / except LicenseValidationError as e:
+    // /     print(f"Validation failed: {str(e)}")
+    // /     # Handle specific validation errors
+    // / ```
+    create_exception!(chipa_license_validator, LicenseValidationError, PyException);
+
+    fn chipa_error(e: ChipaError) -> PyErr {
+        let err = PyErr::new::<ChipaFileError, _>(e.to_string());
+        Python::with_gil(|py| {
+            let _ = err.value_bound(py).setattr("kind", e.kind());
+        });
+        err
+    }
+
+    // How many nodes `to_python` converts between checks for Ctrl-C.
+    const SIGNAL_CHECK_NODES: usize = 64 * 1024;
+
+    fn value_limits(max_depth: Option<usize>, max_nodes: Option<usize>) -> ValueLimits {
+        let defaults = ValueLimits::default();
+        ValueLimits {
+            max_depth: max_depth.unwrap_or(defaults.max_depth),
+            max_nodes: max_nodes.unwrap_or(defaults.max_nodes),
+        }
+    }
+
+    // Only ever given values `read_bounded` produced, so the recursion is bounded by
+    // `max_depth`.
+    fn to_python(py: Python<'_>, value: &Value, nodes: &mut usize) -> PyResult<PyObject> {
+        *nodes += 1;
+        if nodes.is_multiple_of(SIGNAL_CHECK_NODES) {
+            py.check_signals()?;
+        }
+        Ok(match value {
+            Value::Null => py.None(),
+            Value::Bool(b) => b.into_py(py),
+            Value::Number(n) => match (n.as_i64(), n.as_u64()) {
+                (Some(i), _) => i.into_py(py),
+                (None, Some(u)) => u.into_py(py),
+                (None, None) => n.as_f64().into_py(py),
+            },
+            Value::String(s) => s.into_py(py),
+            Value::Array(values) => {
+                let list = PyList::empty_bound(py);
+                for value in values {
+                    list.append(to_python(py, value, nodes)?)?;
+                }
+                list.into_py(py)
+            }
+            Value::Object(map) => {
+                let dict = PyDict::new_bound(py);
+                for (key, value) in map {
+                    dict.set_item(key, to_python(py, value, nodes)?)?;
+                }
+                dict.into_py(py)
+            }
+        })
+    }
+
+    // Walks `data` without recursing before pythonize does, so a value that nests too
+    // deeply fails with the limit it broke instead of overflowing the stack.
+    fn check_limits(data: &Bound<'_, PyAny>, limits: ValueLimits) -> PyResult<()> {
+        let exceeded = |limit, max| chipa_error(ChipaError::LimitExceeded { limit, max });
+        let mut pending = vec![(data.clone(), 0)];
+        let mut nodes = 0;
+        while let Some((item, depth)) = pending.pop() {
+            nodes += 1;
+            let children: Vec<_> = if let Ok(list) = item.downcast::<PyList>() {
+                list.iter().collect()
+            } else if let Ok(tuple) = item.downcast::<PyTuple>() {
+                tuple.iter().collect()
+            } else if let Ok(dict) = item.downcast::<PyDict>() {
+                nodes += dict.len();
+                dict.values().iter().collect()
+            } else {
+                continue;
+            };
+            if nodes > limits.max_nodes {
+                return Err(exceeded("max_nodes", limits.max_nodes));
+            }
+            if depth >= limits.max_depth {
+                return Err(exceeded("max_depth", limits.max_depth));
+            }
+            pending.extend(children.into_iter().map(|child| (child, depth + 1)));
+        }
+        match nodes > limits.max_nodes {
+            true => Err(exceeded("max_nodes", limits.max_nodes)),
+            false => Ok(()),
+        }
+    }
+
+    fn json_data(data: Bound<'_, PyAny>) -> PyResult<Value> {
+        check_limits(&data, ValueLimits::default())?;
+        depythonize_bound(data).map_err(|e| {
+            PyTypeError::new_err(format!("ChipaFile data must be JSON-compatible, {}", e))
+        })
+    }
+
+    // / Exception raised when the license server no longer supports this client's
+    // / protocol version. Subclass of `LicenseValidationError`; `server_min_version`,
+    // / `client_version` and `download_url` (None if the server gave none) tell the
+    // / application what to ask the user to install.
+    create_exception!(
+        chipa_license_validator,
+        ClientTooOldError,
+        LicenseValidationError
+    );
+
+    // / Exception raised when the license has expired or its payment is overdue.
+    // / Subclass of `LicenseValidationError`; its `remediation` is "renew" and
+    // / usually carries the `portal_url` where the user can renew.
+    create_exception!(
+        chipa_license_validator,
+        LicenseExpiredError,
+        LicenseValidationError
+    );
+
+    // / Exception raised when the license server does not know the license. Subclass
+    // / of `LicenseValidationError`. A wrong server URL raises it for every license.
+    create_exception!(
+        chipa_license_validator,
+        LicenseNotFoundError,
+        LicenseValidationError
+    );
+
+    // / Exception raised when the license is valid but does not cover this
+    // / application. Subclass of `LicenseValidationError`.
+    create_exception!(
+        chipa_license_validator,
+        ApplicationUnauthorizedError,
+        LicenseValidationError
+    );
+
+    // / Exception raised when every seat of the license is held by other machines.
+    // / Subclass of `LicenseValidationError`; its `seats` attribute lists them as
+    // / `SeatInfo`, empty if the server does not send them. Free a stale one with
+    // / `LicenseClient.deactivate_activation`.
+    create_exception!(
+        chipa_license_validator,
+        SeatLimitReachedError,
+        LicenseValidationError
+    );
+
+    // / Exception raised when the machine holds no seat of the license, e.g. after
+    // / heartbeats stopped for too long. Subclass of `LicenseValidationError`;
+    // / activate the machine again.
+    create_exception!(
+        chipa_license_validator,
+        UnknownMachineError,
+        LicenseValidationError
+    );
+
+    // / Exception raised when a forced transfer is refused because the license was
+    // / force-transferred too recently. Subclass of `LicenseValidationError`; its
+    // / `remediation` gives the seconds to wait in `retry_after` when the server says.
+    create_exception!(
+        chipa_license_validator,
+        TransferCooldownError,
+        LicenseValidationError
+    );
+
+    // / Exception raised when a `.chipa` file cannot be read, decrypted or written.
+    // / Its `kind` attribute names the failure, e.g. "decryption" for a wrong key or
+    // / "file_creation" for an I/O error.
+    create_exception!(chipa_license_validator, ChipaFileError, PyException);
+
+    // / Exception raised when a license validation does not finish within the
+    // / `timeout` passed to the call, or the client's retries run past its operation
+    // / deadline. Subclass of `LicenseValidationError`.
+    create_exception!(
+        chipa_license_validator,
+        ValidationTimeoutError,
+        LicenseValidationError
+    );
+
+    /// A single seat occupancy sample.
+    ///
+    /// Attributes:
+    ///     timestamp (int): Unix timestamp, in seconds, at which the sample was taken
+    ///     used (int): Number of seats in use at that time
+    #[pyclass]
+    #[gen_stub_pyclass]
+    #[derive(Clone)]
+    pub struct SeatSample {
+        #[pyo3(get)]
+        timestamp: u64,
+        #[pyo3(get)]
+        used: u32,
+    }
+
+    /// Seat occupancy of a license.
+    ///
+    /// Attributes:
+    ///     used (int | None): Number of seats currently in use, or None if unknown
+    ///     total (int | None): Number of seats the license allows, or None if unknown
+    ///     samples (list[SeatSample]): Recent samples, oldest first, if the server provides them
+    #[pyclass]
+    #[gen_stub_pyclass]
+    pub struct SeatUsage {
+        #[pyo3(get)]
+        used: Option<u32>,
+        #[pyo3(get)]
+        total: Option<u32>,
+        #[pyo3(get)]
+        samples: Vec<SeatSample>,
+    }
+
+    impl From<crate::client::SeatUsage> for SeatUsage {
+        fn from(usage: crate::client::SeatUsage) -> Self {
+            Self {
+                used: usage.used,
+                total: usage.total,
+                samples: usage
+                    .samples
+                    .into_iter()
+                    .map(|sample| SeatSample {
+                        timestamp: sample.timestamp,
+                        used: sample.used,
+                    })
+                    .collect(),
+            }
+        }
+    }
+
+    /// A seat of a license held by this machine.
+    ///
+    /// Attributes:
+    ///     token (str): The token to pass to `heartbeat`
+    ///     heartbeat_interval (float | None): Seconds between heartbeats, or None if the
+    ///         server does not expire seats
+    ///     activation_id (str | None): The id other machines see in `SeatInfo.id`, or
+    ///         None if the server does not list seats
+    #[pyclass]
+    #[gen_stub_pyclass]
+    pub struct Activation {
+        #[pyo3(get)]
+        token: String,
+        #[pyo3(get)]
+        heartbeat_interval: Option<f64>,
+        #[pyo3(get)]
+        activation_id: Option<String>,
+    }
+
+    /// A seat held by another machine, listed in the `seats` of a
+    /// `SeatLimitReachedError`.
+    ///
+    /// Attributes:
+    ///     id (str): The activation holding the seat, to pass to `deactivate_activation`
+    ///     machine_label (str | None): The `machine_label` the machine was activated
+    ///         with, if any
+    ///     last_seen (int): Unix timestamp, in seconds, of the machine's last activation
+    ///         or heartbeat
+    #[pyclass]
+    #[gen_stub_pyclass]
+    #[derive(Clone)]
+    pub struct SeatInfo {
+        #[pyo3(get)]
+        id: String,
+        #[pyo3(get)]
+        machine_label: Option<String>,
+        #[pyo3(get)]
+        last_seen: u64,
     }
 
-    impl ValidationError {
-        fn new(msg: String) -> Self {
-            Self { msg }
+    impl From<&client::SeatInfo> for SeatInfo {
+        fn from(seat: &client::SeatInfo) -> Self {
+            Self {
+                id: seat.id.to_string(),
+                machine_label: seat.machine_label.clone(),
+                last_seen: seat.last_seen,
+            }
         }
     }
 
-    impl From<ValidationError> for PyErr {
-        fn from(e: ValidationError) -> Self {
-            PyErr::new::<LicenseValidationError, _>(e.msg)
+    impl From<ActivationToken> for Activation {
+        fn from(activation: ActivationToken) -> Self {
+            Self {
+                token: activation.token,
+                heartbeat_interval: activation
+                    .heartbeat_interval
+                    .map(|interval| interval.as_secs_f64()),
+                activation_id: activation.activation_id.map(|id| id.to_string()),
+            }
         }
     }
 
-    // / Exception raised when license validation fails.
-    // /
-    // / This exception is raised when there are issues validating a license, which can
-    // / include:
-    // /
-    // / - Invalid license UUID format
-    // / - Network connectivity issues
-    // / - Server-side validation failures
-    // / - Expired licenses
-    // / - Unauthorized applications
-    // /
-    // / # Example
-    // / ```python
-    // / from chipa_license_validator import LicenseClient, LicenseValidationError
-    // /
-    // / try:
-    // /     client = LicenseClient("https://license.example.com")
-    // /     token = await client.validate_license(
-    // /         "550e8400-e29b-41d4-a716-446655440000",
-    // /         "my-app"
-    // /     )
-    // / except LicenseValidationError as e:
-    // /     print(f"Validation failed: {str(e)}")
-    // /     # Handle specific validation errors
-    // / ```
-    create_exception!(chipa_license_validator, LicenseValidationError, PyException);
+    fn machine_id(machine_id: Option<String>) -> String {
+        machine_id.unwrap_or_else(|| DeviceFingerprint::collect().activation_id())
+    }
 
     /// A client for validating licenses against the Chipa License Server.
     ///
@@ -193,11 +1680,29 @@ pub mod py {
     /// except LicenseValidationError as e:
     ///     print(f"License validation failed: {str(e)}")
     /// ```
+    ///
+    /// # Forking
+    /// A client belongs to the process that created it. Using it in a forked child
+    /// raises RuntimeError; create clients after forking (e.g. per gunicorn worker).
     #[pyclass]
     #[gen_stub_pyclass]
     pub struct LicenseClient {
         client: TClient,
         application: String,
+        pid: u32,
+    }
+
+    impl LicenseClient {
+        fn check_process(&self) -> PyResult<()> {
+            let pid = std::process::id();
+            if pid != self.pid {
+                return Err(PyRuntimeError::new_err(format!(
+                    "This LicenseClient was created in process {} and cannot be used in forked process {}, create a new client after forking",
+                    self.pid, pid
+                )));
+            }
+            Ok(())
+        }
     }
 
     #[gen_stub_pymethods]
@@ -210,20 +1715,119 @@ pub mod py {
         ///
         /// Args:
         ///     base_url (str): The base URL of the license server (e.g., "https://license.example.com")
+        ///     application (str): The identifier of the application requesting validation
+        ///     max_concurrency (int, optional): Maximum number of requests this client has
+        ///         in flight at once. Further requests wait for a free slot instead of
+        ///         failing, so `asyncio.gather` over many licenses never floods the server.
+        ///         Defaults to no limit.
+        ///     timeout (float, optional): Seconds each HTTP attempt may take, from sending
+        ///         the request to reading the whole response. Unlike the `timeout` of
+        ///         `validate_license`, a timed out attempt can be retried. Defaults to no
+        ///         timeout.
+        ///     connect_timeout (float, optional): Seconds to wait for a connection to the
+        ///         license server. Defaults to no timeout.
+        ///     retries (int, optional): How many times a validation or seat query is
+        ///         retried when the server cannot be reached or answers with a 5xx status.
+        ///         4xx answers, such as an expired license, are never retried. Defaults to 0.
+        ///     retry_backoff (float, optional): Seconds to wait before the first retry,
+        ///         doubled for each one after. Defaults to 0.2.
+        ///     operation_timeout (float, optional): Seconds a request may take with all of
+        ///         its retries and backoff; past it the request fails with
+        ///         `ValidationTimeoutError`. Defaults to no deadline.
+        ///     headers (dict[str, str], optional): Extra headers sent with every request.
+        ///         `Authorization`, `Content-Type` and the protocol version header cannot
+        ///         be overridden.
+        ///     machine_label (str, optional): A name for this machine, e.g. the device
+        ///         name the user chose, sent with activations. Other machines see it in
+        ///         `SeatInfo.machine_label`.
         ///
         /// Returns:
         ///     LicenseClient: A new instance of the license client configured with the specified URL
         ///
+        /// Raises:
+        ///     ValueError: If `max_concurrency` is 0, a duration is negative or not a finite
+        ///         number, or a header name or value is invalid
+        ///
         /// Example:
         ///     ```python
-        ///     client = LicenseClient("https://license.example.com")
+        ///     client = LicenseClient("https://license.example.com", "my-app", max_concurrency=10)
+        ///     tokens = await asyncio.gather(*(client.validate_license(l) for l in licenses))
+        ///
+        ///     client = LicenseClient(
+        ///         "https://license.example.com",
+        ///         "my-app",
+        ///         timeout=5.0,
+        ///         retries=3,
+        ///         headers={"X-Tenant": "acme"},
+        ///     )
         ///     ```
         #[new]
-        pub fn new(base_url: String, application: String) -> Self {
-            Self {
-                client: TClient::new(base_url),
-                application,
+        #[pyo3(signature = (
+            base_url,
+            application,
+            max_concurrency=None,
+            timeout=None,
+            connect_timeout=None,
+            retries=0,
+            retry_backoff=None,
+            operation_timeout=None,
+            headers=None,
+            machine_label=None
+        ))]
+        #[allow(clippy::too_many_arguments)]
+        pub fn new(
+            base_url: String,
+            application: String,
+            max_concurrency: Option<usize>,
+            timeout: Option<f64>,
+            connect_timeout: Option<f64>,
+            retries: u32,
+            retry_backoff: Option<f64>,
+            operation_timeout: Option<f64>,
+            headers: Option<HashMap<String, String>>,
+            machine_label: Option<String>,
+        ) -> PyResult<Self> {
+            let max_concurrency = match max_concurrency {
+                Some(max) => Some(NonZeroUsize::new(max).ok_or_else(|| {
+                    PyValueError::new_err("max_concurrency must be at least 1")
+                })?),
+                None => None,
+            };
+            let mut client = TClient::new(base_url)
+                .set_max_concurrency(max_concurrency)
+                .set_timeout(parse_seconds("timeout", timeout)?)
+                .set_connect_timeout(parse_seconds("connect_timeout", connect_timeout)?)
+                .set_retries(retries)
+                .set_operation_timeout(parse_seconds("operation_timeout", operation_timeout)?)
+                .set_machine_label(machine_label);
+            if let Some(backoff) = parse_seconds("retry_backoff", retry_backoff)? {
+                client = client.set_retry_backoff(backoff);
+            }
+            for (name, value) in headers.unwrap_or_default() {
+                let invalid = |e: &dyn std::fmt::Display| {
+                    PyValueError::new_err(format!("Invalid header '{}', {}", name, e))
+                };
+                let header = HeaderName::from_bytes(name.as_bytes()).map_err(|e| invalid(&e))?;
+                let value = HeaderValue::from_str(&value).map_err(|e| invalid(&e))?;
+                client = client.set_header(header, value);
             }
+            Ok(Self {
+                client,
+                application,
+                pid: std::process::id(),
+            })
+        }
+
+        /// The number of requests this client currently has in flight.
+        ///
+        /// Requests waiting for a slot under `max_concurrency` are not counted, so this
+        /// never exceeds the limit.
+        ///
+        /// Returns:
+        ///     int: The number of requests currently being sent or awaiting a response
+        #[getter]
+        pub fn in_flight(&self) -> usize {
+            self.client.in_flight()
         }
 
         /// Updates the base URL of the license client.
@@ -245,91 +1849,697 @@ pub mod py {
             Self {
                 client: self.client.clone().set_url(url),
                 application: self.application.clone(),
+                pid: std::process::id(),
             }
         }
 
+        /// Releases this client's hold on its shared connection pool.
+        ///
+        /// Clients with the same base URL share one connection pool, which is freed once
+        /// no client uses it. Calling `close` releases this client's share immediately
+        /// instead of waiting for garbage collection. A closed client can still be used;
+        /// it reacquires a pool for the duration of each request.
+        ///
+        /// Example:
+        ///     ```python
+        ///     client = LicenseClient("https://license.example.com", "my-app")
+        ///     token = await client.validate_license(license)
+        ///     client.close()
+        ///     ```
+        pub fn close(&mut self) {
+            self.client.close();
+        }
+
         /// Validates a license against the server.
         ///
         /// Performs an asynchronous validation of the provided license key for the
         /// specified application.
         ///
         /// Args:
-        ///     license (str): The license UUID to validate (must be a valid UUID string)
-        ///     application (str): The application identifier requesting validation
+        ///     license (str): The license to validate, either a UUID or a
+        ///         `CHIPA-XXXX-XXXX-XXXX-XXXX` key (case and dashes are normalized)
+        ///     timeout (float, optional): Maximum number of seconds the validation may take.
+        ///         When it elapses the underlying request is aborted. Defaults to no timeout.
+        ///     cache_path (str, optional): Path of an encrypted file keeping the last
+        ///         successful validation. It is refreshed on every successful validation
+        ///         and used only when the server cannot be reached; a timeout does not fall
+        ///         back to it. Requires `grace`.
+        ///     grace (float, optional): How old, in seconds, a cached validation may be to
+        ///         stand in for the server. Requires `cache_path`.
         ///
         /// Returns:
         ///     str: A validation token that can be used to verify the license status
         ///
         /// Raises:
-        ///     LicenseValidationError: If validation fails for any reason:
-        ///         - Invalid license UUID format
-        ///         - Network connectivity issues
-        ///         - Server-side validation failures
-        ///         - Expired licenses
-        ///         - Unauthorized applications
+        ///     ValueError: If `timeout` or `grace` is negative or not a finite number, or
+        ///         only one of `cache_path` and `grace` is given
+        ///     ValidationTimeoutError: If the validation did not finish within `timeout` seconds
+        ///     ClientTooOldError: If the license server no longer supports this client's
+        ///         protocol version; `download_url` tells where to get a newer release
+        ///     LicenseExpiredError: If the license has expired or is unpaid
+        ///     LicenseNotFoundError: If the license server does not know the license
+        ///     ApplicationUnauthorizedError: If the license does not cover this application
+        ///     LicenseValidationError: If validation fails for any other reason:
+        ///         - Malformed license UUID or key
+        ///         - Network connectivity issues, without a cached validation within `grace`
+        ///         - Other server-side validation failures, even if a cached validation exists
+        ///
+        ///     All of them subclass LicenseValidationError and have a `status` attribute
+        ///     with the server's HTTP status, or None if it was not reached, and a `kind`
+        ///     string shared with the Rust and Node libraries. They carry a
+        ///     `remediation` dict describing what the user can do about the failure. Its
+        ///     `action` key is one of "retry_after", "retry_later", "check_internet",
+        ///     "renew", "contact_support" or "update_app"; "retry_after" adds
+        ///     `retry_after` (seconds) and "renew" adds `portal_url`.
         ///
         /// Example:
         ///     ```python
         ///     try:
         ///         token = await client.validate_license(
         ///             "550e8400-e29b-41d4-a716-446655440000",
-        ///             "my-app"
+        ///             timeout=5.0
         ///         )
         ///         print(f"Validation successful: {token}")
+        ///     except ValidationTimeoutError:
+        ///         print("License server took too long to answer")
+        ///     except ClientTooOldError as e:
+        ///         print(f"Please update the app: {e.download_url}")
+        ///     except LicenseExpiredError as e:
+        ///         print(f"Your license expired, renew at {e.remediation.get('portal_url')}")
         ///     except LicenseValidationError as e:
-        ///         print(f"Validation failed: {str(e)}")
+        ///         if e.remediation["action"] == "check_internet":
+        ///             print("Check your internet connection")
+        ///         else:
+        ///             print(f"Validation failed: {str(e)}")
+        ///
+        ///     # Keep working for up to a day without network.
+        ///     token = await client.validate_license(
+        ///         license, cache_path="license.chipa", grace=24 * 3600
+        ///     )
         ///     ```
+        #[pyo3(signature = (license, timeout=None, cache_path=None, grace=None))]
         pub fn validate_license<'py>(
             &self,
             py: Python<'py>,
             license: String,
+            timeout: Option<f64>,
+            cache_path: Option<String>,
+            grace: Option<f64>,
         ) -> PyResult<Bound<'py, PyAny>> {
+            self.check_process()?;
             let client = self.client.clone();
             let app = self.application.clone();
-            pyo3_async_runtimes::tokio::future_into_py(py, async move {
-                Ok(client
-                    .validate_license(
-                        Uuid::parse_str(&license)
-                            .map_err(TError::from)
-                            .map_err(|e| ValidationError::new(e.to_string()))?,
-                        app,
-                    )
-                    .await
-                    .map_err(|e| ValidationError::new(e.to_string()))?)
+            let timeout = parse_seconds("timeout", timeout)?;
+            let cache = match (cache_path, parse_seconds("grace", grace)?) {
+                (None, None) => None,
+                (Some(cache_path), Some(grace)) => Some((cache_path, grace)),
+                _ => {
+                    return Err(PyValueError::new_err(
+                        "cache_path and grace must be given together",
+                    ))
+                }
+            };
+            spawn(py, async move {
+                let license = license
+                    .parse::<LicenseId>()
+                    .map_err(ValidationError::from)?;
+                match cache {
+                    Some((cache_path, grace)) => {
+                        with_timeout(
+                            timeout,
+                            client.validate_license_cached(license, app, grace, &cache_path),
+                        )
+                        .await
+                    }
+                    None => with_timeout(timeout, client.validate_license(license, app)).await,
+                }
+            })
+        }
+
+        /// Validates a license and returns the server's response without interpreting it.
+        ///
+        /// Unlike `validate_license`, this does not raise on an error status or on fields
+        /// the client does not know about, which makes it possible to read experimental
+        /// response fields before the typed API supports them.
+        ///
+        /// Args:
+        ///     license (str): The license to validate, either a UUID or a
+        ///         `CHIPA-XXXX-XXXX-XXXX-XXXX` key (case and dashes are normalized)
+        ///     timeout (float, optional): Maximum number of seconds the validation may take.
+        ///         Defaults to no timeout.
+        ///
+        /// Returns:
+        ///     dict: `{"status": int, "body": ...}` where `body` is the decrypted JSON
+        ///         response as plain Python objects, or `None` for an empty response.
+        ///         `body` contains the license token in clear, so keep it out of logs
+        ///         and error reports.
+        ///
+        /// Raises:
+        ///     ValueError: If `timeout` is negative or not a finite number
+        ///     ValidationTimeoutError: If the validation did not finish within `timeout` seconds
+        ///     LicenseValidationError: If the license is malformed, the server cannot be
+        ///         reached, or the response cannot be decrypted or is not JSON
+        ///
+        /// Example:
+        ///     ```python
+        ///     raw = await client.validate_license_raw("550e8400-e29b-41d4-a716-446655440000")
+        ///     if raw["status"] == 200:
+        ///         seats = raw["body"].get("experimental", {}).get("seats")
+        ///     ```
+        #[pyo3(signature = (license, timeout=None))]
+        pub fn validate_license_raw<'py>(
+            &self,
+            py: Python<'py>,
+            license: String,
+            timeout: Option<f64>,
+        ) -> PyResult<Bound<'py, PyAny>> {
+            self.check_process()?;
+            let client = self.client.clone();
+            let app = self.application.clone();
+            let timeout = parse_seconds("timeout", timeout)?;
+            spawn(py, async move {
+                let license = license
+                    .parse::<LicenseId>()
+                    .map_err(ValidationError::from)?;
+                let raw = with_timeout(timeout, client.validate_license_raw(license, app)).await?;
+                Python::with_gil(|py| {
+                    let dict = PyDict::new_bound(py);
+                    dict.set_item("status", raw.status.as_u16())?;
+                    dict.set_item("body", pythonize(py, &raw.body)?)?;
+                    Ok(dict.unbind())
+                })
+            })
+        }
+
+        /// Fetches the seat occupancy of a license.
+        ///
+        /// Args:
+        ///     license (str): The license to query, either a UUID or a
+        ///         `CHIPA-XXXX-XXXX-XXXX-XXXX` key
+        ///     lenient (bool, optional): When True, servers that do not support seat
+        ///         reporting yield an unknown usage (`used` and `total` are None) instead
+        ///         of raising. Defaults to False.
+        ///     timeout (float, optional): Maximum number of seconds the request may take.
+        ///         Defaults to no timeout.
+        ///
+        /// Returns:
+        ///     SeatUsage: The current seat occupancy
+        ///
+        /// Raises:
+        ///     ValueError: If `timeout` is negative or not a finite number
+        ///     ValidationTimeoutError: If the request did not finish within `timeout` seconds
+        ///     LicenseValidationError: If the license is malformed, the server cannot be
+        ///         reached, or the server rejects the request
+        ///
+        /// Example:
+        ///     ```python
+        ///     usage = await client.seat_usage(license, lenient=True)
+        ///     if usage.used is not None:
+        ///         print(f"{usage.used}/{usage.total} seats in use")
+        ///     ```
+        #[pyo3(signature = (license, lenient=false, timeout=None))]
+        pub fn seat_usage<'py>(
+            &self,
+            py: Python<'py>,
+            license: String,
+            lenient: bool,
+            timeout: Option<f64>,
+        ) -> PyResult<Bound<'py, PyAny>> {
+            self.check_process()?;
+            let client = self.client.clone().set_lenient(lenient);
+            let timeout = parse_seconds("timeout", timeout)?;
+            spawn(py, async move {
+                let license = license
+                    .parse::<LicenseId>()
+                    .map_err(ValidationError::from)?;
+                let usage = with_timeout(timeout, client.seat_usage(license)).await?;
+                Ok(SeatUsage::from(usage))
+            })
+        }
+
+        /// Takes a seat of a seat-limited license for this machine.
+        ///
+        /// Activating a machine that already holds a seat refreshes its token.
+        ///
+        /// Args:
+        ///     license (str): The license to activate, either a UUID or a
+        ///         `CHIPA-XXXX-XXXX-XXXX-XXXX` key
+        ///     machine_id (str, optional): Identifier of the machine. Defaults to a hash
+        ///         of the machine id or volume serial, which never leaves the machine in clear.
+        ///     timeout (float, optional): Maximum number of seconds the request may take.
+        ///         Defaults to no timeout.
+        ///
+        /// Returns:
+        ///     Activation: The seat, whose `token` is sent to `heartbeat` every
+        ///         `heartbeat_interval` seconds
+        ///
+        /// Raises:
+        ///     ValueError: If `timeout` is negative or not a finite number
+        ///     ValidationTimeoutError: If the request did not finish within `timeout` seconds
+        ///     SeatLimitReachedError: If every seat is held by other machines, listed in its
+        ///         `seats`
+        ///     LicenseValidationError: If the license is malformed, the server cannot be
+        ///         reached, has no seat-limited licenses, or rejects the license
+        ///
+        /// Example:
+        ///     ```python
+        ///     seat = await client.activate_license(license)
+        ///     token = seat.token
+        ///     while True:
+        ///         await asyncio.sleep(seat.heartbeat_interval or 300)
+        ///         token = (await client.heartbeat(license, token)).token
+        ///     ```
+        #[pyo3(signature = (license, machine_id=None, timeout=None))]
+        pub fn activate_license<'py>(
+            &self,
+            py: Python<'py>,
+            license: String,
+            machine_id: Option<String>,
+            timeout: Option<f64>,
+        ) -> PyResult<Bound<'py, PyAny>> {
+            self.check_process()?;
+            let client = self.client.clone();
+            let app = self.application.clone();
+            let timeout = parse_seconds("timeout", timeout)?;
+            spawn(py, async move {
+                let license = license
+                    .parse::<LicenseId>()
+                    .map_err(ValidationError::from)?;
+                let machine_id = self::machine_id(machine_id);
+                let activation =
+                    with_timeout(timeout, client.activate_license(license, app, machine_id))
+                        .await?;
+                Ok(Activation::from(activation))
+            })
+        }
+
+        /// Frees the seat held by this machine, e.g. when the user signs out.
+        ///
+        /// Args:
+        ///     license (str): The activated license, either a UUID or a
+        ///         `CHIPA-XXXX-XXXX-XXXX-XXXX` key
+        ///     machine_id (str, optional): Identifier of the machine, the same as given
+        ///         to `activate_license`
+        ///     timeout (float, optional): Maximum number of seconds the request may take.
+        ///         Defaults to no timeout.
+        ///
+        /// Raises:
+        ///     ValueError: If `timeout` is negative or not a finite number
+        ///     ValidationTimeoutError: If the request did not finish within `timeout` seconds
+        ///     UnknownMachineError: If the machine holds no seat
+        ///     LicenseValidationError: If the license is malformed, the server cannot be
+        ///         reached, or the server rejects the request
+        #[pyo3(signature = (license, machine_id=None, timeout=None))]
+        pub fn deactivate_license<'py>(
+            &self,
+            py: Python<'py>,
+            license: String,
+            machine_id: Option<String>,
+            timeout: Option<f64>,
+        ) -> PyResult<Bound<'py, PyAny>> {
+            self.check_process()?;
+            let client = self.client.clone();
+            let app = self.application.clone();
+            let timeout = parse_seconds("timeout", timeout)?;
+            spawn(py, async move {
+                let license = license
+                    .parse::<LicenseId>()
+                    .map_err(ValidationError::from)?;
+                let machine_id = self::machine_id(machine_id);
+                with_timeout(timeout, client.deactivate_license(license, app, machine_id)).await
+            })
+        }
+
+        /// Frees the seat of another machine, e.g. a stale one listed in the `seats` of a
+        /// `SeatLimitReachedError`.
+        ///
+        /// Args:
+        ///     license (str): The activated license, either a UUID or a
+        ///         `CHIPA-XXXX-XXXX-XXXX-XXXX` key
+        ///     activation_id (str): The `id` of the seat's `SeatInfo`
+        ///     timeout (float, optional): Maximum number of seconds the request may take.
+        ///         Defaults to no timeout.
+        ///
+        /// Raises:
+        ///     ValueError: If `activation_id` is not a UUID, or `timeout` is negative or
+        ///         not a finite number
+        ///     ValidationTimeoutError: If the request did not finish within `timeout` seconds
+        ///     UnknownMachineError: If the seat is already free
+        ///     LicenseValidationError: If the license is malformed, the server cannot be
+        ///         reached, or the server rejects the request
+        ///
+        /// Example:
+        ///     ```python
+        ///     try:
+        ///         seat = await client.activate_license(license)
+        ///     except SeatLimitReachedError as e:
+        ///         stale = min(e.seats, key=lambda seat: seat.last_seen)
+        ///         await client.deactivate_activation(license, stale.id)
+        ///         seat = await client.activate_license(license)
+        ///     ```
+        #[pyo3(signature = (license, activation_id, timeout=None))]
+        pub fn deactivate_activation<'py>(
+            &self,
+            py: Python<'py>,
+            license: String,
+            activation_id: String,
+            timeout: Option<f64>,
+        ) -> PyResult<Bound<'py, PyAny>> {
+            self.check_process()?;
+            let client = self.client.clone();
+            let app = self.application.clone();
+            let timeout = parse_seconds("timeout", timeout)?;
+            let activation_id = activation_id.parse::<Uuid>().map_err(|e| {
+                PyValueError::new_err(format!("Invalid activation_id, {}", e))
+            })?;
+            spawn(py, async move {
+                let license = license
+                    .parse::<LicenseId>()
+                    .map_err(ValidationError::from)?;
+                let deactivation = client.deactivate_activation(license, app, activation_id);
+                with_timeout(timeout, deactivation).await
+            })
+        }
+
+        /// Moves the seat of another machine to this one in a single server call, so
+        /// there is no moment when neither machine holds it, e.g. when the user replaces
+        /// a laptop. The old machine's token stops working.
+        ///
+        /// Args:
+        ///     license (str): The activated license, either a UUID or a
+        ///         `CHIPA-XXXX-XXXX-XXXX-XXXX` key
+        ///     from_activation (str): The `activation_id` of the seat to move
+        ///     machine_id (str, optional): Identifier of this machine, as for
+        ///         `activate_license`
+        ///     force (bool, optional): When True, asks the server to move the seat even
+        ///         though the old machine has not released it, e.g. because it is broken.
+        ///         Servers allow that only so often. Defaults to False.
+        ///     timeout (float, optional): Maximum number of seconds the request may take.
+        ///         Defaults to no timeout.
+        ///
+        /// Returns:
+        ///     Activation: This machine's seat
+        ///
+        /// Raises:
+        ///     ValueError: If `from_activation` is not a UUID, or `timeout` is negative or
+        ///         not a finite number
+        ///     ValidationTimeoutError: If the request did not finish within `timeout` seconds
+        ///     TransferCooldownError: If a forced transfer was made too recently
+        ///     UnknownMachineError: If the seat to move is already free
+        ///     LicenseValidationError: If the license is malformed, the server cannot be
+        ///         reached, or the server rejects the request
+        ///
+        /// Example:
+        ///     ```python
+        ///     seat = await client.transfer_license(license, old_activation_id, force=True)
+        ///     ```
+        #[pyo3(signature = (license, from_activation, machine_id=None, force=false, timeout=None))]
+        pub fn transfer_license<'py>(
+            &self,
+            py: Python<'py>,
+            license: String,
+            from_activation: String,
+            machine_id: Option<String>,
+            force: bool,
+            timeout: Option<f64>,
+        ) -> PyResult<Bound<'py, PyAny>> {
+            self.check_process()?;
+            let client = self.client.clone();
+            let app = self.application.clone();
+            let timeout = parse_seconds("timeout", timeout)?;
+            let from_activation = from_activation.parse::<Uuid>().map_err(|e| {
+                PyValueError::new_err(format!("Invalid from_activation, {}", e))
+            })?;
+            spawn(py, async move {
+                let license = license
+                    .parse::<LicenseId>()
+                    .map_err(ValidationError::from)?;
+                let machine_id = self::machine_id(machine_id);
+                let transfer =
+                    client.transfer_license(license, app, from_activation, machine_id, force);
+                Ok(Activation::from(with_timeout(timeout, transfer).await?))
+            })
+        }
+
+        /// Confirms that this machine still holds its seat and refreshes the token.
+        ///
+        /// Args:
+        ///     license (str): The activated license, either a UUID or a
+        ///         `CHIPA-XXXX-XXXX-XXXX-XXXX` key
+        ///     token (str): The token of the last activation or heartbeat
+        ///     lenient (bool, optional): When True, servers without seat-limited licenses
+        ///         hand back `token` instead of raising. Defaults to False.
+        ///     timeout (float, optional): Maximum number of seconds the request may take.
+        ///         Defaults to no timeout.
+        ///
+        /// Returns:
+        ///     Activation: The refreshed seat
+        ///
+        /// Raises:
+        ///     ValueError: If `timeout` is negative or not a finite number
+        ///     ValidationTimeoutError: If the request did not finish within `timeout` seconds
+        ///     UnknownMachineError: If the server has freed the seat
+        ///     LicenseValidationError: If the license is malformed, the server cannot be
+        ///         reached, or the server rejects the request
+        #[pyo3(signature = (license, token, lenient=false, timeout=None))]
+        pub fn heartbeat<'py>(
+            &self,
+            py: Python<'py>,
+            license: String,
+            token: String,
+            lenient: bool,
+            timeout: Option<f64>,
+        ) -> PyResult<Bound<'py, PyAny>> {
+            self.check_process()?;
+            let client = self.client.clone().set_lenient(lenient);
+            let app = self.application.clone();
+            let timeout = parse_seconds("timeout", timeout)?;
+            spawn(py, async move {
+                let license = license
+                    .parse::<LicenseId>()
+                    .map_err(ValidationError::from)?;
+                let activation =
+                    with_timeout(timeout, client.heartbeat(license, app, token)).await?;
+                Ok(Activation::from(activation))
+            })
+        }
+
+        /// Validates a license and loads the `.chipa` file at `path`, encrypted with the
+        /// validation token.
+        ///
+        /// The license is validated without holding the GIL; it is only taken to convert
+        /// the decrypted value to Python objects.
+        ///
+        /// Args:
+        ///     path (str): Path of the `.chipa` file
+        ///     license (str): The license to validate, either a UUID or a
+        ///         `CHIPA-XXXX-XXXX-XXXX-XXXX` key
+        ///     timeout (float, optional): Maximum number of seconds the validation may take.
+        ///         Defaults to no timeout.
+        ///     max_depth (int, optional): How deeply the value may nest. Defaults to 512.
+        ///     max_nodes (int, optional): How many values, keys included, the value may
+        ///         hold. Defaults to 10,000,000.
+        ///
+        /// Returns:
+        ///     Any: The value stored in the file, as plain Python objects
+        ///
+        /// Raises:
+        ///     ValueError: If `timeout` is negative or not a finite number
+        ///     ValidationTimeoutError: If the validation did not finish within `timeout` seconds
+        ///     LicenseValidationError: If the license could not be validated
+        ///     ChipaFileError: If the file cannot be read, was not encrypted with the
+        ///         token of this license (`kind` is "decryption") or goes over `max_depth`
+        ///         or `max_nodes` (`kind` is "limit_exceeded")
+        ///
+        /// Example:
+        ///     ```python
+        ///     strategy = await client.load("strategy.chipa", license)
+        ///     print(strategy["risk"])
+        ///     ```
+        #[pyo3(signature = (path, license, timeout=None, max_depth=None, max_nodes=None))]
+        pub fn load<'py>(
+            &self,
+            py: Python<'py>,
+            path: String,
+            license: String,
+            timeout: Option<f64>,
+            max_depth: Option<usize>,
+            max_nodes: Option<usize>,
+        ) -> PyResult<Bound<'py, PyAny>> {
+            self.check_process()?;
+            let client = self.client.clone();
+            let app = self.application.clone();
+            let timeout = parse_seconds("timeout", timeout)?;
+            let limits = value_limits(max_depth, max_nodes);
+            spawn(py, async move {
+                let license = license
+                    .parse::<LicenseId>()
+                    .map_err(ValidationError::from)?;
+                let token = with_timeout(timeout, client.validate_license(license, app)).await?;
+                // Reading and decrypting a large file must not stall the runtime's workers.
+                let data: Value = tokio::task::spawn_blocking(move || {
+                    encryption::ChipaFile::load(&path, &token)
+                        .and_then(|file| file.read_bounded(limits))
+                })
+                .await
+                .map_err(|e| PyRuntimeError::new_err(e.to_string()))?
+                .map_err(chipa_error)?;
+                Python::with_gil(|py| to_python(py, &data, &mut 0))
+            })
+        }
+    }
+    /// An encrypted `.chipa` file holding any JSON-compatible value.
+    ///
+    /// The same files are read and written by the Rust and Node.js libraries, so
+    /// configuration encrypted by one can be shipped to the others.
+    ///
+    /// Args:
+    ///     data (Any, optional): The value to store. Defaults to None.
+    ///
+    /// Raises:
+    ///     TypeError: If `data` is not JSON-compatible
+    ///     ChipaFileError: If `data` nests deeper than 512 or holds more than 10,000,000
+    ///         values, the defaults `read` enforces (`kind` is "limit_exceeded")
+    ///
+    /// Example:
+    ///     ```python
+    ///     from chipa_license_validator import ChipaFile, ChipaFileError
+    ///
+    ///     ChipaFile({"strategy": "momentum", "risk": 0.02}).save("strategy.chipa", key)
+    ///
+    ///     try:
+    ///         strategy = ChipaFile.load("strategy.chipa", key).read()
+    ///     except ChipaFileError as e:
+    ///         if e.kind == "decryption":
+    ///             print("Wrong key")
+    ///     ```
+    #[pyclass]
+    #[gen_stub_pyclass]
+    pub struct ChipaFile {
+        file: encryption::ChipaFile,
+    }
+
+    #[gen_stub_pymethods]
+    #[pymethods]
+    impl ChipaFile {
+        #[new]
+        #[pyo3(signature = (data=None))]
+        pub fn new(data: Option<Bound<'_, PyAny>>) -> PyResult<Self> {
+            let data = match data {
+                Some(data) => json_data(data)?,
+                None => Value::Null,
+            };
+            Ok(Self {
+                file: encryption::ChipaFile::new(Version::LATEST, &data).map_err(chipa_error)?,
             })
         }
 
-        // pub fn load<'py>(&self, py: Python<'py>, path: String, license: String) -> PyResult<Bound<'static, PyAny>> {
-        //     let client = self.client.clone();
-        //     let app = self.application.clone();
-        //     pyo3_async_runtimes::tokio::future_into_py(py, async move {
-        //         let license = client.validate_license(Uuid::parse_str(&license)
-        //         .map_err(TError::from)
-        //         .map_err(|e| ValidationError::new(e.to_string()))?, app)
-        //         .await
-        //         .map_err(|e| ValidationError::new(e.to_string()))?;
-        //         Python::with_gil(|py: Python<'static>| {
-        //         let file = ChipaFile::load(&path, &license)
-        //             .map_err(TError::from)
-        //             .map_err(|e| ValidationError::new(e.to_string()))?;
-        //             let data: Value = file.read()
-        //                 .map_err(TError::from)
-        //                 .map_err(|e| ValidationError::new(e.to_string()))?;
-
-        //             pythonize(py, &data).map_err(move |e| PyErr::from(e))
-                    
-        //         })
-        //     })
-        // }
+        /// Reads and decrypts the file at `path` with `key`.
+        ///
+        /// Args:
+        ///     path (str): Path of the `.chipa` file
+        ///     key (str): The key the file was saved with
+        ///
+        /// Returns:
+        ///     ChipaFile: The decrypted file
+        ///
+        /// Raises:
+        ///     ChipaFileError: If the file cannot be read (`kind` is "file_creation") or
+        ///         `key` is wrong (`kind` is "decryption")
+        #[staticmethod]
+        pub fn load(py: Python<'_>, path: String, key: String) -> PyResult<Self> {
+            let file = py
+                .allow_threads(|| encryption::ChipaFile::load(&path, &key))
+                .map_err(chipa_error)?;
+            Ok(Self { file })
+        }
+
+        /// Encrypts the file with `key` and writes it to `path`, replacing any existing
+        /// file atomically.
+        ///
+        /// Args:
+        ///     path (str): Where to write the file
+        ///     key (str): The key to encrypt it with
+        ///
+        /// Raises:
+        ///     ChipaFileError: If the file cannot be encrypted or written
+        pub fn save(&self, py: Python<'_>, path: String, key: String) -> PyResult<()> {
+            py.allow_threads(|| self.file.save(&path, &key))
+                .map_err(chipa_error)
+        }
+
+        /// Returns the value held by the file. It is decoded without holding the GIL,
+        /// and converting it to Python objects can be interrupted with Ctrl-C.
+        ///
+        /// Args:
+        ///     max_depth (int, optional): How deeply the value may nest. Defaults to 512.
+        ///     max_nodes (int, optional): How many values, keys included, the value may
+        ///         hold. Defaults to 10,000,000.
+        ///
+        /// Returns:
+        ///     Any: The value, as plain Python objects
+        ///
+        /// Raises:
+        ///     ChipaFileError: If the file does not hold a JSON-compatible value, or it
+        ///         goes over `max_depth` or `max_nodes` (`kind` is "limit_exceeded")
+        #[pyo3(signature = (max_depth=None, max_nodes=None))]
+        pub fn read(
+            &self,
+            py: Python<'_>,
+            max_depth: Option<usize>,
+            max_nodes: Option<usize>,
+        ) -> PyResult<PyObject> {
+            let limits = value_limits(max_depth, max_nodes);
+            let data = py
+                .allow_threads(|| self.file.read_bounded(limits))
+                .map_err(chipa_error)?;
+            to_python(py, &data, &mut 0)
+        }
+
+        /// Replaces the value held by the file. Call `save` to persist it.
+        ///
+        /// Args:
+        ///     data (Any): The new value
+        ///
+        /// Raises:
+        ///     TypeError: If `data` is not JSON-compatible
+        ///     ChipaFileError: If `data` nests deeper than 512 or holds more than
+        ///         10,000,000 values (`kind` is "limit_exceeded")
+        pub fn write(&mut self, data: Bound<'_, PyAny>) -> PyResult<()> {
+            let data = json_data(data)?;
+            self.file.write(&data).map_err(chipa_error)
+        }
     }
+
     #[pymodule]
     #[pyo3(name = "chipa_license_validator")]
     fn chipa(py: Python<'_>, m: &Bound<'_, PyModule>) -> PyResult<()> {
         m.add_class::<LicenseClient>()?;
+        m.add_class::<SeatUsage>()?;
+        m.add_class::<SeatSample>()?;
+        m.add_class::<Activation>()?;
+        m.add_class::<SeatInfo>()?;
+        m.add_class::<ChipaFile>()?;
+        m.add_function(wrap_pyfunction!(configure_runtime, m)?)?;
         m.add(
             "LicenseValidationError",
-            py.get_type::<LicenseValidationError>(),
+            py.get_type_bound::<LicenseValidationError>(),
+        )?;
+        m.add(
+            "ValidationTimeoutError",
+            py.get_type_bound::<ValidationTimeoutError>(),
+        )?;
+        m.add("ClientTooOldError", py.get_type_bound::<ClientTooOldError>())?;
+        m.add("LicenseExpiredError", py.get_type_bound::<LicenseExpiredError>())?;
+        m.add("LicenseNotFoundError", py.get_type_bound::<LicenseNotFoundError>())?;
+        m.add(
+            "ApplicationUnauthorizedError",
+            py.get_type_bound::<ApplicationUnauthorizedError>(),
         )?;
+        m.add("SeatLimitReachedError", py.get_type_bound::<SeatLimitReachedError>())?;
+        m.add("UnknownMachineError", py.get_type_bound::<UnknownMachineError>())?;
+        m.add("TransferCooldownError", py.get_type_bound::<TransferCooldownError>())?;
+        m.add("ChipaFileError", py.get_type_bound::<ChipaFileError>())?;
 
         Ok(())
     }
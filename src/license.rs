@@ -0,0 +1,128 @@
+use core::fmt;
+use std::str::FromStr;
+
+use uuid::Uuid;
+
+use crate::client::TError;
+
+const KEY_PREFIX: &str = "CHIPA";
+const KEY_GROUPS: usize = 4;
+const KEY_GROUP_LEN: usize = 4;
+
+// Shared with the license server, which derives the same identity for a key.
+pub const LICENSE_KEY_NAMESPACE: Uuid = Uuid::from_u128(0x6c1c0b5e_8f0a_4d3e_9b7a_2f4c6e8a1d35);
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum LicenseId {
+    Uuid(Uuid),
+    Key(String),
+}
+
+impl LicenseId {
+    pub fn key(key: &str) -> Result<Self, TError> {
+        let compact: String = key
+            .trim()
+            .chars()
+            .filter(|c| *c != '-')
+            .map(|c| c.to_ascii_uppercase())
+            .collect();
+        let body = compact
+            .strip_prefix(KEY_PREFIX)
+            .filter(|body| {
+                body.len() == KEY_GROUPS * KEY_GROUP_LEN
+                    && body.chars().all(|c| c.is_ascii_alphanumeric())
+            })
+            .ok_or_else(|| {
+                TError::InvalidLicense(format!(
+                    "'{}' is neither a UUID nor a {}-XXXX-XXXX-XXXX-XXXX key",
+                    key, KEY_PREFIX
+                ))
+            })?;
+        let mut normalized = String::from(KEY_PREFIX);
+        for group in body.as_bytes().chunks(KEY_GROUP_LEN) {
+            normalized.push('-');
+            normalized.push_str(std::str::from_utf8(group).unwrap_or_default());
+        }
+        Ok(LicenseId::Key(normalized))
+    }
+
+    pub fn identity(&self) -> Uuid {
+        match self {
+            LicenseId::Uuid(uuid) => *uuid,
+            LicenseId::Key(key) => Uuid::new_v5(&LICENSE_KEY_NAMESPACE, key.as_bytes()),
+        }
+    }
+}
+
+impl fmt::Display for LicenseId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LicenseId::Uuid(uuid) => write!(f, "{}", uuid),
+            LicenseId::Key(key) => write!(f, "{}", key),
+        }
+    }
+}
+
+impl FromStr for LicenseId {
+    type Err = TError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match Uuid::parse_str(s.trim()) {
+            Ok(uuid) => Ok(LicenseId::Uuid(uuid)),
+            Err(_) => LicenseId::key(s),
+        }
+    }
+}
+
+impl From<Uuid> for LicenseId {
+    fn from(uuid: Uuid) -> Self {
+        LicenseId::Uuid(uuid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_uuid() {
+        let uuid = Uuid::new_v4();
+        let id: LicenseId = uuid.to_string().parse().unwrap();
+        assert_eq!(id, LicenseId::Uuid(uuid));
+        assert_eq!(id.identity(), uuid);
+        assert_eq!(id.to_string(), uuid.to_string());
+    }
+
+    #[test]
+    fn test_parse_key() {
+        let id: LicenseId = "CHIPA-AB12-CD34-EF56-GH78".parse().unwrap();
+        assert_eq!(id, LicenseId::Key("CHIPA-AB12-CD34-EF56-GH78".to_string()));
+        for variant in [" chipa-ab12-cd34-ef56-gh78 ", "CHIPAAB12CD34EF56GH78"] {
+            assert_eq!(variant.parse::<LicenseId>().unwrap(), id);
+        }
+        assert_eq!(id.identity(), id.identity());
+        assert_eq!(id.identity().get_version_num(), 5);
+        assert_ne!(
+            id.identity(),
+            LicenseId::key("CHIPA-AB12-CD34-EF56-GH79").unwrap().identity()
+        );
+    }
+
+    #[test]
+    fn test_reject_garbage() {
+        for garbage in [
+            "",
+            "not-a-license",
+            "CHIPA-AB12-CD34-EF56",
+            "CHIPA-AB12-CD34-EF56-GH78-IJ90",
+            "CHIPA-AB12-CD34-EF56-GH7!",
+            "OTHER-AB12-CD34-EF56-GH78",
+        ] {
+            assert!(
+                matches!(garbage.parse::<LicenseId>(), Err(TError::InvalidLicense(_))),
+                "{:?} should be rejected",
+                garbage
+            );
+        }
+    }
+}
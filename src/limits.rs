@@ -0,0 +1,224 @@
+//! Decoding `ChipaFile` bodies of untrusted size into a `serde_json::Value`.
+//!
+//! The limits are checked while the body is decoded, so a hostile file fails before
+//! it is fully in memory and never recurses deeper than `max_depth`.
+
+use std::fmt;
+
+use serde::de::{DeserializeSeed, Error, MapAccess, SeqAccess, Visitor};
+use serde_json::{Map, Number, Value};
+
+use crate::encryption::{ChipaError, ChipaResult};
+
+pub const DEFAULT_MAX_DEPTH: usize = 512;
+pub const DEFAULT_MAX_NODES: usize = 10_000_000;
+
+/// Bounds for [`ChipaFile::read_bounded`](crate::ChipaFile::read_bounded). Every array,
+/// object, key and scalar counts as one node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ValueLimits {
+    pub max_depth: usize,
+    pub max_nodes: usize,
+}
+
+impl Default for ValueLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_nodes: DEFAULT_MAX_NODES,
+        }
+    }
+}
+
+pub(crate) fn decode(body: &[u8], limits: ValueLimits) -> ChipaResult<Value> {
+    let mut deserializer = rmp_serde::Deserializer::from_read_ref(body);
+    // The seed stops before this, it only keeps rmp_serde's own default out of the way.
+    deserializer.set_max_depth(limits.max_depth.saturating_add(2));
+    let mut budget = Budget {
+        limits,
+        nodes: 0,
+        exceeded: None,
+    };
+    let seed = Bounded {
+        budget: &mut budget,
+        depth: 0,
+    };
+    match seed.deserialize(&mut deserializer) {
+        Ok(value) => Ok(value),
+        Err(e) => Err(match budget.exceeded {
+            Some((limit, max)) => ChipaError::LimitExceeded { limit, max },
+            None => ChipaError::Decode(e.to_string()),
+        }),
+    }
+}
+
+struct Budget {
+    limits: ValueLimits,
+    nodes: usize,
+    exceeded: Option<(&'static str, usize)>,
+}
+
+impl Budget {
+    fn exceed<E: Error>(&mut self, limit: &'static str, max: usize) -> E {
+        self.exceeded = Some((limit, max));
+        E::custom(format!("{} of {} exceeded", limit, max))
+    }
+
+    fn node<E: Error>(&mut self) -> Result<(), E> {
+        self.nodes += 1;
+        match self.nodes > self.limits.max_nodes {
+            true => Err(self.exceed("max_nodes", self.limits.max_nodes)),
+            false => Ok(()),
+        }
+    }
+
+    fn nest<E: Error>(&mut self, depth: usize) -> Result<(), E> {
+        self.node()?;
+        match depth >= self.limits.max_depth {
+            true => Err(self.exceed("max_depth", self.limits.max_depth)),
+            false => Ok(()),
+        }
+    }
+}
+
+struct Bounded<'a> {
+    budget: &'a mut Budget,
+    depth: usize,
+}
+
+impl Bounded<'_> {
+    fn child(&mut self) -> Bounded<'_> {
+        Bounded {
+            budget: self.budget,
+            depth: self.depth + 1,
+        }
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for Bounded<'_> {
+    type Value = Value;
+
+    fn deserialize<D: serde::Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for Bounded<'_> {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a JSON-compatible value")
+    }
+
+    fn visit_bool<E: Error>(self, v: bool) -> Result<Value, E> {
+        self.budget.node()?;
+        Ok(Value::Bool(v))
+    }
+
+    fn visit_i64<E: Error>(self, v: i64) -> Result<Value, E> {
+        self.budget.node()?;
+        Ok(Value::from(v))
+    }
+
+    fn visit_u64<E: Error>(self, v: u64) -> Result<Value, E> {
+        self.budget.node()?;
+        Ok(Value::from(v))
+    }
+
+    fn visit_f64<E: Error>(self, v: f64) -> Result<Value, E> {
+        self.budget.node()?;
+        Ok(Number::from_f64(v).map_or(Value::Null, Value::Number))
+    }
+
+    fn visit_str<E: Error>(self, v: &str) -> Result<Value, E> {
+        self.visit_string(v.to_string())
+    }
+
+    fn visit_string<E: Error>(self, v: String) -> Result<Value, E> {
+        self.budget.node()?;
+        Ok(Value::String(v))
+    }
+
+    fn visit_unit<E: Error>(self) -> Result<Value, E> {
+        self.budget.node()?;
+        Ok(Value::Null)
+    }
+
+    fn visit_none<E: Error>(self) -> Result<Value, E> {
+        self.visit_unit()
+    }
+
+    fn visit_some<D: serde::Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(mut self, mut seq: A) -> Result<Value, A::Error> {
+        self.budget.nest(self.depth)?;
+        let mut values = Vec::new();
+        while let Some(value) = seq.next_element_seed(self.child())? {
+            values.push(value);
+        }
+        Ok(Value::Array(values))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(mut self, mut map: A) -> Result<Value, A::Error> {
+        self.budget.nest(self.depth)?;
+        let mut values = Map::new();
+        while let Some(key) = map.next_key::<String>()? {
+            self.budget.node()?;
+            let value = map.next_value_seed(self.child())?;
+            values.insert(key, value);
+        }
+        Ok(Value::Object(values))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A msgpack array holding an array ... `depth` times, around a nil.
+    fn nested(depth: usize) -> Vec<u8> {
+        let mut body = vec![0x91; depth];
+        body.push(0xc0);
+        body
+    }
+
+    #[test]
+    fn test_decode_within_limits() {
+        let body = rmp_serde::to_vec(&serde_json::json!({ "a": [1, 2.5, "x", null, true] }))
+            .unwrap();
+        let value = decode(&body, ValueLimits::default()).unwrap();
+        assert_eq!(value, serde_json::json!({ "a": [1, 2.5, "x", null, true] }));
+    }
+
+    #[test]
+    fn test_decode_deeply_nested() {
+        let limits = ValueLimits {
+            max_depth: 100,
+            ..Default::default()
+        };
+        assert!(decode(&nested(100), limits).is_ok());
+        let e = decode(&nested(10_000), limits).unwrap_err();
+        assert!(matches!(e, ChipaError::LimitExceeded { limit: "max_depth", max: 100 }));
+        assert!(e.to_string().contains("max_depth"), "{}", e);
+    }
+
+    #[test]
+    fn test_decode_too_many_nodes() {
+        let body = rmp_serde::to_vec(&vec![0u8; 1000]).unwrap();
+        let limits = |max_nodes| ValueLimits {
+            max_nodes,
+            ..Default::default()
+        };
+        assert!(decode(&body, limits(1001)).is_ok());
+        let e = decode(&body, limits(1000)).unwrap_err();
+        assert!(matches!(e, ChipaError::LimitExceeded { limit: "max_nodes", max: 1000 }));
+    }
+
+    #[test]
+    fn test_decode_malformed() {
+        let e = decode(&[0x92, 0x01], ValueLimits::default()).unwrap_err();
+        assert!(matches!(e, ChipaError::Decode(_)));
+    }
+}
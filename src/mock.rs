@@ -0,0 +1,764 @@
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    fmt,
+    net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use hyper::{
+    header::{HeaderMap, HeaderValue, AUTHORIZATION, RETRY_AFTER},
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tenacity_utils::security::headers::VERSION as VERSION_STR;
+use tokio::{sync::oneshot, task::JoinHandle};
+use uuid::Uuid;
+
+use crate::{client::Capability, license::LicenseId, version::Version};
+
+const VERSION: Version = Version::V1;
+const SCENARIO_PREFIX: u128 = 0xc41fa000_0000_4000_8000_000000000000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Scenario {
+    Valid,
+    Expired,
+    RateLimited,
+    Malformed,
+    Empty,
+    EmptyError,
+    ClientTooOld,
+    Unpaid,
+    NotFound,
+    UnauthorizedApp,
+    BadGateway,
+}
+
+impl Scenario {
+    pub const ALL: [Scenario; 11] = [
+        Scenario::Valid,
+        Scenario::Expired,
+        Scenario::RateLimited,
+        Scenario::Malformed,
+        Scenario::Empty,
+        Scenario::EmptyError,
+        Scenario::ClientTooOld,
+        Scenario::Unpaid,
+        Scenario::NotFound,
+        Scenario::UnauthorizedApp,
+        Scenario::BadGateway,
+    ];
+
+    pub fn license(self) -> Uuid {
+        let index = Self::ALL.iter().position(|s| *s == self).unwrap_or_default();
+        Uuid::from_u128(SCENARIO_PREFIX | (index as u128 + 1))
+    }
+
+    pub fn from_license(license: Uuid) -> Option<Self> {
+        Self::ALL.into_iter().find(|s| s.license() == license)
+    }
+}
+
+impl fmt::Display for Scenario {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Scenario::Valid => "valid",
+            Scenario::Expired => "expired",
+            Scenario::RateLimited => "rate-limited",
+            Scenario::Malformed => "malformed",
+            Scenario::Empty => "empty",
+            Scenario::EmptyError => "empty-error",
+            Scenario::ClientTooOld => "client-too-old",
+            Scenario::Unpaid => "unpaid",
+            Scenario::NotFound => "not-found",
+            Scenario::UnauthorizedApp => "unauthorized-app",
+            Scenario::BadGateway => "bad-gateway",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Scenario {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|scenario| scenario.to_string() == s)
+            .ok_or_else(|| format!("Unknown scenario '{}'", s))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MockConfig {
+    pub host: IpAddr,
+    pub port: u16,
+    pub default_scenario: Scenario,
+    pub token: String,
+    /// Seats used and total, as reported by `/subscriptions/seats`. The total also
+    /// caps how many machines can be activated at once.
+    pub seats: Option<(u32, u32)>,
+    pub latency: Duration,
+    /// Answer this many requests with `503 Service Unavailable` before serving any.
+    pub fail_first: usize,
+    /// Behave like a server that predates `/capabilities` and context validation, to
+    /// test graceful degradation. Set `seats` to `None` as well for the oldest ones.
+    pub legacy: bool,
+    /// How long after a forced transfer of a license the next one is refused.
+    pub transfer_cooldown: Duration,
+}
+
+impl Default for MockConfig {
+    fn default() -> Self {
+        Self {
+            host: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port: 0,
+            default_scenario: Scenario::Valid,
+            token: "mock-token".to_string(),
+            seats: Some((3, 10)),
+            latency: Duration::ZERO,
+            fail_first: 0,
+            legacy: false,
+            transfer_cooldown: Duration::from_secs(24 * 3600),
+        }
+    }
+}
+
+struct MockState {
+    config: MockConfig,
+    requests: AtomicUsize,
+    in_flight: AtomicUsize,
+    peak_in_flight: AtomicUsize,
+    contexts: Mutex<Vec<Value>>,
+    last_headers: Mutex<Option<HeaderMap>>,
+    activations: Mutex<HashMap<Uuid, Vec<Seat>>>,
+    forced_transfers: Mutex<HashMap<Uuid, Instant>>,
+}
+
+struct Seat {
+    id: Uuid,
+    machine_id: String,
+    machine_label: Option<String>,
+    last_seen: u64,
+}
+
+impl Seat {
+    fn info(&self) -> Value {
+        json!({ "id": self.id, "machine_label": self.machine_label, "last_seen": self.last_seen })
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+pub struct MockServer {
+    addr: SocketAddr,
+    state: Arc<MockState>,
+    shutdown: oneshot::Sender<()>,
+    handle: JoinHandle<()>,
+}
+
+impl MockServer {
+    pub async fn start(config: MockConfig) -> std::io::Result<Self> {
+        let listener = TcpListener::bind((config.host, config.port))?;
+        listener.set_nonblocking(true)?;
+        let state = Arc::new(MockState {
+            config,
+            requests: AtomicUsize::new(0),
+            in_flight: AtomicUsize::new(0),
+            peak_in_flight: AtomicUsize::new(0),
+            contexts: Mutex::new(Vec::new()),
+            last_headers: Mutex::new(None),
+            activations: Mutex::new(HashMap::new()),
+            forced_transfers: Mutex::new(HashMap::new()),
+        });
+        let service_state = state.clone();
+        let server = Server::from_tcp(listener)
+            .map_err(std::io::Error::other)?
+            .serve(make_service_fn(move |_| {
+                let state = service_state.clone();
+                async move {
+                    Ok::<_, Infallible>(service_fn(move |req| handle(state.clone(), req)))
+                }
+            }));
+        let addr = server.local_addr();
+        let (shutdown, rx) = oneshot::channel::<()>();
+        let handle = tokio::spawn(async move {
+            let _ = server
+                .with_graceful_shutdown(async {
+                    let _ = rx.await;
+                })
+                .await;
+        });
+        Ok(Self {
+            addr,
+            state,
+            shutdown,
+            handle,
+        })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    pub fn request_count(&self) -> usize {
+        self.state.requests.load(Ordering::SeqCst)
+    }
+
+    pub fn peak_concurrency(&self) -> usize {
+        self.state.peak_in_flight.load(Ordering::SeqCst)
+    }
+
+    pub fn received_contexts(&self) -> Vec<Value> {
+        self.state.contexts.lock().unwrap().clone()
+    }
+
+    pub fn last_headers(&self) -> Option<HeaderMap> {
+        self.state.last_headers.lock().unwrap().clone()
+    }
+
+    pub async fn stop(self) {
+        let _ = self.shutdown.send(());
+        let _ = self.handle.await;
+    }
+}
+
+async fn handle(state: Arc<MockState>, req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let index = state.requests.fetch_add(1, Ordering::SeqCst);
+    *state.last_headers.lock().unwrap() = Some(req.headers().clone());
+    let in_flight = state.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+    state.peak_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+    if !state.config.latency.is_zero() {
+        tokio::time::sleep(state.config.latency).await;
+    }
+    let response = match index < state.config.fail_first {
+        true => plain(
+            StatusCode::SERVICE_UNAVAILABLE,
+            json!({ "error": "Service unavailable" }),
+        ),
+        false => route(&state, req).await,
+    };
+    state.in_flight.fetch_sub(1, Ordering::SeqCst);
+    Ok(response)
+}
+
+async fn route(state: &MockState, req: Request<Body>) -> Response<Body> {
+    let method = req.method().clone();
+    let path = req.uri().path().trim_matches('/').to_string();
+    let segments: Vec<&str> = path.split('/').collect();
+    match (method, segments.as_slice()) {
+        (Method::GET, ["capabilities"]) if !state.config.legacy => capabilities(state).await,
+        (Method::GET, ["subscriptions", "validateapp", license, application]) => {
+            match license.parse::<LicenseId>() {
+                Ok(license) => {
+                    validate(state, req.headers(), license.identity(), application).await
+                }
+                Err(e) => plain(StatusCode::BAD_REQUEST, json!({ "error": e.to_string() })),
+            }
+        }
+        (Method::POST, ["subscriptions", "validateapp", license, application])
+            if !state.config.legacy =>
+        {
+            match license.parse::<LicenseId>() {
+                Ok(license) => {
+                    let license = license.identity();
+                    let (parts, body) = req.into_parts();
+                    match decrypt_body(license, body).await {
+                        Ok(context) => {
+                            state.contexts.lock().unwrap().push(context);
+                            validate(state, &parts.headers, license, application).await
+                        }
+                        Err(e) => {
+                            encrypted(
+                                license,
+                                StatusCode::BAD_REQUEST,
+                                &json!({ "error": e.to_string() }).to_string(),
+                            )
+                            .await
+                        }
+                    }
+                }
+                Err(e) => plain(StatusCode::BAD_REQUEST, json!({ "error": e.to_string() })),
+            }
+        }
+        (method, ["subscriptions", action, license, application])
+            if matches!(*action, "activate" | "heartbeat" | "transfer") && !state.config.legacy =>
+        {
+            let Ok(license) = license.parse::<LicenseId>() else {
+                return plain(StatusCode::BAD_REQUEST, json!({ "error": "Invalid license" }));
+            };
+            let license = license.identity();
+            let (parts, body) = req.into_parts();
+            let body = match decrypt_body(license, body).await {
+                Ok(body) => body,
+                Err(e) => {
+                    let error = json!({ "error": e.to_string() }).to_string();
+                    return encrypted(license, StatusCode::BAD_REQUEST, &error).await;
+                }
+            };
+            match (method, *action) {
+                (Method::POST, "activate") => {
+                    activate(state, &parts.headers, license, application, &body).await
+                }
+                (Method::DELETE, "activate") => {
+                    deactivate(state, &parts.headers, license, &body).await
+                }
+                (Method::POST, "heartbeat") => {
+                    heartbeat(state, &parts.headers, license, &body).await
+                }
+                (Method::POST, "transfer") => {
+                    transfer(state, &parts.headers, license, &body).await
+                }
+                _ => plain(StatusCode::NOT_FOUND, json!({ "error": "Not found" })),
+            }
+        }
+        (Method::GET, ["subscriptions", "seats", license]) => {
+            match license.parse::<LicenseId>() {
+                Ok(license) => seats(state, req.headers(), license.identity()).await,
+                Err(e) => plain(StatusCode::BAD_REQUEST, json!({ "error": e.to_string() })),
+            }
+        }
+        // Legacy servers only route the GET, like any web framework they refuse the POST.
+        (Method::POST, ["subscriptions", "validateapp", _, _]) => plain(
+            StatusCode::METHOD_NOT_ALLOWED,
+            json!({ "error": "Method not allowed" }),
+        ),
+        _ => plain(StatusCode::NOT_FOUND, json!({ "error": "Not found" })),
+    }
+}
+
+async fn decrypt_body(id: Uuid, body: Body) -> anyhow::Result<Value> {
+    let body = hyper::body::to_bytes(body).await?;
+    let body = VERSION
+        .encryptor()
+        .decrypt(id, std::str::from_utf8(&body)?)
+        .await?;
+    Ok(serde_json::from_str(&body)?)
+}
+
+async fn validate(
+    state: &MockState,
+    headers: &HeaderMap,
+    license: Uuid,
+    application: &str,
+) -> Response<Body> {
+    if !authorized(headers, license).await {
+        return unauthorized(license).await;
+    }
+    let scenario = Scenario::from_license(license).unwrap_or(state.config.default_scenario);
+    match scenario {
+        Scenario::Valid => {
+            encrypted(
+                license,
+                StatusCode::OK,
+                &json!({
+                    "success": format!("License validated for {}", application),
+                    "token": state.config.token,
+                    "experimental": { "seats": 5 },
+                })
+                .to_string(),
+            )
+            .await
+        }
+        Scenario::Expired => {
+            encrypted(
+                license,
+                StatusCode::GONE,
+                &json!({
+                    "error": "License has expired",
+                    "portal_url": "https://portal.example.com/renew",
+                })
+                .to_string(),
+            )
+            .await
+        }
+        Scenario::RateLimited => {
+            let mut response = encrypted(
+                license,
+                StatusCode::TOO_MANY_REQUESTS,
+                &json!({ "error": "Too many requests" }).to_string(),
+            )
+            .await;
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, HeaderValue::from_static("30"));
+            response
+        }
+        Scenario::Malformed => encrypted(license, StatusCode::OK, "{\"token\": ").await,
+        Scenario::Empty => response(StatusCode::OK, Body::empty()),
+        Scenario::EmptyError => response(StatusCode::INTERNAL_SERVER_ERROR, Body::empty()),
+        // A server that dropped our protocol version cannot encrypt the answer.
+        Scenario::ClientTooOld => plain(
+            StatusCode::UPGRADE_REQUIRED,
+            json!({
+                "error": "Unsupported protocol version",
+                "code": "unsupported_version",
+                "min_version": 2,
+                "download_url": "https://example.com/download",
+            }),
+        ),
+        Scenario::Unpaid => {
+            encrypted(
+                license,
+                StatusCode::PAYMENT_REQUIRED,
+                &json!({
+                    "error": "License payment is overdue",
+                    "code": "license_expired",
+                    "portal_url": "https://portal.example.com/renew",
+                })
+                .to_string(),
+            )
+            .await
+        }
+        Scenario::NotFound => {
+            encrypted(
+                license,
+                StatusCode::NOT_FOUND,
+                &json!({ "error": "License not found", "code": "license_not_found" }).to_string(),
+            )
+            .await
+        }
+        Scenario::UnauthorizedApp => {
+            encrypted(
+                license,
+                StatusCode::FORBIDDEN,
+                &json!({
+                    "error": format!("License does not cover {}", application),
+                    "code": "application_unauthorized",
+                })
+                .to_string(),
+            )
+            .await
+        }
+        // A proxy in front of the server answers on its own, in HTML.
+        Scenario::BadGateway => response(
+            StatusCode::BAD_GATEWAY,
+            Body::from("<html><body><h1>502 Bad Gateway</h1></body></html>"),
+        ),
+    }
+}
+
+// Capabilities are not about a license, so the answer is encrypted for the nil id.
+async fn capabilities(state: &MockState) -> Response<Body> {
+    let mut capabilities = vec![
+        Capability::ValidateContext.name(),
+        Capability::Activation.name(),
+        Capability::Transfer.name(),
+    ];
+    if state.config.seats.is_some() {
+        capabilities.push(Capability::Seats.name());
+    }
+    encrypted(
+        Uuid::nil(),
+        StatusCode::OK,
+        &json!({ "server_version": "mock", "capabilities": capabilities }).to_string(),
+    )
+    .await
+}
+
+// Activation tokens name the machine, so a heartbeat knows which seat it is for.
+async fn activated(state: &MockState, license: Uuid, seat: (Uuid, String)) -> Response<Body> {
+    let (id, machine_id) = seat;
+    encrypted(
+        license,
+        StatusCode::OK,
+        &json!({
+            "token": format!("{}:{}", state.config.token, machine_id),
+            "heartbeat_interval": 300,
+            "activation_id": id,
+        })
+        .to_string(),
+    )
+    .await
+}
+
+async fn machine_not_activated(license: Uuid) -> Response<Body> {
+    encrypted(
+        license,
+        StatusCode::NOT_FOUND,
+        &json!({ "error": "Machine is not activated", "code": "machine_not_activated" })
+            .to_string(),
+    )
+    .await
+}
+
+async fn activate(
+    state: &MockState,
+    headers: &HeaderMap,
+    license: Uuid,
+    application: &str,
+    body: &Value,
+) -> Response<Body> {
+    if !authorized(headers, license).await {
+        return unauthorized(license).await;
+    }
+    // Other scenarios reject activations the way they reject validations.
+    let scenario = Scenario::from_license(license).unwrap_or(state.config.default_scenario);
+    if scenario != Scenario::Valid {
+        return validate(state, headers, license, application).await;
+    }
+    let Some(machine_id) = body["machine_id"].as_str() else {
+        let error = json!({ "error": "Missing machine_id" }).to_string();
+        return encrypted(license, StatusCode::BAD_REQUEST, &error).await;
+    };
+    let machine_label = body["machine_label"].as_str().map(str::to_string);
+    let limit = state.config.seats.map_or(usize::MAX, |(_, total)| total as usize);
+    let taken = {
+        let mut activations = state.activations.lock().unwrap();
+        let seats = activations.entry(license).or_default();
+        match seats.iter().position(|seat| seat.machine_id == machine_id) {
+            Some(held) => {
+                let seat = &mut seats[held];
+                seat.machine_label = machine_label;
+                seat.last_seen = now();
+                Ok((seat.id, seat.machine_id.clone()))
+            }
+            None if seats.len() >= limit => Err(seats.iter().map(Seat::info).collect::<Vec<_>>()),
+            None => {
+                let seat = Seat {
+                    id: Uuid::new_v4(),
+                    machine_id: machine_id.to_string(),
+                    machine_label,
+                    last_seen: now(),
+                };
+                let taken = (seat.id, seat.machine_id.clone());
+                seats.push(seat);
+                Ok(taken)
+            }
+        }
+    };
+    match taken {
+        Ok(seat) => activated(state, license, seat).await,
+        Err(seats) => {
+            encrypted(
+                license,
+                StatusCode::CONFLICT,
+                &json!({
+                    "error": "Seat limit reached",
+                    "code": "seat_limit_reached",
+                    "seats": seats,
+                })
+                .to_string(),
+            )
+            .await
+        }
+    }
+}
+
+async fn deactivate(
+    state: &MockState,
+    headers: &HeaderMap,
+    license: Uuid,
+    body: &Value,
+) -> Response<Body> {
+    if !authorized(headers, license).await {
+        return unauthorized(license).await;
+    }
+    // Machines free their own seat, anyone may free a seat by its activation id.
+    let held = |seat: &Seat| match body["activation_id"].as_str() {
+        Some(id) => seat.id.to_string() == id,
+        None => body["machine_id"].as_str() == Some(seat.machine_id.as_str()),
+    };
+    let removed = state
+        .activations
+        .lock()
+        .unwrap()
+        .get_mut(&license)
+        .is_some_and(|seats| {
+            let before = seats.len();
+            seats.retain(|seat| !held(seat));
+            seats.len() < before
+        });
+    match removed {
+        true => {
+            encrypted(
+                license,
+                StatusCode::OK,
+                &json!({ "success": "Machine deactivated" }).to_string(),
+            )
+            .await
+        }
+        false => machine_not_activated(license).await,
+    }
+}
+
+async fn heartbeat(
+    state: &MockState,
+    headers: &HeaderMap,
+    license: Uuid,
+    body: &Value,
+) -> Response<Body> {
+    if !authorized(headers, license).await {
+        return unauthorized(license).await;
+    }
+    let machine_id = body["token"]
+        .as_str()
+        .and_then(|token| token.rsplit_once(':'))
+        .map(|(_, machine_id)| machine_id);
+    let held = machine_id.and_then(|machine_id| {
+        let mut activations = state.activations.lock().unwrap();
+        let seat = activations
+            .get_mut(&license)?
+            .iter_mut()
+            .find(|seat| seat.machine_id == machine_id)?;
+        seat.last_seen = now();
+        Some((seat.id, seat.machine_id.clone()))
+    });
+    match held {
+        Some(seat) => activated(state, license, seat).await,
+        None => machine_not_activated(license).await,
+    }
+}
+
+// Hands the seat of `from_activation` to another machine. Any transfer of a known seat
+// is allowed, forced ones at most once per `transfer_cooldown`.
+async fn transfer(
+    state: &MockState,
+    headers: &HeaderMap,
+    license: Uuid,
+    body: &Value,
+) -> Response<Body> {
+    if !authorized(headers, license).await {
+        return unauthorized(license).await;
+    }
+    let (Some(from), Some(machine_id)) = (
+        body["from_activation"].as_str().and_then(|id| id.parse::<Uuid>().ok()),
+        body["machine_id"].as_str(),
+    ) else {
+        let error = json!({ "error": "Missing from_activation or machine_id" }).to_string();
+        return encrypted(license, StatusCode::BAD_REQUEST, &error).await;
+    };
+    let cooldown = match body["force"].as_bool() == Some(true) {
+        true => {
+            let mut forced = state.forced_transfers.lock().unwrap();
+            let wait = forced.get(&license).and_then(|last| {
+                state.config.transfer_cooldown.checked_sub(last.elapsed())
+            });
+            if wait.is_none() {
+                forced.insert(license, Instant::now());
+            }
+            wait
+        }
+        false => None,
+    };
+    if let Some(wait) = cooldown {
+        let mut response = encrypted(
+            license,
+            StatusCode::TOO_MANY_REQUESTS,
+            &json!({ "error": "Transfer cooldown", "code": "transfer_cooldown" }).to_string(),
+        )
+        .await;
+        let seconds = wait.as_secs().max(1).to_string();
+        if let Ok(value) = HeaderValue::from_str(&seconds) {
+            response.headers_mut().insert(RETRY_AFTER, value);
+        }
+        return response;
+    }
+    let moved = {
+        let mut activations = state.activations.lock().unwrap();
+        let seats = activations.entry(license).or_default();
+        match seats.iter().position(|seat| seat.id == from) {
+            Some(held) => {
+                seats.remove(held);
+                seats.retain(|seat| seat.machine_id != machine_id);
+                let seat = Seat {
+                    id: Uuid::new_v4(),
+                    machine_id: machine_id.to_string(),
+                    machine_label: body["machine_label"].as_str().map(str::to_string),
+                    last_seen: now(),
+                };
+                let moved = (seat.id, seat.machine_id.clone());
+                seats.push(seat);
+                Some(moved)
+            }
+            None => None,
+        }
+    };
+    match moved {
+        Some(seat) => activated(state, license, seat).await,
+        None => machine_not_activated(license).await,
+    }
+}
+
+async fn seats(state: &MockState, headers: &HeaderMap, license: Uuid) -> Response<Body> {
+    if !authorized(headers, license).await {
+        return unauthorized(license).await;
+    }
+    match state.config.seats {
+        Some((used, total)) => {
+            let samples: Vec<Value> = (1..=used)
+                .map(|used| json!({ "timestamp": 1_700_000_000 + 3600 * used as u64, "used": used }))
+                .collect();
+            encrypted(
+                license,
+                StatusCode::OK,
+                &json!({ "used": used, "total": total, "samples": samples }).to_string(),
+            )
+            .await
+        }
+        None => plain(StatusCode::NOT_FOUND, json!({ "error": "Not found" })),
+    }
+}
+
+// The client encrypts the license id into the Authorization header, so a header that
+// does not decrypt to the license in the path is rejected like on the real server.
+async fn authorized(headers: &HeaderMap, license: Uuid) -> bool {
+    let versioned = headers
+        .get(VERSION_STR)
+        .is_some_and(|h| h == "v1");
+    let Some(header) = headers.get(AUTHORIZATION).and_then(|h| h.to_str().ok()) else {
+        return false;
+    };
+    versioned
+        && VERSION
+            .encryptor()
+            .decrypt(license, header)
+            .await
+            .is_ok_and(|id| id == license.to_string())
+}
+
+async fn unauthorized(license: Uuid) -> Response<Body> {
+    encrypted(
+        license,
+        StatusCode::UNAUTHORIZED,
+        &json!({ "error": "Missing or invalid authorization headers" }).to_string(),
+    )
+    .await
+}
+
+async fn encrypted(id: Uuid, status: StatusCode, body: &str) -> Response<Body> {
+    match VERSION.encryptor().encrypt(id, body).await {
+        Ok(body) => response(status, Body::from(body)),
+        Err(e) => plain(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            json!({ "error": e.to_string() }),
+        ),
+    }
+}
+
+fn plain(status: StatusCode, body: Value) -> Response<Body> {
+    response(status, Body::from(body.to_string()))
+}
+
+fn response(status: StatusCode, body: Body) -> Response<Body> {
+    let mut response = Response::new(body);
+    *response.status_mut() = status;
+    response
+}
@@ -0,0 +1,48 @@
+use std::{error::Error, time::Duration};
+
+use chipa_license_validator::mock::{MockConfig, MockServer, Scenario};
+
+const USAGE: &str = "Usage: chipa-mock-server [--host <ip>] [--port <port>] [--scenario <valid|expired|rate-limited|malformed|empty|empty-error|client-too-old|unpaid|not-found|unauthorized-app|bad-gateway>] [--token <token>] [--fail-first <n>] [--latency <ms>] [--legacy] [--seats <n>] [--no-seats]";
+
+fn parse_args() -> Result<MockConfig, Box<dyn Error>> {
+    let mut config = MockConfig::default();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("Missing value for '{}'\n{}", arg, USAGE));
+        match arg.as_str() {
+            "--host" => config.host = value()?.parse()?,
+            "--port" => config.port = value()?.parse()?,
+            "--scenario" => config.default_scenario = value()?.parse::<Scenario>()?,
+            "--token" => config.token = value()?,
+            "--fail-first" => config.fail_first = value()?.parse()?,
+            "--latency" => config.latency = Duration::from_millis(value()?.parse()?),
+            "--legacy" => config.legacy = true,
+            "--seats" => config.seats = Some((0, value()?.parse()?)),
+            "--no-seats" => config.seats = None,
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+            _ => return Err(format!("Unknown argument '{}'\n{}", arg, USAGE).into()),
+        }
+    }
+    Ok(config)
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let config = parse_args()?;
+    let server = MockServer::start(config).await?;
+    println!("Listening on {}", server.url());
+    let width = Scenario::ALL
+        .iter()
+        .map(|scenario| scenario.to_string().len())
+        .max()
+        .unwrap_or_default();
+    for scenario in Scenario::ALL {
+        println!("  {:<width$} {}", scenario.to_string(), scenario.license());
+    }
+    tokio::signal::ctrl_c().await?;
+    server.stop().await;
+    Ok(())
+}
@@ -0,0 +1,356 @@
+//! Slow operation detection.
+//!
+//! Validations and `ChipaFile` loads and saves are timed; the ones slower than
+//! [`slow_threshold`] are kept for [`PerformanceReport::collect`] and handed to the
+//! observer set with [`set_slow_op_observer`], with [`EnvironmentHints`] gathered at
+//! that moment. Nothing beyond reading the clock happens for fast operations.
+//!
+//! Key derivation runs inside the encryptor and counts towards the load or save that
+//! needed it; timing it on its own is left for when the encryptor exposes it.
+
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, RwLock,
+    },
+    time::Duration,
+};
+
+use serde::Serialize;
+
+/// How many slow operations `PerformanceReport::collect` keeps, oldest dropped first.
+pub const SLOW_OP_CAPACITY: usize = 32;
+
+const DEFAULT_SLOW_THRESHOLD: Duration = Duration::from_secs(1);
+
+static THRESHOLD_MICROS: AtomicU64 = AtomicU64::new(DEFAULT_SLOW_THRESHOLD.as_micros() as u64);
+static SLOW_OPS: SlowOpLog = SlowOpLog::new();
+static OBSERVER: RwLock<Option<SlowOpObserver>> = RwLock::new(None);
+
+// Saves smaller than this finish too fast for their throughput to say much about the disk.
+#[cfg(any(test, not(target_arch = "wasm32")))]
+const SLOW_DISK_MIN_BYTES: usize = 1 << 20;
+#[cfg(any(test, not(target_arch = "wasm32")))]
+const SLOW_DISK_BYTES_PER_SEC: f64 = 20_000_000.0;
+
+/// Called with every slow operation, on the thread that ran it.
+pub type SlowOpObserver = Box<dyn Fn(&SlowOp) + Send + Sync>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    Validate,
+    FileLoad,
+    FileSave,
+}
+
+impl Operation {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Validate => "validate",
+            Self::FileLoad => "file_load",
+            Self::FileSave => "file_save",
+        }
+    }
+}
+
+/// An operation that took longer than `slow_threshold()`. `payload_bytes` is the size
+/// of the response body for validations, of the encrypted file for loads and saves,
+/// and of the body for streamed loads and saves.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SlowOp {
+    pub operation: Operation,
+    pub duration: Duration,
+    pub payload_bytes: Option<usize>,
+    pub hints: EnvironmentHints,
+}
+
+/// What the machine looked like when an operation was slow. `None` means unknown.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct EnvironmentHints {
+    /// Running on battery, which often throttles the CPU. Only known on Linux with the
+    /// `fs` feature.
+    pub on_battery: Option<bool>,
+    /// A save of at least 1 MiB wrote under 20 MB/s, as spinning and network disks do.
+    /// Unknown for other operations and smaller saves.
+    pub slow_disk: Option<bool>,
+}
+
+impl SlowOp {
+    /// Bytes per second, a hint for slow disks when it is low on a large file.
+    pub fn throughput(&self) -> Option<f64> {
+        let bytes = self.payload_bytes?;
+        let secs = self.duration.as_secs_f64();
+        (secs > 0.0).then(|| bytes as f64 / secs)
+    }
+}
+
+/// The slow operations recorded by this process, for a support bundle or a log line.
+///
+/// ```
+/// use chipa_license_validator::{set_slow_threshold, PerformanceReport};
+/// use std::time::Duration;
+///
+/// set_slow_threshold(Duration::from_millis(250));
+/// let report = PerformanceReport::collect();
+/// assert_eq!(report.threshold, Duration::from_millis(250));
+/// for op in &report.slow_ops {
+///     eprintln!("{} took {:?}", op.operation.kind(), op.duration);
+/// }
+/// let bundle = serde_json::to_string(&report).unwrap();
+/// # let _ = bundle;
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PerformanceReport {
+    pub threshold: Duration,
+    pub slow_ops: Vec<SlowOp>,
+}
+
+impl PerformanceReport {
+    /// Returns the last `SLOW_OP_CAPACITY` slow operations, oldest first.
+    pub fn collect() -> Self {
+        Self {
+            threshold: slow_threshold(),
+            slow_ops: SLOW_OPS.snapshot(),
+        }
+    }
+}
+
+/// Hands every slow operation to `observer` as it is recorded, e.g. to log a warning
+/// with the application's logger. `None` removes the observer.
+///
+/// ```
+/// use chipa_license_validator::set_slow_op_observer;
+///
+/// set_slow_op_observer(Some(Box::new(|op| {
+///     eprintln!("slow {} took {:?}, {:?}", op.operation.kind(), op.duration, op.hints);
+/// })));
+/// # set_slow_op_observer(None);
+/// ```
+pub fn set_slow_op_observer(observer: Option<SlowOpObserver>) {
+    *OBSERVER.write().unwrap_or_else(|e| e.into_inner()) = observer;
+}
+
+/// Sets how long validations and `ChipaFile` loads and saves may take before they are
+/// recorded as slow. Defaults to one second.
+pub fn set_slow_threshold(threshold: Duration) {
+    let micros = u64::try_from(threshold.as_micros()).unwrap_or(u64::MAX);
+    THRESHOLD_MICROS.store(micros, Ordering::Relaxed);
+}
+
+pub fn slow_threshold() -> Duration {
+    Duration::from_micros(THRESHOLD_MICROS.load(Ordering::Relaxed))
+}
+
+struct SlowOpLog {
+    ops: Mutex<VecDeque<SlowOp>>,
+}
+
+impl SlowOpLog {
+    const fn new() -> Self {
+        Self {
+            ops: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    #[cfg(any(test, not(target_arch = "wasm32")))]
+    fn record(&self, op: SlowOp) {
+        let mut ops = self.ops.lock().unwrap_or_else(|e| e.into_inner());
+        if ops.len() == SLOW_OP_CAPACITY {
+            ops.pop_front();
+        }
+        ops.push_back(op);
+    }
+
+    fn snapshot(&self) -> Vec<SlowOp> {
+        let ops = self.ops.lock().unwrap_or_else(|e| e.into_inner());
+        ops.iter().cloned().collect()
+    }
+}
+
+// `Instant::now` panics on wasm32-unknown-unknown, so nothing is timed there.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) struct Timer(std::time::Instant);
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Timer {
+    pub(crate) fn start() -> Self {
+        Self(std::time::Instant::now())
+    }
+
+    // Only takes the lock when the operation was slow.
+    pub(crate) fn finish(self, operation: Operation, payload_bytes: Option<usize>) {
+        let duration = self.0.elapsed();
+        if duration >= slow_threshold() {
+            let op = slow_op(operation, duration, payload_bytes);
+            if let Some(observer) = &*OBSERVER.read().unwrap_or_else(|e| e.into_inner()) {
+                observer(&op);
+            }
+            SLOW_OPS.record(op);
+        }
+    }
+}
+
+#[cfg(any(test, not(target_arch = "wasm32")))]
+fn slow_op(operation: Operation, duration: Duration, payload_bytes: Option<usize>) -> SlowOp {
+    let mut op = SlowOp {
+        operation,
+        duration,
+        payload_bytes,
+        hints: EnvironmentHints {
+            on_battery: on_battery(),
+            slow_disk: None,
+        },
+    };
+    if operation == Operation::FileSave && payload_bytes.is_some_and(|b| b >= SLOW_DISK_MIN_BYTES) {
+        op.hints.slow_disk = op.throughput().map(|t| t < SLOW_DISK_BYTES_PER_SEC);
+    }
+    op
+}
+
+#[cfg(all(feature = "fs", target_os = "linux"))]
+fn on_battery() -> Option<bool> {
+    let supplies = std::fs::read_dir("/sys/class/power_supply").ok()?;
+    let discharging = supplies.flatten().any(|supply| {
+        let read = |name: &str| std::fs::read_to_string(supply.path().join(name)).ok();
+        read("type").is_some_and(|t| t.trim() == "Battery")
+            && read("status").is_some_and(|s| s.trim() == "Discharging")
+    });
+    Some(discharging)
+}
+
+#[cfg(all(
+    any(test, not(target_arch = "wasm32")),
+    not(all(feature = "fs", target_os = "linux"))
+))]
+fn on_battery() -> Option<bool> {
+    None
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) struct Timer;
+
+#[cfg(target_arch = "wasm32")]
+impl Timer {
+    pub(crate) fn start() -> Self {
+        Self
+    }
+
+    pub(crate) fn finish(self, _operation: Operation, _payload_bytes: Option<usize>) {}
+}
+
+// The threshold and observer are global, so tests that change them take turns. Runs
+// `run` with `threshold` and returns the slow operations it timed on this thread.
+#[cfg(test)]
+pub(crate) fn observe_slow_ops(threshold: Duration, run: impl FnOnce()) -> Vec<SlowOp> {
+    use std::sync::{Arc, PoisonError};
+
+    static EXCLUSIVE: Mutex<()> = Mutex::new(());
+    let _exclusive = EXCLUSIVE.lock().unwrap_or_else(PoisonError::into_inner);
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let me = std::thread::current().id();
+    let sink = seen.clone();
+    set_slow_op_observer(Some(Box::new(move |op| {
+        if std::thread::current().id() == me {
+            sink.lock().unwrap_or_else(PoisonError::into_inner).push(op.clone());
+        }
+    })));
+    let previous = slow_threshold();
+    set_slow_threshold(threshold);
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(run));
+    set_slow_threshold(previous);
+    set_slow_op_observer(None);
+    if let Err(panic) = result {
+        std::panic::resume_unwind(panic);
+    }
+    let ops = seen.lock().unwrap_or_else(PoisonError::into_inner).clone();
+    ops
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        fs::{ChipaFs, MemoryFs},
+        ChipaFile, Version,
+    };
+
+    fn op(bytes: usize) -> SlowOp {
+        SlowOp {
+            operation: Operation::FileLoad,
+            duration: Duration::from_millis(500),
+            payload_bytes: Some(bytes),
+            hints: EnvironmentHints::default(),
+        }
+    }
+
+    #[test]
+    fn test_slow_op_log_keeps_the_latest() {
+        let log = SlowOpLog::new();
+        for bytes in 0..SLOW_OP_CAPACITY + 3 {
+            log.record(op(bytes));
+        }
+        let ops = log.snapshot();
+        assert_eq!(ops.len(), SLOW_OP_CAPACITY);
+        assert_eq!(ops[0], op(3));
+        assert_eq!(ops[SLOW_OP_CAPACITY - 1], op(SLOW_OP_CAPACITY + 2));
+    }
+
+    #[test]
+    fn test_throughput() {
+        assert_eq!(op(1000).throughput(), Some(2000.0));
+        let instant = SlowOp {
+            duration: Duration::ZERO,
+            ..op(1000)
+        };
+        assert_eq!(instant.throughput(), None);
+        let unsized_op = SlowOp {
+            payload_bytes: None,
+            ..op(1000)
+        };
+        assert_eq!(unsized_op.throughput(), None);
+    }
+
+    #[test]
+    fn test_file_operations_are_recorded() {
+        let fs = MemoryFs::new();
+        let file = ChipaFile::new(Version::LATEST, &"x".repeat(4093)).unwrap();
+        let ops = observe_slow_ops(Duration::ZERO, || {
+            file.save_with("perf.chipa", "key", &fs).unwrap();
+            ChipaFile::load_with("perf.chipa", "key", &fs).unwrap();
+        });
+
+        let size = fs.read("perf.chipa".as_ref()).unwrap().len();
+        let recorded: Vec<_> = ops.iter().map(|op| (op.operation, op.payload_bytes)).collect();
+        assert_eq!(
+            recorded,
+            vec![(Operation::FileSave, Some(size)), (Operation::FileLoad, Some(size))]
+        );
+    }
+
+    #[test]
+    fn test_slow_disk_hint() {
+        let save = |bytes, millis| {
+            slow_op(Operation::FileSave, Duration::from_millis(millis), Some(bytes))
+                .hints
+                .slow_disk
+        };
+        assert_eq!(save(SLOW_DISK_MIN_BYTES * 4, 1000), Some(true));
+        assert_eq!(save(SLOW_DISK_MIN_BYTES * 100, 1000), Some(false));
+        assert_eq!(save(SLOW_DISK_MIN_BYTES - 1, 1000), None);
+        let load = slow_op(Operation::FileLoad, Duration::from_secs(1), Some(SLOW_DISK_MIN_BYTES));
+        assert_eq!(load.hints.slow_disk, None);
+    }
+
+    #[test]
+    fn test_observer_sees_slow_ops() {
+        let ops = observe_slow_ops(DEFAULT_SLOW_THRESHOLD, || {
+            Timer::start().finish(Operation::FileLoad, None);
+            let slow = Timer(std::time::Instant::now() - DEFAULT_SLOW_THRESHOLD);
+            slow.finish(Operation::Validate, None);
+        });
+        let seen: Vec<_> = ops.iter().map(|op| op.operation).collect();
+        assert_eq!(seen, vec![Operation::Validate]);
+    }
+}
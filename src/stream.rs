@@ -0,0 +1,886 @@
+//! Chunked `.chipa` layout for bodies too large to hold in memory.
+//!
+//! A streamed file starts with the usual 2-byte version prefix, with `STREAM_FLAG`
+//! set so older readers reject it instead of misreading it, followed by a random
+//! 16-byte file id. Then come the chunks: a 4-byte big-endian length and the chunk,
+//! encrypted on its own with the version's encryptor. A decrypted chunk holds the
+//! file id, its 8-byte index, a last-chunk flag and up to `CHUNK_SIZE` bytes of the
+//! body, so chunks cannot be dropped, reordered or copied from another file without
+//! the load failing.
+//!
+//! `ChipaFile::save` and `to_bytes` write serialized values in this layout too, as a
+//! body like any other. The pre-streaming layout is still read, see `legacy`.
+//!
+//! [`ChipaReader`] reads a streamed file chunk by chunk, for bodies that should not be
+//! decrypted into memory in one piece.
+
+use std::{
+    io::{self, Read, Seek, SeekFrom, Write},
+    iter,
+};
+#[cfg(feature = "fs")]
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+};
+
+use bytes::{Buf, Bytes};
+use serde::de::DeserializeOwned;
+use uuid::Uuid;
+
+use crate::{
+    encryption::{ChipaError, ChipaFile, ChipaResult},
+    version::{Encryptor, Version},
+};
+#[cfg(feature = "fs")]
+use crate::{
+    fs::replace_file,
+    perf::{Operation, Timer},
+};
+
+// Versions are small numbers, the top bit of the prefix is free to mark the layout.
+pub(crate) const STREAM_FLAG: u16 = 0x8000;
+
+/// Bytes of body per chunk of a streamed `.chipa` file. Streaming holds a few chunks in
+/// memory at a time, whatever the size of the body.
+pub const CHUNK_SIZE: usize = 1024 * 1024;
+
+// Bounds the allocation for a chunk whose length prefix is corrupted.
+const MAX_SEALED_CHUNK: usize = 2 * CHUNK_SIZE;
+const FILE_ID_LEN: usize = 16;
+const CHUNK_HEADER: usize = FILE_ID_LEN + 8 + 1;
+
+impl ChipaFile {
+    /// Encrypts everything `reader` yields into `writer` in the streamed layout and
+    /// returns the size of the body. Unlike `new` the body is raw bytes, not a
+    /// serialized value, and is never held in memory as a whole.
+    ///
+    /// ```
+    /// use chipa_license_validator::{ChipaFile, Version};
+    ///
+    /// let weights = vec![7u8; 3 * 1024 * 1024];
+    /// let mut encrypted = Vec::new();
+    /// ChipaFile::encrypt_stream(Version::LATEST, "secret", weights.as_slice(), &mut encrypted)?;
+    ///
+    /// let mut decrypted = Vec::new();
+    /// let size = ChipaFile::decrypt_stream("secret", encrypted.as_slice(), &mut decrypted)?;
+    /// assert_eq!(size, weights.len() as u64);
+    /// assert_eq!(decrypted, weights);
+    /// # Ok::<(), chipa_license_validator::ChipaError>(())
+    /// ```
+    pub fn encrypt_stream(
+        version: Version,
+        key: &str,
+        mut reader: impl Read,
+        mut writer: impl Write,
+    ) -> ChipaResult<u64> {
+        let file_id = Uuid::new_v4();
+        writer.write_all(&(STREAM_FLAG | u16::from(version)).to_be_bytes())?;
+        writer.write_all(file_id.as_bytes())?;
+        let encryptor = version.encryptor();
+        let mut chunk = read_chunk(&mut reader)?;
+        let mut index = 0u64;
+        let mut size = 0u64;
+        loop {
+            // A short chunk means the reader is exhausted.
+            let next = match chunk.len() {
+                CHUNK_SIZE => read_chunk(&mut reader)?,
+                _ => Vec::new(),
+            };
+            let last = next.is_empty();
+            let mut plain = Vec::with_capacity(CHUNK_HEADER + chunk.len());
+            plain.extend_from_slice(file_id.as_bytes());
+            plain.extend_from_slice(&index.to_be_bytes());
+            plain.push(u8::from(last));
+            plain.extend_from_slice(&chunk);
+            let sealed = encryptor
+                .encrypt_bytes(key, &Bytes::from(plain))
+                .map_err(ChipaError::Encryption)?;
+            writer.write_all(&(sealed.len() as u32).to_be_bytes())?;
+            writer.write_all(&sealed)?;
+            size += chunk.len() as u64;
+            if last {
+                break;
+            }
+            chunk = next;
+            index += 1;
+        }
+        writer.flush()?;
+        Ok(size)
+    }
+
+    /// Decrypts a file written by `encrypt_stream` or `save_stream` into `writer` and
+    /// returns the size of the body.
+    ///
+    /// Chunks are written as they are verified, so on error `writer` may already hold
+    /// the start of the body and should be discarded. A wrong key fails with
+    /// `ChipaError::Decryption`, a missing, reordered or foreign chunk with
+    /// `ChipaError::Tampered` or `ChipaError::InvalidFileFormat`.
+    pub fn decrypt_stream(key: &str, reader: impl Read, writer: impl Write) -> ChipaResult<u64> {
+        Self::decrypt_versioned(key, reader, writer).map(|(_, size)| size)
+    }
+
+    // `decrypt_stream`, also returning the version the file was written with.
+    pub(crate) fn decrypt_versioned(
+        key: &str,
+        mut reader: impl Read,
+        mut writer: impl Write,
+    ) -> ChipaResult<(Version, u64)> {
+        let (version, file_id) = read_header(&mut reader)?;
+        let encryptor = version.encryptor();
+        let mut index = 0u64;
+        let mut size = 0u64;
+        loop {
+            let sealed = read_sealed(&mut reader, index)?;
+            let (last, body) = open_chunk(encryptor, key, sealed, &file_id, index)?;
+            writer.write_all(&body)?;
+            size += body.len() as u64;
+            if last {
+                break;
+            }
+            index += 1;
+        }
+        if reader.read(&mut [0u8; 1])? != 0 {
+            return Err(ChipaError::Tampered(
+                "data follows the last chunk".to_string(),
+            ));
+        }
+        writer.flush()?;
+        Ok((version, size))
+    }
+
+    #[cfg(feature = "fs")]
+    /// Encrypts everything `reader` yields into a streamed `.chipa` file at `path` with
+    /// `Version::LATEST` and returns the size of the body. The file is replaced
+    /// atomically, like `save`. Read it back with `load_stream`; `load` reads it too but
+    /// holds the whole body in memory.
+    ///
+    /// ```
+    /// # #[cfg(all(feature = "test-util", feature = "fs"))]
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use chipa_license_validator::{test_util::TempDir, ChipaFile};
+    /// use std::fs::File;
+    ///
+    /// let dir = TempDir::new()?;
+    /// std::fs::write(dir.file("model.bin"), vec![1u8; 5 * 1024 * 1024])?;
+    ///
+    /// let path = dir.file("model.chipa");
+    /// ChipaFile::save_stream(&path, "secret", File::open(dir.file("model.bin"))?)?;
+    ///
+    /// let restored = File::create(dir.file("restored.bin"))?;
+    /// let size = ChipaFile::load_stream(&path, "secret", restored)?;
+    /// assert_eq!(size, 5 * 1024 * 1024);
+    /// # Ok(())
+    /// # }
+    /// # #[cfg(not(all(feature = "test-util", feature = "fs")))]
+    /// # fn main() {}
+    /// ```
+    pub fn save_stream(path: &str, key: &str, reader: impl Read) -> ChipaResult<u64> {
+        let timer = Timer::start();
+        let size = replace_file(&Self::chipa_path(path), |file| {
+            Self::encrypt_stream(Version::LATEST, key, reader, BufWriter::new(file))
+        })?;
+        timer.finish(Operation::FileSave, usize::try_from(size).ok());
+        Ok(size)
+    }
+
+    #[cfg(feature = "fs")]
+    /// Decrypts the streamed `.chipa` file at `path` into `writer` and returns the size
+    /// of the body. See `decrypt_stream` for the errors and for what `writer` holds
+    /// when one occurs.
+    pub fn load_stream(path: &str, key: &str, writer: impl Write) -> ChipaResult<u64> {
+        let timer = Timer::start();
+        let file = File::open(Self::existing_chipa_path(path)?)?;
+        let size = Self::decrypt_stream(key, BufReader::new(file), writer)?;
+        timer.finish(Operation::FileLoad, usize::try_from(size).ok());
+        Ok(size)
+    }
+}
+
+/// Reads a streamed `.chipa` file one chunk at a time, so a large body never has to be
+/// in memory as a whole.
+///
+/// Opening it reads the length prefix of every chunk and decrypts the last one, so a
+/// wrong key or a truncated file fail right away and `len` is known without decrypting
+/// the rest.
+///
+/// ```
+/// use std::io::Cursor;
+/// use chipa_license_validator::{ChipaFile, ChipaReader, Version, CHUNK_SIZE};
+///
+/// let table: Vec<u8> = (0..3 * CHUNK_SIZE).map(|i| i as u8).collect();
+/// let mut encrypted = Vec::new();
+/// ChipaFile::encrypt_stream(Version::LATEST, "secret", table.as_slice(), &mut encrypted)?;
+///
+/// let mut reader = ChipaReader::new("secret", Cursor::new(encrypted))?;
+/// assert_eq!(reader.len(), table.len() as u64);
+/// let segment = reader.read_range(CHUNK_SIZE as u64 - 10, 20)?;
+/// assert_eq!(segment, table[CHUNK_SIZE - 10..CHUNK_SIZE + 10]);
+/// # Ok::<(), chipa_license_validator::ChipaError>(())
+/// ```
+pub struct ChipaReader<R> {
+    reader: R,
+    key: String,
+    encryptor: Encryptor,
+    file_id: [u8; FILE_ID_LEN],
+    // Where the length prefix of each chunk starts.
+    chunks: Vec<u64>,
+    len: u64,
+}
+
+impl<R: Read + Seek> ChipaReader<R> {
+    pub fn new(key: &str, mut reader: R) -> ChipaResult<Self> {
+        let end = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(0))?;
+        let (version, file_id) = read_header(&mut reader)?;
+        let mut chunks = Vec::new();
+        let mut at = (2 + FILE_ID_LEN) as u64;
+        while at < end {
+            let mut len = [0u8; 4];
+            read_exact(&mut reader, &mut len, "File is truncated, the last chunk is missing")?;
+            chunks.push(at);
+            at += 4 + u64::from(u32::from_be_bytes(len));
+            if at > end {
+                return Err(ChipaError::InvalidFileFormat(
+                    "File is truncated inside a chunk".to_string(),
+                ));
+            }
+            reader.seek(SeekFrom::Start(at))?;
+        }
+        if chunks.is_empty() {
+            return Err(ChipaError::InvalidFileFormat(
+                "File is truncated, the last chunk is missing".to_string(),
+            ));
+        }
+        let mut file = Self {
+            reader,
+            key: key.to_string(),
+            encryptor: version.encryptor(),
+            file_id,
+            chunks,
+            len: 0,
+        };
+        let last = file.chunks.len() - 1;
+        file.len = last as u64 * CHUNK_SIZE as u64 + file.chunk(last)?.len() as u64;
+        Ok(file)
+    }
+
+    /// The size of the body.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Decrypts the `len` bytes of the body starting at `offset`, opening only the chunks
+    /// they fall in. Like `Read`, a range that runs past the end of the body stops there
+    /// and one that starts at or past it is empty; a zero-length range decrypts nothing.
+    pub fn read_range(&mut self, offset: u64, len: usize) -> ChipaResult<Bytes> {
+        let end = offset.saturating_add(len as u64).min(self.len);
+        if offset >= end {
+            return Ok(Bytes::new());
+        }
+        let first = (offset / CHUNK_SIZE as u64) as usize;
+        let last = ((end - 1) / CHUNK_SIZE as u64) as usize;
+        let mut range = Vec::with_capacity((end - offset) as usize);
+        for index in first..=last {
+            let chunk = self.chunk(index)?;
+            let start = index as u64 * CHUNK_SIZE as u64;
+            let from = offset.saturating_sub(start) as usize;
+            let to = (end - start).min(chunk.len() as u64) as usize;
+            if first == last {
+                return Ok(chunk.slice(from..to));
+            }
+            range.extend_from_slice(&chunk[from..to]);
+        }
+        Ok(Bytes::from(range))
+    }
+
+    /// Decodes the body as a sequence, e.g. a file saved from a `Vec<T>`, one element at
+    /// a time: memory holds a chunk and an element, never the whole sequence.
+    ///
+    /// An element that fails to decode, or a chunk that fails to decrypt, is yielded as
+    /// an error and ends the iteration. Each call starts over from the first element.
+    ///
+    /// ```
+    /// use std::io::Cursor;
+    /// use chipa_license_validator::{ChipaFile, ChipaReader, Version};
+    ///
+    /// let trades: Vec<(u64, f64)> = (0..100_000).map(|i| (i, i as f64 / 4.0)).collect();
+    /// let encrypted = ChipaFile::new(Version::LATEST, &trades)?.to_bytes("secret")?;
+    ///
+    /// let mut reader = ChipaReader::new("secret", Cursor::new(encrypted))?;
+    /// let mut total = 0.0;
+    /// for trade in reader.deserialize_seq::<(u64, f64)>() {
+    ///     total += trade?.1;
+    /// }
+    /// assert_eq!(total, trades.iter().map(|trade| trade.1).sum::<f64>());
+    /// # Ok::<(), chipa_license_validator::ChipaError>(())
+    /// ```
+    pub fn deserialize_seq<T: DeserializeOwned>(
+        &mut self,
+    ) -> impl Iterator<Item = ChipaResult<T>> + '_ {
+        let mut body = Body {
+            file: self,
+            next: 0,
+            chunk: Bytes::new(),
+            error: None,
+        };
+        // Elements left to decode, unknown until the sequence header is read.
+        let mut left = None;
+        iter::from_fn(move || {
+            let remaining = match left {
+                Some(remaining) => remaining,
+                None => match body.seq_len() {
+                    Ok(len) => len,
+                    Err(e) => {
+                        left = Some(0);
+                        return Some(Err(e));
+                    }
+                },
+            };
+            if remaining == 0 {
+                left = Some(0);
+                return None;
+            }
+            match rmp_serde::from_read(&mut body) {
+                Ok(element) => {
+                    left = Some(remaining - 1);
+                    Some(Ok(element))
+                }
+                Err(e) => {
+                    left = Some(0);
+                    Some(Err(body.failure(e.to_string())))
+                }
+            }
+        })
+    }
+
+    // Decrypts the chunk at `index` and returns its body.
+    fn chunk(&mut self, index: usize) -> ChipaResult<Bytes> {
+        self.reader.seek(SeekFrom::Start(self.chunks[index]))?;
+        let sealed = read_sealed(&mut self.reader, index as u64)?;
+        let (last, body) =
+            open_chunk(self.encryptor, &self.key, sealed, &self.file_id, index as u64)?;
+        // Offsets into the body assume every chunk but the last is full.
+        if index + 1 == self.chunks.len() {
+            if !last {
+                return Err(ChipaError::InvalidFileFormat(
+                    "File is truncated, the last chunk is missing".to_string(),
+                ));
+            }
+        } else if last {
+            return Err(ChipaError::Tampered(
+                "data follows the last chunk".to_string(),
+            ));
+        } else if body.len() != CHUNK_SIZE {
+            return Err(ChipaError::Tampered(format!("chunk {} is not full", index)));
+        }
+        Ok(body)
+    }
+}
+
+#[cfg(feature = "fs")]
+impl ChipaReader<BufReader<File>> {
+    /// Opens the streamed `.chipa` file at `path`.
+    pub fn open(path: &str, key: &str) -> ChipaResult<Self> {
+        let file = File::open(ChipaFile::existing_chipa_path(path)?)?;
+        Self::new(key, BufReader::new(file))
+    }
+}
+
+// The body of a `ChipaReader` as a `Read`, decrypting one chunk at a time.
+struct Body<'a, R> {
+    file: &'a mut ChipaReader<R>,
+    next: usize,
+    chunk: Bytes,
+    // Why the last read failed, if a chunk did not open.
+    error: Option<ChipaError>,
+}
+
+impl<R: Read + Seek> Body<'_, R> {
+    // Reads the header of the msgpack array the body holds.
+    fn seq_len(&mut self) -> ChipaResult<u64> {
+        let mut marker = [0u8; 1];
+        self.read_exact(&mut marker)
+            .map_err(|e| self.failure(e.to_string()))?;
+        let size = match marker[0] {
+            marker @ 0x90..=0x9f => return Ok(u64::from(marker & 0x0f)),
+            0xdc => 2,
+            0xdd => 4,
+            marker => {
+                return Err(ChipaError::Decode(format!(
+                    "expected a sequence, found marker 0x{:02x}",
+                    marker
+                )))
+            }
+        };
+        let mut len = [0u8; 4];
+        self.read_exact(&mut len[4 - size..])
+            .map_err(|e| self.failure(e.to_string()))?;
+        Ok(u64::from(u32::from_be_bytes(len)))
+    }
+
+    fn failure(&mut self, decode: String) -> ChipaError {
+        self.error.take().unwrap_or(ChipaError::Decode(decode))
+    }
+}
+
+impl<R: Read + Seek> Read for Body<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.chunk.is_empty() && self.next < self.file.chunks.len() {
+            match self.file.chunk(self.next) {
+                Ok(chunk) => {
+                    self.chunk = chunk;
+                    self.next += 1;
+                }
+                Err(e) => {
+                    let message = e.to_string();
+                    self.error = Some(e);
+                    return Err(io::Error::other(message));
+                }
+            }
+        }
+        let n = buf.len().min(self.chunk.len());
+        buf[..n].copy_from_slice(&self.chunk[..n]);
+        self.chunk.advance(n);
+        Ok(n)
+    }
+}
+
+fn read_chunk(reader: &mut impl Read) -> io::Result<Vec<u8>> {
+    let mut chunk = Vec::with_capacity(CHUNK_SIZE);
+    reader.take(CHUNK_SIZE as u64).read_to_end(&mut chunk)?;
+    Ok(chunk)
+}
+
+fn read_exact(reader: &mut impl Read, buf: &mut [u8], eof: &str) -> ChipaResult<()> {
+    reader.read_exact(buf).map_err(|e| match e.kind() {
+        io::ErrorKind::UnexpectedEof => ChipaError::InvalidFileFormat(eof.to_string()),
+        _ => ChipaError::FileCreation(e),
+    })
+}
+
+// Reads the version prefix and file id of a streamed file.
+fn read_header(reader: &mut impl Read) -> ChipaResult<(Version, [u8; FILE_ID_LEN])> {
+    let mut prefix = [0u8; 2];
+    read_exact(reader, &mut prefix, "File is too small")?;
+    let prefix = u16::from_be_bytes(prefix);
+    if prefix & STREAM_FLAG == 0 {
+        return Err(ChipaError::InvalidFileFormat(
+            "File is not streamed, open it with ChipaFile::load or from_bytes".to_string(),
+        ));
+    }
+    let version = Version::try_from(prefix & !STREAM_FLAG)
+        .map_err(|e| ChipaError::InvalidFileFormat(e.to_string()))?;
+    let mut file_id = [0u8; FILE_ID_LEN];
+    read_exact(reader, &mut file_id, "File is too small")?;
+    Ok((version, file_id))
+}
+
+// Reads the length prefix of the chunk at `index` and the sealed chunk after it.
+fn read_sealed(reader: &mut impl Read, index: u64) -> ChipaResult<Vec<u8>> {
+    let mut len = [0u8; 4];
+    read_exact(reader, &mut len, "File is truncated, the last chunk is missing")?;
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_SEALED_CHUNK {
+        return Err(ChipaError::InvalidFileFormat(format!(
+            "Chunk {} is {} bytes, more than the {} bytes limit",
+            index, len, MAX_SEALED_CHUNK
+        )));
+    }
+    let mut sealed = vec![0u8; len];
+    read_exact(reader, &mut sealed, "File is truncated inside a chunk")?;
+    Ok(sealed)
+}
+
+// Decrypts the chunk at `index` and checks it belongs there, returning whether it is
+// the last one and its part of the body.
+fn open_chunk(
+    encryptor: Encryptor,
+    key: &str,
+    sealed: Vec<u8>,
+    file_id: &[u8; FILE_ID_LEN],
+    index: u64,
+) -> ChipaResult<(bool, Bytes)> {
+    let plain = encryptor
+        .decrypt_bytes(key, &Bytes::from(sealed))
+        .map_err(ChipaError::Decryption)?;
+    let in_place = plain.len() >= CHUNK_HEADER
+        && plain[..FILE_ID_LEN] == file_id[..]
+        && plain[FILE_ID_LEN..FILE_ID_LEN + 8] == index.to_be_bytes()
+        && plain[CHUNK_HEADER - 1] <= 1;
+    if !in_place {
+        return Err(ChipaError::Tampered(format!(
+            "chunk {} does not belong at this position",
+            index
+        )));
+    }
+    Ok((plain[CHUNK_HEADER - 1] == 1, plain.slice(CHUNK_HEADER..)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        alloc::{GlobalAlloc, Layout, System},
+        cell::Cell,
+        io::Cursor,
+        ops::Range,
+    };
+
+    use serde::{Deserialize, Serialize};
+    #[cfg(feature = "fs")]
+    use sha2::{Digest, Sha256};
+
+    use super::*;
+
+    // Tracks what the current thread has allocated, to check what reading holds at once.
+    struct Counting;
+
+    thread_local! {
+        static LIVE: Cell<isize> = const { Cell::new(0) };
+        static PEAK: Cell<isize> = const { Cell::new(0) };
+    }
+
+    fn track(size: isize) {
+        let _ = LIVE.try_with(|live| {
+            live.set(live.get() + size);
+            let _ = PEAK.try_with(|peak| peak.set(peak.get().max(live.get())));
+        });
+    }
+
+    unsafe impl GlobalAlloc for Counting {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let ptr = unsafe { System.alloc(layout) };
+            if !ptr.is_null() {
+                track(layout.size() as isize);
+            }
+            ptr
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            unsafe { System.dealloc(ptr, layout) };
+            track(-(layout.size() as isize));
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: Counting = Counting;
+
+    // The most `run` had allocated at once on this thread, beyond what was live before.
+    fn peak_allocated(run: impl FnOnce()) -> usize {
+        let before = LIVE.with(Cell::get);
+        PEAK.with(|peak| peak.set(before));
+        run();
+        (PEAK.with(Cell::get) - before) as usize
+    }
+
+    const KEY: &str = "stream key";
+
+    fn body(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 31 % 251) as u8).collect()
+    }
+
+    fn encrypt(body: &[u8]) -> Vec<u8> {
+        let mut encrypted = Vec::new();
+        let size = ChipaFile::encrypt_stream(Version::LATEST, KEY, body, &mut encrypted).unwrap();
+        assert_eq!(size, body.len() as u64);
+        encrypted
+    }
+
+    fn decrypt(encrypted: &[u8]) -> ChipaResult<Vec<u8>> {
+        let mut decrypted = Vec::new();
+        ChipaFile::decrypt_stream(KEY, encrypted, &mut decrypted)?;
+        Ok(decrypted)
+    }
+
+    // Byte ranges of each chunk, length prefix included.
+    fn chunks(encrypted: &[u8]) -> Vec<Range<usize>> {
+        let mut ranges = Vec::new();
+        let mut at = 2 + FILE_ID_LEN;
+        while at < encrypted.len() {
+            let len = u32::from_be_bytes(encrypted[at..at + 4].try_into().unwrap()) as usize;
+            ranges.push(at..at + 4 + len);
+            at += 4 + len;
+        }
+        ranges
+    }
+
+    #[test]
+    fn test_stream_round_trip_across_chunk_boundaries() {
+        for len in [0, 1, CHUNK_SIZE - 1, CHUNK_SIZE, 2 * CHUNK_SIZE, 3 * CHUNK_SIZE + 12_345] {
+            let body = body(len);
+            let encrypted = encrypt(&body);
+            assert_eq!(chunks(&encrypted).len(), len.div_ceil(CHUNK_SIZE).max(1), "{} bytes", len);
+            assert_eq!(decrypt(&encrypted).unwrap(), body, "{} bytes", len);
+        }
+    }
+
+    #[test]
+    fn test_stream_wrong_key_and_layout() {
+        let encrypted = encrypt(&body(10));
+        let result = ChipaFile::decrypt_stream("wrong key", encrypted.as_slice(), io::sink());
+        assert!(matches!(result, Err(ChipaError::Decryption(_))));
+
+        let file = ChipaFile::new(Version::LATEST, &"value").unwrap();
+        let classic = file.to_legacy_bytes(KEY).unwrap();
+        assert!(matches!(decrypt(&classic), Err(ChipaError::InvalidFileFormat(_))));
+        let value: String = rmp_serde::from_slice(&decrypt(&file.to_bytes(KEY).unwrap()).unwrap())
+            .unwrap();
+        assert_eq!(value, "value");
+        assert!(!ChipaFile::from_bytes(&encrypted, KEY).unwrap().is_legacy());
+        assert!(matches!(decrypt(&encrypted[..1]), Err(ChipaError::InvalidFileFormat(_))));
+    }
+
+    #[test]
+    fn test_stream_detects_corrupted_chunks() {
+        let encrypted = encrypt(&body(2 * CHUNK_SIZE + 100));
+        let ranges = chunks(&encrypted);
+        assert_eq!(ranges.len(), 3);
+
+        let mut corrupted = encrypted.clone();
+        corrupted[ranges[1].start + ranges[1].len() / 2] ^= 0x01;
+        assert!(matches!(decrypt(&corrupted), Err(ChipaError::Decryption(_))));
+
+        let mut swapped = encrypted[..ranges[0].start].to_vec();
+        for range in [&ranges[1], &ranges[0], &ranges[2]] {
+            swapped.extend_from_slice(&encrypted[range.clone()]);
+        }
+        assert!(matches!(decrypt(&swapped), Err(ChipaError::Tampered(_))));
+
+        let truncated = &encrypted[..ranges[2].start];
+        assert!(matches!(decrypt(truncated), Err(ChipaError::InvalidFileFormat(_))));
+        let cut = &encrypted[..ranges[2].end - 1];
+        assert!(matches!(decrypt(cut), Err(ChipaError::InvalidFileFormat(_))));
+
+        let mut trailing = encrypted.clone();
+        trailing.push(0);
+        assert!(matches!(decrypt(&trailing), Err(ChipaError::Tampered(_))));
+
+        let other = encrypt(&body(2 * CHUNK_SIZE + 100));
+        let other_ranges = chunks(&other);
+        let mut spliced = encrypted[..ranges[1].start].to_vec();
+        spliced.extend_from_slice(&other[other_ranges[1].clone()]);
+        spliced.extend_from_slice(&encrypted[ranges[2].clone()]);
+        assert!(matches!(decrypt(&spliced), Err(ChipaError::Tampered(_))));
+
+        let mut oversized = encrypted.clone();
+        oversized[ranges[1].start..ranges[1].start + 4].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert!(matches!(decrypt(&oversized), Err(ChipaError::InvalidFileFormat(_))));
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Record {
+        id: u64,
+        price: f64,
+        symbol: String,
+    }
+
+    fn record(id: u64) -> Record {
+        Record {
+            id,
+            price: id as f64 / 8.0,
+            symbol: format!("SYM{}", id % 997),
+        }
+    }
+
+    // The body of a file saved from a `Vec<Record>`, without building the `Vec`.
+    fn records(len: u32) -> Vec<u8> {
+        let mut body = vec![0xdd];
+        body.extend_from_slice(&len.to_be_bytes());
+        for id in 0..len {
+            rmp_serde::encode::write(&mut body, &record(id.into())).unwrap();
+        }
+        body
+    }
+
+    #[test]
+    fn test_deserialize_seq_holds_one_chunk() {
+        const RECORDS: u32 = 1_000_000;
+        const CAP: usize = 8 * CHUNK_SIZE;
+        let encrypted = encrypt(&records(RECORDS));
+        assert!(encrypted.len() > 2 * CAP);
+
+        let mut reader = ChipaReader::new(KEY, Cursor::new(encrypted.as_slice())).unwrap();
+        let mut read = 0u64;
+        let streamed = peak_allocated(|| {
+            for element in reader.deserialize_seq::<Record>() {
+                assert_eq!(element.unwrap(), record(read));
+                read += 1;
+            }
+        });
+        assert_eq!(read, u64::from(RECORDS));
+        assert!(streamed < CAP, "streaming held {} bytes", streamed);
+
+        let buffered = peak_allocated(|| {
+            let file = ChipaFile::from_bytes(&encrypted, KEY).unwrap();
+            assert_eq!(file.read::<Vec<Record>>().unwrap().len(), RECORDS as usize);
+        });
+        assert!(buffered > CAP, "buffering held {} bytes", buffered);
+    }
+
+    #[test]
+    fn test_deserialize_seq_stops_at_the_first_error() {
+        let mut body = vec![0x93];
+        rmp_serde::encode::write(&mut body, &record(1)).unwrap();
+        body.push(0xc1);
+        rmp_serde::encode::write(&mut body, &record(3)).unwrap();
+        let encrypted = encrypt(&body);
+        let mut reader = ChipaReader::new(KEY, Cursor::new(encrypted)).unwrap();
+        let mut elements = reader.deserialize_seq::<Record>();
+        assert_eq!(elements.next().unwrap().unwrap(), record(1));
+        assert!(matches!(elements.next(), Some(Err(ChipaError::Decode(_)))));
+        assert!(elements.next().is_none());
+
+        let encrypted = ChipaFile::new(Version::LATEST, &"not a sequence")
+            .unwrap()
+            .to_bytes(KEY)
+            .unwrap();
+        let mut reader = ChipaReader::new(KEY, Cursor::new(encrypted)).unwrap();
+        let elements: Vec<_> = reader.deserialize_seq::<Record>().collect();
+        assert!(matches!(elements[..], [Err(ChipaError::Decode(_))]));
+
+        let mut encrypted = encrypt(&records(200_000));
+        let ranges = chunks(&encrypted);
+        assert!(ranges.len() > 2);
+        encrypted[ranges[1].start + ranges[1].len() / 2] ^= 0x01;
+        let mut reader = ChipaReader::new(KEY, Cursor::new(encrypted)).unwrap();
+        let elements: Vec<_> = reader.deserialize_seq::<Record>().collect();
+        assert!(elements[..elements.len() - 1].iter().all(Result::is_ok));
+        assert!(matches!(elements.last(), Some(Err(ChipaError::Decryption(_)))));
+    }
+
+    #[test]
+    fn test_reader_checks_the_file_on_open() {
+        let encrypted = encrypt(&body(2 * CHUNK_SIZE + 100));
+        let ranges = chunks(&encrypted);
+        let open = |data: &[u8]| ChipaReader::new(KEY, Cursor::new(data.to_vec())).map(|_| ());
+
+        assert!(open(&encrypted).is_ok());
+        assert!(matches!(
+            ChipaReader::new("wrong key", Cursor::new(encrypted.as_slice())),
+            Err(ChipaError::Decryption(_))
+        ));
+        let classic = ChipaFile::new(Version::LATEST, &"value").unwrap().to_legacy_bytes(KEY);
+        assert!(matches!(open(&classic.unwrap()), Err(ChipaError::InvalidFileFormat(_))));
+        assert!(matches!(
+            open(&encrypted[..ranges[2].start]),
+            Err(ChipaError::InvalidFileFormat(_))
+        ));
+        assert!(matches!(
+            open(&encrypted[..ranges[2].end - 1]),
+            Err(ChipaError::InvalidFileFormat(_))
+        ));
+        assert!(matches!(
+            open(&encrypted[..2 + FILE_ID_LEN]),
+            Err(ChipaError::InvalidFileFormat(_))
+        ));
+    }
+
+    #[test]
+    fn test_read_range() {
+        let len = 3 * CHUNK_SIZE + 500;
+        let body = body(len);
+        let mut reader = ChipaReader::new(KEY, Cursor::new(encrypt(&body))).unwrap();
+        assert_eq!(reader.len(), len as u64);
+        assert!(!reader.is_empty());
+
+        let ranges = [
+            (0, 1024),
+            (CHUNK_SIZE - 1, 2),
+            (CHUNK_SIZE, CHUNK_SIZE),
+            (CHUNK_SIZE / 2, 2 * CHUNK_SIZE),
+            (0, len),
+            (len - 500, 500),
+        ];
+        for (offset, size) in ranges {
+            let range = reader.read_range(offset as u64, size).unwrap();
+            assert_eq!(range, body[offset..offset + size], "{}..+{}", offset, size);
+        }
+
+        // Zero-length and past the end are empty, running past the end stops at it.
+        assert!(reader.read_range(CHUNK_SIZE as u64, 0).unwrap().is_empty());
+        assert!(reader.read_range(len as u64, 10).unwrap().is_empty());
+        assert!(reader.read_range(u64::MAX, usize::MAX).unwrap().is_empty());
+        let tail = reader.read_range(len as u64 - 10, 100).unwrap();
+        assert_eq!(tail, body[len - 10..]);
+
+        let mut empty = ChipaReader::new(KEY, Cursor::new(encrypt(&[]))).unwrap();
+        assert!(empty.is_empty());
+        assert!(empty.read_range(0, 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_read_range_opens_only_its_chunks() {
+        let body = body(3 * CHUNK_SIZE);
+        let mut encrypted = encrypt(&body);
+        let ranges = chunks(&encrypted);
+        encrypted[ranges[0].start + ranges[0].len() / 2] ^= 0x01;
+        let mut reader = ChipaReader::new(KEY, Cursor::new(encrypted)).unwrap();
+
+        let range = reader.read_range(CHUNK_SIZE as u64 + 5, CHUNK_SIZE).unwrap();
+        assert_eq!(range, body[CHUNK_SIZE + 5..2 * CHUNK_SIZE + 5]);
+        assert!(matches!(reader.read_range(10, 10), Err(ChipaError::Decryption(_))));
+    }
+
+    // Yields `len` bytes without ever holding them, to check the file path streams.
+    #[cfg(feature = "fs")]
+    struct Generated {
+        at: usize,
+        len: usize,
+    }
+
+    #[cfg(feature = "fs")]
+    impl Read for Generated {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = buf.len().min(self.len - self.at);
+            for (i, byte) in buf[..n].iter_mut().enumerate() {
+                *byte = ((self.at + i) * 31 % 251) as u8;
+            }
+            self.at += n;
+            Ok(n)
+        }
+    }
+
+    #[cfg(feature = "fs")]
+    struct Hasher(Sha256);
+
+    #[cfg(feature = "fs")]
+    impl Write for Hasher {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.update(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_save_and_load_stream() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("weights").to_string_lossy().into_owned();
+        let len = 8 * CHUNK_SIZE + 777;
+
+        let size = ChipaFile::save_stream(&path, KEY, Generated { at: 0, len }).unwrap();
+        assert_eq!(size, len as u64);
+        let path = format!("{}.chipa", path);
+        assert!(!std::path::Path::new(&format!("{}.tmp", path)).exists());
+
+        let mut expected = Hasher(Sha256::new());
+        io::copy(&mut Generated { at: 0, len }, &mut expected).unwrap();
+        let mut actual = Hasher(Sha256::new());
+        assert_eq!(ChipaFile::load_stream(&path, KEY, &mut actual).unwrap(), len as u64);
+        assert_eq!(actual.0.finalize(), expected.0.finalize());
+
+        assert!(!ChipaFile::load(&path, KEY).unwrap().is_legacy());
+        assert!(matches!(
+            ChipaFile::load_stream(&path, "wrong key", io::sink()),
+            Err(ChipaError::Decryption(_))
+        ));
+    }
+}
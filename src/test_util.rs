@@ -0,0 +1,60 @@
+//! Helpers for tests and doctests, enabled by the `test-util` feature.
+//!
+//! The mock license server is re-exported here when `mock-server` is enabled too.
+
+#[cfg(feature = "fs")]
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "fs")]
+use uuid::Uuid;
+
+pub use crate::fs::{check_chipa_fs, MemoryFs};
+#[cfg(feature = "mock-server")]
+pub use crate::mock::{MockConfig, MockServer, Scenario};
+
+/// A directory under the system temp dir that is removed with everything in it when
+/// dropped.
+#[cfg(feature = "fs")]
+#[derive(Debug)]
+pub struct TempDir {
+    path: PathBuf,
+}
+
+#[cfg(feature = "fs")]
+impl TempDir {
+    pub fn new() -> std::io::Result<Self> {
+        let path = std::env::temp_dir().join(format!("chipa-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&path)?;
+        Ok(Self { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn file(&self, name: &str) -> String {
+        self.path.join(name).to_string_lossy().into_owned()
+    }
+}
+
+#[cfg(feature = "fs")]
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+#[cfg(all(test, feature = "fs"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_temp_dir_is_removed() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().to_path_buf();
+        std::fs::write(dir.file("a.chipa"), b"data").unwrap();
+        assert!(path.is_dir());
+        drop(dir);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,477 @@
+//! Atomic writes of several `.chipa` files at once.
+//!
+//! A transaction writes, next to its first target, a journal named
+//! `.chipa-txn-<id>.journal` that lists every target with its staged copy
+//! (`<target>.<id>.stage`), its lock file (`<target>.lock`, holding the transaction id)
+//! and a digest of the new contents. Committing takes these steps:
+//!
+//! 1. the journal is written in the `Pending` state;
+//! 2. every lock file is created, failing with [`ChipaError::Conflict`] if another
+//!    transaction holds one of them;
+//! 3. every staged copy is written;
+//! 4. the journal is rewritten in the `Committed` state. This is the commit point: a
+//!    crash before it leaves the old files, a crash after it the new ones;
+//! 5. every staged copy is renamed over its target, then the journal and the locks are
+//!    removed.
+//!
+//! [`ChipaTxn::recover`] finishes what a crash left behind and must run before any new
+//! transaction in the directory, typically at startup. It cannot tell a crashed
+//! transaction from one still running, so running it next to live transactions rolls
+//! them back; they notice and fail instead of reporting a commit that did not happen.
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::{
+    encryption::{ChipaError, ChipaFile, ChipaResult},
+    fs::{ChipaFs, RealFs},
+};
+
+const JOURNAL_PREFIX: &str = ".chipa-txn-";
+const JOURNAL_EXTENSION: &str = "journal";
+
+/// A set of `.chipa` files written all or nothing. See the [module docs](self) for the
+/// files it leaves next to the targets and how a crash is recovered.
+///
+/// ```no_run
+/// # fn main() -> Result<(), chipa_license_validator::ChipaError> {
+/// use chipa_license_validator::{ChipaFile, ChipaTxn, Version};
+///
+/// ChipaTxn::recover("state")?;
+/// let settings = ChipaFile::new(Version::LATEST, &"dark mode")?;
+/// let index = ChipaFile::new(Version::LATEST, &vec![1u32, 2, 3])?;
+/// ChipaTxn::new()
+///     .write("state/settings", &settings, "key")
+///     .write("state/index", &index, "key")
+///     .commit()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Default)]
+pub struct ChipaTxn<'a> {
+    writes: Vec<(PathBuf, &'a ChipaFile, &'a str)>,
+}
+
+/// What [`ChipaTxn::recover`] found: transactions that crashed after their commit point
+/// and were completed, and transactions that crashed before it and were undone.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TxnRecovery {
+    pub rolled_forward: usize,
+    pub rolled_back: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+enum TxnState {
+    Pending,
+    Committed,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct TxnEntry {
+    target: PathBuf,
+    staged: PathBuf,
+    lock: PathBuf,
+    digest: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Journal {
+    id: Uuid,
+    state: TxnState,
+    entries: Vec<TxnEntry>,
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+impl<'a> ChipaTxn<'a> {
+    /// An empty transaction; committing it does nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `file`, encrypted with `key`, to be written at `path` with a `.chipa`
+    /// extension. The journal goes next to the first path added.
+    pub fn write(mut self, path: &str, file: &'a ChipaFile, key: &'a str) -> Self {
+        self.writes.push((ChipaFile::chipa_path(path), file, key));
+        self
+    }
+
+    /// Writes every file or none of them. Fails with [`ChipaError::Conflict`] if
+    /// another transaction holds one of the targets, or was recovered from under this
+    /// one.
+    pub fn commit(self) -> ChipaResult<()> {
+        self.commit_with(&RealFs)
+    }
+
+    /// [`ChipaTxn::commit`] on any [`ChipaFs`].
+    pub fn commit_with(self, fs: &dyn ChipaFs) -> ChipaResult<()> {
+        if self.writes.is_empty() {
+            return Ok(());
+        }
+        self.prepare(fs)?.commit(fs)
+    }
+
+    /// Completes the transactions in `dir` that crashed after their commit point and
+    /// undoes those that crashed before it. Run it before starting any transaction in
+    /// `dir`: it takes every journal it finds for a crashed one.
+    pub fn recover(dir: impl AsRef<Path>) -> ChipaResult<TxnRecovery> {
+        Self::recover_with(dir, &RealFs)
+    }
+
+    /// [`ChipaTxn::recover`] on any [`ChipaFs`].
+    pub fn recover_with(dir: impl AsRef<Path>, fs: &dyn ChipaFs) -> ChipaResult<TxnRecovery> {
+        let mut recovered = TxnRecovery::default();
+        for path in fs.list(dir.as_ref())? {
+            let is_journal = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with(JOURNAL_PREFIX))
+                && path.extension().is_some_and(|e| e == JOURNAL_EXTENSION);
+            if !is_journal {
+                continue;
+            }
+            let journal = Journal::read(path, fs)?;
+            match journal.state {
+                TxnState::Pending => {
+                    journal.roll_back(fs)?;
+                    recovered.rolled_back += 1;
+                }
+                TxnState::Committed => {
+                    journal.roll_forward(fs)?;
+                    recovered.rolled_forward += 1;
+                }
+            }
+        }
+        Ok(recovered)
+    }
+
+    fn prepare(self, fs: &dyn ChipaFs) -> ChipaResult<Journal> {
+        let id = Uuid::new_v4();
+        let dir = parent(&self.writes[0].0).to_path_buf();
+        let staged = self
+            .writes
+            .iter()
+            .map(|(_, file, key)| file.to_bytes(key))
+            .collect::<ChipaResult<Vec<_>>>()?;
+        let journal = Journal {
+            id,
+            state: TxnState::Pending,
+            entries: self
+                .writes
+                .iter()
+                .zip(&staged)
+                .map(|((target, _, _), data)| TxnEntry {
+                    target: target.clone(),
+                    staged: suffixed(target, &format!("{}.stage", id)),
+                    lock: suffixed(target, "lock"),
+                    digest: digest(data),
+                })
+                .collect(),
+            path: dir.join(format!("{}{}.{}", JOURNAL_PREFIX, id, JOURNAL_EXTENSION)),
+        };
+        journal.persist(fs)?;
+
+        if let Err(e) = journal.lock_all(fs) {
+            journal.roll_back(fs)?;
+            return Err(e);
+        }
+        for (entry, data) in journal.entries.iter().zip(&staged) {
+            if let Err(e) = fs.write_atomic(&entry.staged, data) {
+                journal.roll_back(fs)?;
+                return Err(e.into());
+            }
+        }
+        Ok(journal)
+    }
+}
+
+impl Journal {
+    fn read(path: PathBuf, fs: &dyn ChipaFs) -> ChipaResult<Self> {
+        let data = fs.read(&path)?;
+        let mut journal: Journal = serde_json::from_slice(&data).map_err(|e| {
+            ChipaError::InvalidFileFormat(format!("Corrupt transaction journal, {}", e))
+        })?;
+        journal.path = path;
+        Ok(journal)
+    }
+
+    fn persist(&self, fs: &dyn ChipaFs) -> ChipaResult<()> {
+        let data = serde_json::to_vec(self).map_err(|e| ChipaError::Encode(e.to_string()))?;
+        fs.write_atomic(&self.path, &data)?;
+        fs.sync_dir(parent(&self.path))?;
+        Ok(())
+    }
+
+    fn set_state(&mut self, state: TxnState, fs: &dyn ChipaFs) -> ChipaResult<()> {
+        self.state = state;
+        self.persist(fs)
+    }
+
+    // Passes the commit point, unless a recovery took the transaction since it was
+    // prepared: then its staged copies or its locks are gone.
+    fn commit(mut self, fs: &dyn ChipaFs) -> ChipaResult<()> {
+        let taken = self
+            .entries
+            .iter()
+            .find(|entry| !self.owns(&entry.lock, fs) || !fs.exists(&entry.staged));
+        if let Some(entry) = taken {
+            let target = entry.target.clone();
+            self.roll_back(fs)?;
+            return Err(ChipaError::Conflict(target));
+        }
+        self.set_state(TxnState::Committed, fs)?;
+        self.roll_forward(fs)
+    }
+
+    fn owns(&self, lock: &Path, fs: &dyn ChipaFs) -> bool {
+        fs.read(lock).is_ok_and(|owner| owner == self.id.as_bytes())
+    }
+
+    fn lock_all(&self, fs: &dyn ChipaFs) -> ChipaResult<()> {
+        for entry in &self.entries {
+            match fs.create_new(&entry.lock, self.id.as_bytes()) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    return Err(ChipaError::Conflict(entry.target.clone()))
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(())
+    }
+
+    // A missing staged copy is fine only if it already replaced its target, in an
+    // earlier attempt or a concurrent recovery; otherwise the new contents are lost.
+    fn roll_forward(&self, fs: &dyn ChipaFs) -> ChipaResult<()> {
+        for entry in &self.entries {
+            if fs.exists(&entry.staged) {
+                fs.rename(&entry.staged, &entry.target)?;
+                fs.sync_dir(parent(&entry.target))?;
+            } else if !fs.read(&entry.target).is_ok_and(|data| digest(&data) == entry.digest) {
+                return Err(ChipaError::Conflict(entry.target.clone()));
+            }
+        }
+        self.finish(fs)
+    }
+
+    fn roll_back(&self, fs: &dyn ChipaFs) -> ChipaResult<()> {
+        for entry in &self.entries {
+            remove_if_exists(&entry.staged, fs)?;
+        }
+        self.finish(fs)
+    }
+
+    fn finish(&self, fs: &dyn ChipaFs) -> ChipaResult<()> {
+        remove_if_exists(&self.path, fs)?;
+        for entry in &self.entries {
+            if self.owns(&entry.lock, fs) {
+                remove_if_exists(&entry.lock, fs)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn digest(data: &[u8]) -> String {
+    Sha256::digest(data).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn parent(path: &Path) -> &Path {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    }
+}
+
+fn suffixed(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(".");
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+fn remove_if_exists(path: &Path, fs: &dyn ChipaFs) -> std::io::Result<()> {
+    match fs.remove(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fs::MemoryFs, version::Version};
+
+    fn files() -> (ChipaFile, ChipaFile) {
+        (
+            ChipaFile::new(Version::V1, &"settings v2").unwrap(),
+            ChipaFile::new(Version::V1, &vec![1u32, 2, 3]).unwrap(),
+        )
+    }
+
+    fn path(dir: &Path, name: &str) -> String {
+        dir.join(name).to_str().unwrap().to_string()
+    }
+
+    fn read<T: serde::de::DeserializeOwned>(path: &str) -> T {
+        ChipaFile::load(path, "key").unwrap().read().unwrap()
+    }
+
+    fn leftovers(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|n| !n.ends_with(".chipa"))
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_commit() {
+        let dir = tempfile::tempdir().unwrap();
+        let (settings, index) = files();
+        let (a, b) = (path(dir.path(), "settings.chipa"), path(dir.path(), "index.chipa"));
+        ChipaTxn::new()
+            .write(&a, &settings, "key")
+            .write(&b, &index, "key")
+            .commit()
+            .unwrap();
+        assert_eq!(read::<String>(&a), "settings v2");
+        assert_eq!(read::<Vec<u32>>(&b), vec![1, 2, 3]);
+        assert!(leftovers(dir.path()).is_empty());
+    }
+
+    #[test]
+    fn test_recover_rolls_back_pending() {
+        let dir = tempfile::tempdir().unwrap();
+        let (settings, index) = files();
+        let (a, b) = (path(dir.path(), "settings.chipa"), path(dir.path(), "index.chipa"));
+        ChipaFile::new(Version::V1, &"settings v1")
+            .unwrap()
+            .save(&a, "key")
+            .unwrap();
+
+        // Crash after staging, before the commit point.
+        let journal = ChipaTxn::new()
+            .write(&a, &settings, "key")
+            .write(&b, &index, "key")
+            .prepare(&RealFs)
+            .unwrap();
+        drop(journal);
+
+        let recovered = ChipaTxn::recover(dir.path()).unwrap();
+        assert_eq!(recovered.rolled_back, 1);
+        assert_eq!(read::<String>(&a), "settings v1");
+        assert!(!Path::new(&b).exists());
+        assert!(leftovers(dir.path()).is_empty());
+    }
+
+    #[test]
+    fn test_recover_rolls_forward_committed() {
+        let dir = tempfile::tempdir().unwrap();
+        let (settings, index) = files();
+        let (a, b) = (path(dir.path(), "settings.chipa"), path(dir.path(), "index.chipa"));
+
+        // Crash after the commit point, with only the first file renamed.
+        let mut journal = ChipaTxn::new()
+            .write(&a, &settings, "key")
+            .write(&b, &index, "key")
+            .prepare(&RealFs)
+            .unwrap();
+        journal.set_state(TxnState::Committed, &RealFs).unwrap();
+        std::fs::rename(&journal.entries[0].staged, &journal.entries[0].target).unwrap();
+        drop(journal);
+
+        let recovered = ChipaTxn::recover(dir.path()).unwrap();
+        assert_eq!(recovered.rolled_forward, 1);
+        assert_eq!(read::<String>(&a), "settings v2");
+        assert_eq!(read::<Vec<u32>>(&b), vec![1, 2, 3]);
+        assert!(leftovers(dir.path()).is_empty());
+    }
+
+    #[test]
+    fn test_overlapping_transactions_conflict() {
+        let dir = tempfile::tempdir().unwrap();
+        let (settings, index) = files();
+        let (a, b) = (path(dir.path(), "settings.chipa"), path(dir.path(), "index.chipa"));
+
+        let first = ChipaTxn::new()
+            .write(&a, &settings, "key")
+            .prepare(&RealFs)
+            .unwrap();
+        let second = ChipaTxn::new()
+            .write(&b, &index, "key")
+            .write(&a, &settings, "key")
+            .commit();
+        assert!(matches!(second, Err(ChipaError::Conflict(p)) if p == Path::new(&a)));
+        assert!(!Path::new(&b).exists());
+
+        first.commit(&RealFs).unwrap();
+        assert!(leftovers(dir.path()).is_empty());
+        ChipaTxn::new()
+            .write(&b, &index, "key")
+            .write(&a, &settings, "key")
+            .commit()
+            .unwrap();
+        assert_eq!(read::<Vec<u32>>(&b), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_commit_after_recovery_conflicts() {
+        let dir = tempfile::tempdir().unwrap();
+        let (settings, _) = files();
+        let a = path(dir.path(), "settings.chipa");
+
+        // A recovery running next to a live transaction takes it for a crashed one.
+        let journal = ChipaTxn::new()
+            .write(&a, &settings, "key")
+            .prepare(&RealFs)
+            .unwrap();
+        assert_eq!(ChipaTxn::recover(dir.path()).unwrap().rolled_back, 1);
+        let committed = journal.commit(&RealFs);
+        assert!(matches!(committed, Err(ChipaError::Conflict(p)) if p == Path::new(&a)));
+        assert!(!Path::new(&a).exists());
+        assert!(leftovers(dir.path()).is_empty());
+    }
+
+    #[test]
+    fn test_roll_forward_missing_stage_fails() {
+        let fs = MemoryFs::new();
+        let (settings, index) = files();
+        let mut journal = ChipaTxn::new()
+            .write("state/settings", &settings, "key")
+            .write("state/index", &index, "key")
+            .prepare(&fs)
+            .unwrap();
+        journal.set_state(TxnState::Committed, &fs).unwrap();
+        fs.rename(&journal.entries[0].staged, &journal.entries[0].target).unwrap();
+        fs.remove(&journal.entries[1].staged).unwrap();
+        drop(journal);
+
+        let recovered = ChipaTxn::recover_with("state", &fs);
+        let index = Path::new("state/index.chipa");
+        assert!(matches!(recovered, Err(ChipaError::Conflict(p)) if p == index));
+    }
+
+    #[test]
+    fn test_commit_with_store() {
+        let fs = MemoryFs::new();
+        let (settings, index) = files();
+        ChipaTxn::new()
+            .write("state/settings", &settings, "key")
+            .write("state/index", &index, "key")
+            .commit_with(&fs)
+            .unwrap();
+        let settings: String = ChipaFile::load_with("state/settings.chipa", "key", &fs)
+            .unwrap()
+            .read()
+            .unwrap();
+        assert_eq!(settings, "settings v2");
+        assert_eq!(fs.list(Path::new("state")).unwrap().len(), 2);
+        assert_eq!(ChipaTxn::recover_with("state", &fs).unwrap(), TxnRecovery::default());
+    }
+}
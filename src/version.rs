@@ -0,0 +1,150 @@
+//! Stable wrappers around the `tenacity_utils` security primitives.
+//!
+//! `Version` and `Encryptor` belong to this crate and are the only encryption types
+//! in its public API. They convert to the upstream types internally, so changes to
+//! `tenacity_utils` are absorbed here instead of breaking users. Variants are only
+//! ever added, never renumbered: `u16::from(version)` is the value written at the
+//! start of every `.chipa` file and sent in the version header.
+
+use bytes::Bytes;
+use tenacity_utils::security::{TenacityMiddleware, Version as Upstream};
+use uuid::Uuid;
+
+/// A version of the encryption protocol. `u16::from(version)` is the value written at
+/// the start of `.chipa` files and sent in the version header, so it never changes for
+/// an existing variant.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Version {
+    V1,
+}
+
+/// A protocol number this build does not know, e.g. from a file written by a newer
+/// release.
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+#[error("Unknown version, {0}")]
+pub struct UnknownVersion(pub u16);
+
+impl Version {
+    /// Every version this build can read, oldest first.
+    pub const ALL: [Version; 1] = [Version::V1];
+    /// The version new files and requests use.
+    pub const LATEST: Version = Version::V1;
+
+    /// The encryption primitives of this version.
+    pub fn encryptor(self) -> Encryptor {
+        Encryptor { version: self }
+    }
+
+    pub(crate) fn upstream(self) -> Upstream {
+        match self {
+            Version::V1 => Upstream::V1,
+        }
+    }
+
+    pub(crate) fn from_upstream(version: Upstream) -> Result<Self, UnknownVersion> {
+        Self::try_from(u16::from(version))
+    }
+}
+
+impl From<Version> for u16 {
+    fn from(version: Version) -> Self {
+        match version {
+            Version::V1 => 1,
+        }
+    }
+}
+
+impl TryFrom<u16> for Version {
+    type Error = UnknownVersion;
+
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        Version::ALL
+            .into_iter()
+            .find(|version| u16::from(*version) == value)
+            .ok_or(UnknownVersion(value))
+    }
+}
+
+/// The encryption primitives of one [`Version`], from [`Version::encryptor`].
+///
+/// The `*_bytes` methods encrypt with a caller supplied key, as `.chipa` files do. The
+/// others key on a license id, as requests to and responses from the license server do.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Encryptor {
+    version: Version,
+}
+
+impl Encryptor {
+    /// The version whose primitives these are.
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    /// Encrypts `data` with `key`. Only [`Encryptor::decrypt_bytes`] with the same key
+    /// and version recovers it.
+    pub fn encrypt_bytes(&self, key: &str, data: &Bytes) -> anyhow::Result<Bytes> {
+        self.version.upstream().encryptor().encrypt_bytes(key, data)
+    }
+
+    /// Decrypts what [`Encryptor::encrypt_bytes`] produced, failing if `key` is wrong or
+    /// `data` was altered.
+    pub fn decrypt_bytes(&self, key: &str, data: &Bytes) -> anyhow::Result<Bytes> {
+        self.version.upstream().encryptor().decrypt_bytes(key, data)
+    }
+
+    /// The `Authorization` header value for requests about license `id`. It may use a
+    /// fresh nonce each time, so compare it by decrypting, never to an earlier value.
+    pub async fn encrypt_header(&self, id: Uuid) -> anyhow::Result<String> {
+        self.version.upstream().encryptor().encrypt_header(id).await
+    }
+
+    /// Encrypts a request or response body exchanged about license `id`.
+    pub async fn encrypt(&self, id: Uuid, data: &str) -> anyhow::Result<String> {
+        self.version.upstream().encryptor().encrypt(id, data).await
+    }
+
+    /// Decrypts what [`Encryptor::encrypt`] produced for the same license `id`.
+    pub async fn decrypt(&self, id: Uuid, data: &str) -> anyhow::Result<String> {
+        self.version.upstream().encryptor().decrypt(id, data).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_version_conversions() {
+        for version in Version::ALL {
+            let upstream = version.upstream();
+            assert_eq!(u16::from(version), u16::from(upstream));
+            assert_eq!(Version::from_upstream(upstream), Ok(version));
+            assert_eq!(Version::try_from(u16::from(version)), Ok(version));
+        }
+        assert_eq!(Version::try_from(0), Err(UnknownVersion(0)));
+        assert_eq!(Version::try_from(u16::MAX), Err(UnknownVersion(u16::MAX)));
+    }
+
+    #[tokio::test]
+    async fn test_encryptor_matches_upstream() {
+        let data = Bytes::from_static(b"chipa license validator");
+        let id = Uuid::new_v4();
+        for version in Version::ALL {
+            let ours = version.encryptor();
+            let upstream = version.upstream().encryptor();
+            assert_eq!(ours.version(), version);
+
+            let encrypted = ours.encrypt_bytes("key", &data).unwrap();
+            assert_eq!(upstream.decrypt_bytes("key", &encrypted).unwrap(), data);
+            let encrypted = upstream.encrypt_bytes("key", &data).unwrap();
+            assert_eq!(ours.decrypt_bytes("key", &encrypted).unwrap(), data);
+
+            let encrypted = ours.encrypt(id, "payload").await.unwrap();
+            assert_eq!(upstream.decrypt(id, &encrypted).await.unwrap(), "payload");
+            let encrypted = upstream.encrypt(id, "payload").await.unwrap();
+            assert_eq!(ours.decrypt(id, &encrypted).await.unwrap(), "payload");
+            assert!(!ours.encrypt_header(id).await.unwrap().is_empty());
+        }
+    }
+}
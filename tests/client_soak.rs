@@ -0,0 +1,51 @@
+#![cfg(all(feature = "mock-server", target_os = "linux"))]
+
+use chipa_license_validator::{
+    mock::{MockConfig, MockServer},
+    LicenseClient,
+};
+use uuid::Uuid;
+
+fn open_fds() -> usize {
+    std::fs::read_dir("/proc/self/fd").unwrap().count()
+}
+
+fn rss_bytes() -> usize {
+    let statm = std::fs::read_to_string("/proc/self/statm").unwrap();
+    let pages: usize = statm.split_whitespace().nth(1).unwrap().parse().unwrap();
+    pages * 4096
+}
+
+async fn churn(url: &str, clients: usize) {
+    for _ in 0..clients {
+        let client = LicenseClient::new(url.to_string());
+        client
+            .validate_license(Uuid::new_v4(), "my-app".to_string())
+            .await
+            .unwrap();
+    }
+}
+
+// Slow and sensitive to whatever else runs in the process; CI runs it on its own with
+// `cargo test --test client_soak -- --ignored`.
+#[tokio::test]
+#[ignore]
+async fn test_repeated_client_construction() {
+    let server = MockServer::start(MockConfig::default()).await.unwrap();
+    let url = server.url();
+    let keep = LicenseClient::new(url.clone());
+
+    churn(&url, 1_000).await;
+    let (fds, rss) = (open_fds(), rss_bytes());
+    churn(&url, 10_000).await;
+
+    assert!(open_fds() <= fds + 2, "{} fds before, {} after", fds, open_fds());
+    assert!(
+        rss_bytes() <= rss + 8 * 1024 * 1024,
+        "RSS grew from {} to {} bytes",
+        rss,
+        rss_bytes()
+    );
+    drop(keep);
+    server.stop().await;
+}
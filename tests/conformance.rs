@@ -0,0 +1,65 @@
+#![cfg(feature = "mock-server")]
+
+use chipa_license_validator::{
+    mock::{MockConfig, MockServer, Scenario},
+    LicenseClient, LicenseId,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+const SCENARIOS: &str = include_str!("../conformance/scenarios.json");
+
+#[derive(Deserialize)]
+struct Case {
+    name: String,
+    scenario: Option<Scenario>,
+    license: Option<String>,
+    application: String,
+    expect: Value,
+}
+
+async fn run(client: &LicenseClient, case: &Case) -> Value {
+    let license = match (&case.scenario, &case.license) {
+        (Some(scenario), _) => scenario.license().to_string(),
+        (None, Some(license)) => license.clone(),
+        (None, None) => panic!("Case '{}' needs a scenario or a license", case.name),
+    };
+    let result = match license.parse::<LicenseId>() {
+        Ok(license) => {
+            client
+                .validate_license(license, case.application.clone())
+                .await
+        }
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(token) => json!({ "token": token }),
+        Err(e) => json!({ "error": e.kind(), "remediation": e.remediation().action() }),
+    }
+}
+
+#[tokio::test]
+async fn test_conformance_scenarios() {
+    let cases: Vec<Case> = serde_json::from_str(SCENARIOS).unwrap();
+    let server = MockServer::start(MockConfig::default()).await.unwrap();
+    let client = LicenseClient::new(server.url());
+
+    let mut failures = Vec::new();
+    for case in &cases {
+        let actual = run(&client, case).await;
+        if actual != case.expect {
+            failures.push(format!(
+                "  {}\n    expected: {}\n    actual:   {}",
+                case.name, case.expect, actual
+            ));
+        }
+    }
+    server.stop().await;
+    assert!(
+        failures.is_empty(),
+        "{} of {} conformance scenarios diverged:\n{}",
+        failures.len(),
+        cases.len(),
+        failures.join("\n")
+    );
+}
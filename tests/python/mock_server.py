@@ -0,0 +1,34 @@
+"""Starts chipa-mock-server for the binding tests. Needs CHIPA_MOCK_SERVER pointing at a
+built chipa-mock-server."""
+
+import os
+import subprocess
+from contextlib import contextmanager
+
+MOCK_SERVER = os.environ.get("CHIPA_MOCK_SERVER")
+
+
+@contextmanager
+def mock_server(*args, scenarios=("valid",)):
+    """Runs the mock server with `args` and yields its URL and the license of each
+    scenario in `scenarios`."""
+    server = subprocess.Popen(
+        [MOCK_SERVER, "--port", "0", *args], stdout=subprocess.PIPE, text=True
+    )
+    try:
+        url = None
+        licenses = {}
+        for line in server.stdout:
+            if line.startswith("Listening on "):
+                url = line.split()[-1]
+                continue
+            name, license = line.split()
+            licenses[name] = license
+            if url and set(scenarios) <= licenses.keys():
+                break
+        else:
+            raise RuntimeError(f"chipa-mock-server exited with {server.wait()}")
+        yield url, licenses
+    finally:
+        server.kill()
+        server.wait()
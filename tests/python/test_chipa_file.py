@@ -0,0 +1,37 @@
+"""ChipaFile refuses values that nest too deeply or hold too many nodes instead of
+exhausting the stack or memory converting them."""
+
+import pytest
+
+from chipa_license_validator import ChipaFile, ChipaFileError
+
+
+def nested(depth):
+    value = None
+    for _ in range(depth):
+        value = [value]
+    return value
+
+
+def test_deeply_nested_value_is_refused():
+    with pytest.raises(ChipaFileError) as raised:
+        ChipaFile(nested(10_000))
+    assert raised.value.kind == "limit_exceeded"
+    assert "max_depth" in str(raised.value)
+
+
+def test_read_limits(tmp_path):
+    path = str(tmp_path / "nested.chipa")
+    ChipaFile(nested(100)).save(path, "key")
+    file = ChipaFile.load(path, "key")
+    assert file.read() == nested(100)
+
+    with pytest.raises(ChipaFileError) as raised:
+        file.read(max_depth=50)
+    assert raised.value.kind == "limit_exceeded"
+    assert "max_depth" in str(raised.value)
+
+    with pytest.raises(ChipaFileError) as raised:
+        file.read(max_nodes=10)
+    assert raised.value.kind == "limit_exceeded"
+    assert "max_nodes" in str(raised.value)
@@ -0,0 +1,39 @@
+"""Runs conformance/scenarios.json against the Python wheel, like tests/conformance.rs
+does against the Rust client. Needs CHIPA_MOCK_SERVER pointing at a built
+chipa-mock-server."""
+
+import asyncio
+import json
+from pathlib import Path
+
+import pytest
+
+from chipa_license_validator import LicenseClient, LicenseValidationError
+from mock_server import MOCK_SERVER, mock_server as start_mock_server
+
+SCENARIOS = Path(__file__).parents[2] / "conformance" / "scenarios.json"
+CASES = json.loads(SCENARIOS.read_text())
+
+pytestmark = pytest.mark.skipif(not MOCK_SERVER, reason="CHIPA_MOCK_SERVER is not set")
+
+
+@pytest.fixture(scope="module")
+def mock_server():
+    needed = {case["scenario"] for case in CASES if "scenario" in case}
+    with start_mock_server(scenarios=needed) as server:
+        yield server
+
+
+async def run(url, licenses, case):
+    client = LicenseClient(url, case["application"])
+    license = licenses[case["scenario"]] if "scenario" in case else case["license"]
+    try:
+        return {"token": await client.validate_license(license)}
+    except LicenseValidationError as e:
+        return {"error": e.kind, "remediation": e.remediation["action"]}
+
+
+@pytest.mark.parametrize("case", CASES, ids=[case["name"] for case in CASES])
+def test_conformance(mock_server, case):
+    url, licenses = mock_server
+    assert asyncio.run(run(url, licenses, case)) == case["expect"]
@@ -0,0 +1,58 @@
+"""Validates from forked children, like a pre-fork server's workers do."""
+
+import asyncio
+import os
+
+import pytest
+
+from chipa_license_validator import LicenseClient
+from mock_server import MOCK_SERVER, mock_server
+
+pytestmark = [
+    pytest.mark.skipif(not MOCK_SERVER, reason="CHIPA_MOCK_SERVER is not set"),
+    pytest.mark.skipif(not hasattr(os, "fork"), reason="needs os.fork"),
+    # The parent's runtime threads are exactly what the child must not depend on.
+    pytest.mark.filterwarnings("ignore:.*use of fork\\(\\) may lead to deadlocks"),
+]
+
+
+def in_child(run):
+    """Runs `run` in a forked child and returns its exit code, 0 if `run` returned
+    True."""
+    pid = os.fork()
+    if pid == 0:
+        code = 1
+        try:
+            code = 0 if run() else 1
+        finally:
+            os._exit(code)
+    _, status = os.waitpid(pid, 0)
+    return os.waitstatus_to_exitcode(status)
+
+
+def test_validate_in_parent_then_child():
+    with mock_server() as (url, licenses):
+        license = licenses["valid"]
+
+        def validate():
+            client = LicenseClient(url, "my-app")
+            return asyncio.run(client.validate_license(license)) == "mock-token"
+
+        assert validate()
+        assert in_child(validate) == 0
+        assert validate()
+
+
+def test_client_from_before_fork_raises():
+    with mock_server() as (url, licenses):
+        client = LicenseClient(url, "my-app")
+        assert asyncio.run(client.validate_license(licenses["valid"])) == "mock-token"
+
+        def reuse():
+            try:
+                asyncio.run(client.validate_license(licenses["valid"]))
+            except RuntimeError as e:
+                return "create a new client after forking" in str(e)
+            return False
+
+        assert in_child(reuse) == 0
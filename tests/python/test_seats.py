@@ -0,0 +1,67 @@
+"""Seat activations and transfers against a mock server whose licenses allow a single
+seat."""
+
+import asyncio
+
+import pytest
+
+from chipa_license_validator import (
+    LicenseClient,
+    SeatLimitReachedError,
+    TransferCooldownError,
+    UnknownMachineError,
+)
+from mock_server import MOCK_SERVER, mock_server
+
+pytestmark = pytest.mark.skipif(not MOCK_SERVER, reason="CHIPA_MOCK_SERVER is not set")
+
+
+async def free_stale_seat(url, license):
+    old = LicenseClient(url, "my-app", machine_label="Old laptop")
+    seat = await old.activate_license(license, machine_id="old")
+
+    new = LicenseClient(url, "my-app", machine_label="New laptop")
+    with pytest.raises(SeatLimitReachedError) as raised:
+        await new.activate_license(license, machine_id="new")
+    [held] = raised.value.seats
+    assert held.id == seat.activation_id
+    assert held.machine_label == "Old laptop"
+    assert held.last_seen > 0
+
+    await new.deactivate_activation(license, held.id)
+    await new.activate_license(license, machine_id="new")
+    with pytest.raises(UnknownMachineError):
+        await new.deactivate_activation(license, held.id)
+
+
+def test_free_stale_seat():
+    with mock_server("--seats", "1") as (url, licenses):
+        asyncio.run(free_stale_seat(url, licenses["valid"]))
+
+
+def test_deactivate_activation_rejects_malformed_id():
+    client = LicenseClient("http://127.0.0.1:1", "my-app")
+    with pytest.raises(ValueError):
+        client.deactivate_activation("550e8400-e29b-41d4-a716-446655440000", "stale")
+
+
+async def transfer(url, license):
+    client = LicenseClient(url, "my-app")
+    old = await client.activate_license(license, machine_id="old")
+    new = await client.transfer_license(license, old.activation_id, machine_id="new")
+    with pytest.raises(UnknownMachineError):
+        await client.heartbeat(license, old.token)
+
+    forced = await client.transfer_license(
+        license, new.activation_id, machine_id="third", force=True
+    )
+    with pytest.raises(TransferCooldownError) as raised:
+        await client.transfer_license(
+            license, forced.activation_id, machine_id="fourth", force=True
+        )
+    assert raised.value.remediation["action"] == "retry_after"
+
+
+def test_transfer_license():
+    with mock_server("--seats", "1") as (url, licenses):
+        asyncio.run(transfer(url, licenses["valid"]))
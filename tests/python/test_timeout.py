@@ -0,0 +1,22 @@
+"""The `timeout` of the async methods, against a mock server that answers slowly."""
+
+import asyncio
+import time
+
+import pytest
+
+from chipa_license_validator import LicenseClient, ValidationTimeoutError
+from mock_server import MOCK_SERVER, mock_server
+
+pytestmark = pytest.mark.skipif(not MOCK_SERVER, reason="CHIPA_MOCK_SERVER is not set")
+
+
+def test_validate_license_times_out():
+    with mock_server("--latency", "2000") as (url, licenses):
+        client = LicenseClient(url, "my-app")
+        started = time.monotonic()
+        with pytest.raises(ValidationTimeoutError) as raised:
+            asyncio.run(client.validate_license(licenses["valid"], timeout=0.2))
+        elapsed = time.monotonic() - started
+        assert 0.2 <= elapsed < 1.0
+        assert raised.value.remediation["action"] == "check_internet"
@@ -0,0 +1,63 @@
+#![cfg(feature = "fs")]
+
+use std::{path::Path, process::Command};
+
+use chipa_license_validator::{ChipaError, ChipaFile, Version};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+// The test binary runs itself twice with one of these roles, so that the vendor and
+// the consumer share nothing but the sealed file.
+const ROLE: &str = "CHIPA_SEALED_ROLE";
+const SEALED_PATH: &str = "CHIPA_SEALED_PATH";
+const LICENSE: Uuid = Uuid::from_u128(0x5ea1_ed00_0000_4000_8000_0000_0000_0001);
+const TOKEN: &str = "issued-token";
+
+fn vendor(path: &str) {
+    let payload = json!({ "feature": "pro", "seats": 3 });
+    ChipaFile::seal_for_license(Version::LATEST, &payload, LICENSE, TOKEN)
+        .unwrap()
+        .save_sealed(path)
+        .unwrap();
+}
+
+fn consumer(path: &str) {
+    let payload: Value = ChipaFile::open_sealed(path, LICENSE, TOKEN).unwrap();
+    assert_eq!(payload, json!({ "feature": "pro", "seats": 3 }));
+    assert!(matches!(
+        ChipaFile::open_sealed::<Value>(path, LICENSE, "other-token"),
+        Err(ChipaError::Tampered(_))
+    ));
+    assert!(matches!(
+        ChipaFile::open_sealed::<Value>(path, Uuid::nil(), TOKEN),
+        Err(ChipaError::WrongLicense { .. })
+    ));
+}
+
+fn run_as(role: &str, path: &Path) {
+    let status = Command::new(std::env::current_exe().unwrap())
+        .args(["--exact", "test_sealed_across_processes", "--nocapture"])
+        .env(ROLE, role)
+        .env(SEALED_PATH, path)
+        .status()
+        .unwrap();
+    assert!(status.success(), "the {} process failed", role);
+}
+
+#[test]
+fn test_sealed_across_processes() {
+    if let Ok(role) = std::env::var(ROLE) {
+        let path = std::env::var(SEALED_PATH).unwrap();
+        match role.as_str() {
+            "vendor" => vendor(&path),
+            "consumer" => consumer(&path),
+            _ => panic!("Unknown role '{}'", role),
+        }
+        return;
+    }
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("entitlements.chipa");
+    run_as("vendor", &path);
+    assert!(path.exists());
+    run_as("consumer", &path);
+}